@@ -18,12 +18,27 @@ use std::time::{Duration, Instant};
 /// Index into the [Vec] of threads. Thread 0 is always the main thread.
 pub type ThreadID = usize;
 
+/// Something a thread can be blocked waiting for, see [Environment::block].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ThreadBlock {
+    Semaphore(mem::MutPtr<libc::semaphore::sem_t>),
+    Mutex(mem::MutPtr<libc::pthread::mutex::pthread_mutex_t>),
+    Condition(mem::MutPtr<libc::pthread::cond::pthread_cond_t>),
+    /// Waiting for a thread to finish.
+    Join(ThreadID),
+}
+
 /// Bookkeeping for a thread.
 pub struct Thread {
     /// Once a thread finishes, this is set to false.
     pub active: bool,
     /// If this is not [None], the thread is sleeping until the specified time.
+    /// For a blocked thread, this is the timeout.
     sleeping_until: Option<Instant>,
+    /// If this is not [None], the thread is blocked waiting on the specified
+    /// thing, and won't run until it is woken by [Environment::unblock] (or
+    /// `sleeping_until` passes).
+    blocked_by: Option<ThreadBlock>,
     /// Set to [true] when a thread is running its startup routine (i.e. the
     /// function pointer passed to `pthread_create`). When it returns to the
     /// host, it should become inactive.
@@ -190,6 +205,7 @@ impl Environment {
         let main_thread = Thread {
             active: true,
             sleeping_until: None,
+            blocked_by: None,
             in_start_routine: false, // main thread never terminates
             in_host_function: false,
             context: None,
@@ -335,6 +351,7 @@ impl Environment {
         self.threads.push(Thread {
             active: true,
             sleeping_until: None,
+            blocked_by: None,
             in_start_routine: true,
             in_host_function: false,
             context: Some(cpu::CpuContext::new()),
@@ -382,6 +399,43 @@ impl Environment {
         self.cpu.branch(old_pc);
     }
 
+    /// Block the current thread until another thread wakes it up with
+    /// [Self::unblock], or until `timeout` (if any) passes, running other
+    /// threads in the meantime.
+    ///
+    /// This only handles the scheduling: keeping track of the state of the
+    /// semaphore/mutex/etc and which threads are waiting on it is the caller's
+    /// job. That includes working out whether the wait timed out.
+    pub fn block(&mut self, block: ThreadBlock, timeout: Option<Instant>) {
+        assert!(self.threads[self.current_thread].sleeping_until.is_none());
+        assert!(self.threads[self.current_thread].blocked_by.is_none());
+
+        log_dbg!(
+            "Thread {} is blocking on {:?} (timeout: {:?}).",
+            self.current_thread,
+            block,
+            timeout
+        );
+        self.threads[self.current_thread].blocked_by = Some(block);
+        self.threads[self.current_thread].sleeping_until = timeout;
+
+        let old_pc = self.cpu.pc_with_thumb_bit();
+        self.cpu.branch(self.dyld.return_to_host_routine());
+        // Since the current thread is blocked, this will only run other threads
+        // until it is woken up, at which point it signals return-to-host and
+        // control is returned to this function.
+        self.run_call();
+        self.cpu.branch(old_pc);
+    }
+
+    /// Wake up a thread that is blocked by [Self::block] on `block`.
+    pub fn unblock(&mut self, thread: ThreadID, block: ThreadBlock) {
+        assert_eq!(self.threads[thread].blocked_by, Some(block));
+        log_dbg!("Thread {} was woken by {:?}.", thread, block);
+        self.threads[thread].blocked_by = None;
+        self.threads[thread].sleeping_until = None;
+    }
+
     /// Run the emulator. This is the main loop and won't return until app exit.
     /// Only `main.rs` should call this.
     pub fn run(&mut self) {
@@ -487,6 +541,10 @@ impl Environment {
                         // Secondary thread finished starting
                         // TODO: Having two meanings for this SVC is
                         // dangerous, use a different SVC for this case.
+                        let thread = self.current_thread;
+                        let return_value = mem::Ptr::from_bits(self.cpu.regs()[0]);
+                        libc::pthread::key::thread_exiting(self, thread);
+                        libc::pthread::thread::thread_exited(self, thread, return_value);
                         log_dbg!(
                             "Thread {} finished start routine and became inactive",
                             self.current_thread
@@ -496,6 +554,7 @@ impl Environment {
                         let stack: mem::MutVoidPtr = mem::Ptr::from_bits(*stack.start());
                         log_dbg!("Freeing thread {} stack {:?}", self.current_thread, stack);
                         self.mem.free(stack);
+                        libc::errno::thread_exited(self, thread);
                         return ThreadNextAction::Yield;
                     } else {
                         panic!("Unexpected return-to-host!");
@@ -530,14 +589,16 @@ impl Environment {
             // large so we aren't jumping in and out of dynarmic or trying to
             // poll for events too often. At the same time, very large values
             // are bad for responsiveness.
-            let mut ticks = if self.threads[self.current_thread].sleeping_until.is_some() {
-                // The current thread might be asleep, in which case we want to
-                // immediately switch to another thread. This only happens when
-                // called from Self::sleep().
-                0
-            } else {
-                100_000
-            };
+            let current_thread = &self.threads[self.current_thread];
+            let mut ticks =
+                if current_thread.sleeping_until.is_some() || current_thread.blocked_by.is_some() {
+                    // The current thread might be asleep or blocked, in which case
+                    // we want to immediately switch to another thread. This only
+                    // happens when called from Self::sleep() or Self::block().
+                    0
+                } else {
+                    100_000
+                };
             let mut step_and_debug = false;
             while ticks > 0 {
                 let state = self.cpu.run_or_step(
@@ -591,6 +652,9 @@ impl Environment {
                         if sleeping_until <= Instant::now() {
                             log_dbg!("Thread {} finished sleeping.", i);
                             candidate.sleeping_until = None;
+                            if let Some(block) = candidate.blocked_by.take() {
+                                log_dbg!("Thread {} timed out waiting on {:?}.", i, block);
+                            }
                         } else {
                             next_awakening = match next_awakening {
                                 None => Some(sleeping_until),
@@ -600,6 +664,10 @@ impl Environment {
                         }
                     }
 
+                    if candidate.blocked_by.is_some() {
+                        continue;
+                    }
+
                     suitable_thread = Some(i);
                     break;
                 }
@@ -619,6 +687,8 @@ impl Environment {
                     // Try again, there should be some thread awake now (or
                     // there will be soon, since timing is approximate).
                     continue;
+                } else if self.threads.iter().any(|t| t.blocked_by.is_some()) {
+                    panic!("All threads are blocked, deadlock!");
                 } else {
                     // This should never happen!
                     panic!("No active threads?!");
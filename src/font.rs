@@ -252,3 +252,59 @@ impl Font {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Get the width and height of the area covered when drawing `text`.
+    fn drawn_size(font: &Font, font_size: f32, text: &str) -> (i32, i32) {
+        let mut covered = Vec::new();
+        let put_pixel = |p, coverage: f32| {
+            if coverage > 0.0 {
+                covered.push(p);
+            }
+        };
+        font.draw(
+            font_size,
+            text,
+            (0.0, 0.0),
+            None,
+            TextAlignment::Left,
+            put_pixel,
+        );
+        let xs = covered.iter().map(|&(x, _)| x);
+        let ys = covered.iter().map(|&(_, y)| y);
+        (
+            xs.clone().max().unwrap() - xs.min().unwrap(),
+            ys.clone().max().unwrap() - ys.min().unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_draw_font_size() {
+        // UITextView etc pass their font's size through to here.
+        let font = Font::sans_regular();
+        let small = drawn_size(&font, 12.0, "Hi");
+        let large = drawn_size(&font, 24.0, "Hi");
+        assert!(small.0 > 0 && small.1 > 0);
+        assert!(large.0 > small.0 && large.1 > small.1);
+    }
+
+    #[test]
+    fn test_text_height_grows_with_lines() {
+        // UITextView's content size is based on this.
+        let font = Font::sans_regular();
+        let wrap = Some((100.0, WrapMode::Word));
+        let (_, one_line) = font.calculate_text_size(12.0, "one", wrap);
+        let (_, two_lines) = font.calculate_text_size(12.0, "one\ntwo", wrap);
+        assert!(two_lines > one_line);
+        // Wrapping long lines also makes the text taller.
+        let text = "the quick brown fox jumps over the lazy dog";
+        let (wide_width, wide_height) = font.calculate_text_size(12.0, text, None);
+        let (width, height) = font.calculate_text_size(12.0, text, wrap);
+        assert!(wide_width > 100.0);
+        assert!(width <= 100.0);
+        assert!(height > wide_height);
+    }
+}
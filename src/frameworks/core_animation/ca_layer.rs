@@ -5,9 +5,157 @@
  */
 //! `CALayer`.
 
+use crate::dyld::{ConstantExports, HostConstant};
 use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::{get_static_str, to_rust_string};
 use crate::objc::{id, msg, nil, objc_classes, release, retain, ClassExports, HostObject};
 
+pub const kCAGravityCenter: &str = "center";
+pub const kCAGravityTop: &str = "top";
+pub const kCAGravityBottom: &str = "bottom";
+pub const kCAGravityLeft: &str = "left";
+pub const kCAGravityRight: &str = "right";
+pub const kCAGravityTopLeft: &str = "topLeft";
+pub const kCAGravityTopRight: &str = "topRight";
+pub const kCAGravityBottomLeft: &str = "bottomLeft";
+pub const kCAGravityBottomRight: &str = "bottomRight";
+pub const kCAGravityResize: &str = "resize";
+pub const kCAGravityResizeAspect: &str = "resizeAspect";
+pub const kCAGravityResizeAspectFill: &str = "resizeAspectFill";
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCAGravityCenter",
+        HostConstant::NSString(kCAGravityCenter),
+    ),
+    ("_kCAGravityTop", HostConstant::NSString(kCAGravityTop)),
+    (
+        "_kCAGravityBottom",
+        HostConstant::NSString(kCAGravityBottom),
+    ),
+    ("_kCAGravityLeft", HostConstant::NSString(kCAGravityLeft)),
+    ("_kCAGravityRight", HostConstant::NSString(kCAGravityRight)),
+    (
+        "_kCAGravityTopLeft",
+        HostConstant::NSString(kCAGravityTopLeft),
+    ),
+    (
+        "_kCAGravityTopRight",
+        HostConstant::NSString(kCAGravityTopRight),
+    ),
+    (
+        "_kCAGravityBottomLeft",
+        HostConstant::NSString(kCAGravityBottomLeft),
+    ),
+    (
+        "_kCAGravityBottomRight",
+        HostConstant::NSString(kCAGravityBottomRight),
+    ),
+    (
+        "_kCAGravityResize",
+        HostConstant::NSString(kCAGravityResize),
+    ),
+    (
+        "_kCAGravityResizeAspect",
+        HostConstant::NSString(kCAGravityResizeAspect),
+    ),
+    (
+        "_kCAGravityResizeAspectFill",
+        HostConstant::NSString(kCAGravityResizeAspectFill),
+    ),
+];
+
+/// Parsed form of the `contentsGravity` property.
+///
+/// Apple's header notes that "bottom" always means minimum Y and "top" always
+/// means maximum Y. Y points down on iPhone OS, so "top" is the bottom of the
+/// screen!
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(super) enum ContentsGravity {
+    Center,
+    Top,
+    Bottom,
+    Left,
+    Right,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+    #[default]
+    Resize,
+    ResizeAspect,
+    ResizeAspectFill,
+}
+const GRAVITY_NAMES: &[(ContentsGravity, &str)] = &[
+    (ContentsGravity::Center, kCAGravityCenter),
+    (ContentsGravity::Top, kCAGravityTop),
+    (ContentsGravity::Bottom, kCAGravityBottom),
+    (ContentsGravity::Left, kCAGravityLeft),
+    (ContentsGravity::Right, kCAGravityRight),
+    (ContentsGravity::TopLeft, kCAGravityTopLeft),
+    (ContentsGravity::TopRight, kCAGravityTopRight),
+    (ContentsGravity::BottomLeft, kCAGravityBottomLeft),
+    (ContentsGravity::BottomRight, kCAGravityBottomRight),
+    (ContentsGravity::Resize, kCAGravityResize),
+    (ContentsGravity::ResizeAspect, kCAGravityResizeAspect),
+    (
+        ContentsGravity::ResizeAspectFill,
+        kCAGravityResizeAspectFill,
+    ),
+];
+impl ContentsGravity {
+    fn from_name(name: &str) -> Option<Self> {
+        GRAVITY_NAMES
+            .iter()
+            .find(|&&(_, n)| n == name)
+            .map(|&(gravity, _)| gravity)
+    }
+    fn name(self) -> &'static str {
+        GRAVITY_NAMES.iter().find(|&&(g, _)| g == self).unwrap().1
+    }
+
+    /// Get the rect that contents of size `contents_size` should be drawn in,
+    /// for a layer occupying `frame`. The result may extend outside `frame`.
+    pub(super) fn contents_rect(self, contents_size: CGSize, frame: CGRect) -> CGRect {
+        use ContentsGravity::*;
+
+        if contents_size.width <= 0.0 || contents_size.height <= 0.0 {
+            return frame;
+        }
+        let size = match self {
+            Resize => return frame,
+            ResizeAspect | ResizeAspectFill => {
+                let scale_x = frame.size.width / contents_size.width;
+                let scale_y = frame.size.height / contents_size.height;
+                let scale = if self == ResizeAspect {
+                    scale_x.min(scale_y)
+                } else {
+                    scale_x.max(scale_y)
+                };
+                CGSize {
+                    width: contents_size.width * scale,
+                    height: contents_size.height * scale,
+                }
+            }
+            _ => contents_size,
+        };
+        let x = match self {
+            Left | TopLeft | BottomLeft => frame.origin.x,
+            Right | TopRight | BottomRight => frame.origin.x + frame.size.width - size.width,
+            _ => frame.origin.x + (frame.size.width - size.width) / 2.0,
+        };
+        let y = match self {
+            Bottom | BottomLeft | BottomRight => frame.origin.y,
+            Top | TopLeft | TopRight => frame.origin.y + frame.size.height - size.height,
+            _ => frame.origin.y + (frame.size.height - size.height) / 2.0,
+        };
+        CGRect {
+            origin: CGPoint { x, y },
+            size,
+        }
+    }
+}
+
 pub(super) struct CALayerHostObject {
     /// Possibly nil, usually a UIView. This is a weak reference.
     delegate: id,
@@ -24,6 +172,7 @@ pub(super) struct CALayerHostObject {
     pub(super) background_color: id,
     /// `CGImageRef*`
     pub(super) contents: id,
+    pub(super) contents_gravity: ContentsGravity,
     /// For CAEAGLLayer only
     pub(super) drawable_properties: id,
     /// For CAEAGLLayer only (internal state for compositor)
@@ -57,6 +206,7 @@ pub const CLASSES: ClassExports = objc_classes! {
         opacity: 1.0,
         background_color: nil, // transparency
         contents: nil,
+        contents_gravity: ContentsGravity::Resize,
         drawable_properties: nil,
         presented_pixels: None,
         gles_texture: None,
@@ -224,8 +374,71 @@ pub const CLASSES: ClassExports = objc_classes! {
     release(env, old_contents);
 }
 
+- (id)contentsGravity {
+    let gravity = env.objc.borrow::<CALayerHostObject>(this).contents_gravity;
+    get_static_str(env, gravity.name())
+}
+- (())setContentsGravity:(id)gravity { // NSString*
+    let name = to_rust_string(env, gravity);
+    let Some(gravity) = ContentsGravity::from_name(&name) else {
+        log!("Warning: unknown contentsGravity {:?} for layer {:?}, ignoring", name, this);
+        return;
+    };
+    env.objc.borrow_mut::<CALayerHostObject>(this).contents_gravity = gravity;
+}
+
 // TODO: more
 
 @end
 
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contents_rect_aspect_fit() {
+        let frame = CGRect {
+            origin: CGPoint { x: 10.0, y: 20.0 },
+            size: CGSize {
+                width: 100.0,
+                height: 100.0,
+            },
+        };
+        let wide = CGSize {
+            width: 200.0,
+            height: 100.0,
+        };
+        assert_eq!(
+            ContentsGravity::ResizeAspect.contents_rect(wide, frame),
+            CGRect {
+                origin: CGPoint { x: 10.0, y: 45.0 },
+                size: CGSize {
+                    width: 100.0,
+                    height: 50.0
+                },
+            }
+        );
+        assert_eq!(
+            ContentsGravity::ResizeAspectFill.contents_rect(wide, frame),
+            CGRect {
+                origin: CGPoint { x: -40.0, y: 20.0 },
+                size: CGSize {
+                    width: 200.0,
+                    height: 100.0
+                },
+            }
+        );
+        assert_eq!(ContentsGravity::Resize.contents_rect(wide, frame), frame);
+    }
+
+    #[test]
+    fn test_gravity_names() {
+        for &(gravity, name) in GRAVITY_NAMES {
+            assert_eq!(ContentsGravity::from_name(name), Some(gravity));
+            assert_eq!(gravity.name(), name);
+        }
+        assert_eq!(ContentsGravity::from_name("sideways"), None);
+    }
+}
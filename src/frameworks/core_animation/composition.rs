@@ -307,6 +307,21 @@ unsafe fn composite_layer_recursive(
     // re-borrow immutably
     let host_obj = objc.borrow::<CALayerHostObject>(layer);
 
+    // Contents may be drawn larger or smaller than the layer, depending on
+    // the gravity. The layer's clipping still applies.
+    let contents_frame = if host_obj.contents != nil {
+        let (width, height) = cg_image::borrow_image(objc, host_obj.contents).dimensions();
+        let contents_size = CGSize {
+            width: width as CGFloat,
+            height: height as CGFloat,
+        };
+        host_obj
+            .contents_gravity
+            .contents_rect(contents_size, absolute_frame)
+    } else {
+        absolute_frame_clipped
+    };
+
     // Update texture with CGImageRef pixels, if any
     if need_update && host_obj.contents != nil {
         let image = cg_image::borrow_image(objc, host_obj.contents);
@@ -333,8 +348,10 @@ unsafe fn composite_layer_recursive(
             gles.BlendFunc(gles11::ONE, gles11::ONE_MINUS_SRC_ALPHA);
         }
 
-        let (x, y, w, h) = gl_rect_from_cg_rect(absolute_frame_clipped, scale_hack, fb_height);
+        let clipped = clip_rects(absolute_frame_clipped, contents_frame);
+        let (x, y, w, h) = gl_rect_from_cg_rect(clipped, scale_hack, fb_height);
         gles.Scissor(x, y, w, h);
+        let (x, y, w, h) = gl_rect_from_cg_rect(contents_frame, scale_hack, fb_height);
         gles.Viewport(x, y, w, h);
 
         gles.BindBuffer(gles11::ARRAY_BUFFER, 0);
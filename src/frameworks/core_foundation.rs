@@ -20,8 +20,10 @@ pub mod cf_allocator;
 pub mod cf_array;
 pub mod cf_bundle;
 pub mod cf_data;
+pub mod cf_date_formatter;
 pub mod cf_run_loop;
 pub mod cf_string;
+pub mod cf_time_zone;
 pub mod cf_type;
 pub mod cf_url;
 pub mod time;
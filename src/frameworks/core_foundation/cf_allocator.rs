@@ -7,10 +7,21 @@
 
 use super::CFTypeRef;
 use crate::dyld::{ConstantExports, HostConstant};
-use crate::mem::Ptr;
+use crate::mem::{MutVoidPtr, Ptr};
 
 pub type CFAllocatorRef = CFTypeRef;
 
 pub const kCFAllocatorDefault: CFAllocatorRef = Ptr::null();
 
-pub const CONSTANTS: ConstantExports = &[("_kCFAllocatorDefault", HostConstant::NullPtr)];
+pub const CONSTANTS: ConstantExports = &[
+    ("_kCFAllocatorDefault", HostConstant::NullPtr),
+    // kCFAllocatorNull never allocates or frees anything, so it only needs to
+    // be distinct from kCFAllocatorDefault.
+    (
+        "_kCFAllocatorNull",
+        HostConstant::Custom(|mem| {
+            let allocator: MutVoidPtr = mem.alloc(4);
+            mem.alloc_and_write(allocator).cast().cast_const()
+        }),
+    ),
+];
@@ -4,20 +4,90 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
 //! `CFData.h`
+//!
+//! This is toll-free bridged to `NSData` in Apple's implementation. Here it
+//! is the same type, except for `CGImage` data providers (see
+//! `cg_data_provider.rs`), which are also accepted by the getters.
 
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
 use crate::dyld::FunctionExports;
 use crate::export_c_func;
 use crate::frameworks::core_foundation::{CFIndex, CFRange, CFTypeRef};
 use crate::frameworks::core_graphics::cg_image::borrow_image;
-use crate::mem::MutPtr;
-use crate::objc::ObjC;
+use crate::frameworks::foundation::NSUInteger;
+use crate::mem::{ConstPtr, ConstVoidPtr, GuestUSize, MutPtr, MutVoidPtr};
+use crate::objc::{id, msg, msg_class, ObjC};
 use crate::Environment;
 
 pub type CFDataRef = CFTypeRef;
+pub type CFMutableDataRef = CFTypeRef;
+
+fn CFDataCreateWithBytesNoCopy(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    bytes: ConstPtr<u8>,
+    length: CFIndex,
+    bytes_deallocator: CFAllocatorRef,
+) -> CFDataRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    let bytes: MutVoidPtr = bytes.cast_mut().cast();
+    let length: NSUInteger = length.try_into().unwrap();
+    let new: id = msg_class![env; NSData alloc];
+    if can_take_bytes(bytes_deallocator) {
+        msg![env; new initWithBytesNoCopy:bytes length:length]
+    } else {
+        msg![env; new initWithBytes:bytes length:length]
+    }
+}
+
+/// NSData frees its bytes with the default allocator when it's deallocated, so
+/// it can take ownership of bytes only if they were to be freed that way.
+/// Otherwise (e.g. for `kCFAllocatorNull`) the bytes must not be freed by us,
+/// so they have to be copied.
+/// TODO: call custom deallocators once there are custom allocators
+fn can_take_bytes(bytes_deallocator: CFAllocatorRef) -> bool {
+    bytes_deallocator == kCFAllocatorDefault
+}
+
+fn CFDataCreateMutable(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    capacity: CFIndex,
+) -> CFMutableDataRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+
+    // The capacity is a maximum size, or 0 for no limit. Since it's only a
+    // limit, it is safe to ignore it.
+    log_dbg!("CFDataCreateMutable() ignoring capacity {}", capacity);
+    let capacity: NSUInteger = 0;
+    let new: id = msg_class![env; NSMutableData alloc];
+    msg![env; new initWithCapacity:capacity]
+}
+
+fn CFDataAppendBytes(
+    env: &mut Environment,
+    data: CFMutableDataRef,
+    bytes: ConstPtr<u8>,
+    length: CFIndex,
+) {
+    let length: NSUInteger = length.try_into().unwrap();
+    let bytes: ConstVoidPtr = bytes.cast();
+    () = msg![env; data appendBytes:bytes length:length];
+}
+
+fn CFDataGetBytePtr(env: &mut Environment, data: CFDataRef) -> ConstPtr<u8> {
+    // TODO: CGImage data providers don't have their bytes in guest memory
+    assert!(!is_cgimage(env, data));
+    let bytes: ConstVoidPtr = msg![env; data bytes];
+    bytes.cast()
+}
 
 fn CFDataGetLength(env: &mut Environment, data: CFDataRef) -> CFIndex {
-    // TODO: actually support general CFDataRef :p
-    assert_cgimage(env, data);
+    if !is_cgimage(env, data) {
+        let length: NSUInteger = msg![env; data length];
+        return length.try_into().unwrap();
+    }
 
     borrow_image(&env.objc, data)
         .pixels()
@@ -27,26 +97,85 @@ fn CFDataGetLength(env: &mut Environment, data: CFDataRef) -> CFIndex {
 }
 
 fn CFDataGetBytes(env: &mut Environment, data: CFDataRef, range: CFRange, buffer: MutPtr<u8>) {
-    // TODO: actually support general CFDataRef :p
-    assert_cgimage(env, data);
+    let length: GuestUSize = CFDataGetLength(env, data).try_into().unwrap();
+    let Some((location, count)) = range_within(range, length) else {
+        log!(
+            "Warning: CFDataGetBytes() with range {:?} outside data of length {}",
+            range,
+            length
+        );
+        return;
+    };
+    if count == 0 {
+        return;
+    }
 
-    let src_pixels = borrow_image(&env.objc, data).pixels();
-    let len = src_pixels.len().try_into().unwrap();
-    // TODO: respect range
-    assert_eq!(len, range.length as u32);
-    let _ = &env
-        .mem
-        .bytes_at_mut(buffer, len)
-        .copy_from_slice(src_pixels);
+    let src: Vec<u8> = if is_cgimage(env, data) {
+        let src_pixels = borrow_image(&env.objc, data).pixels();
+        src_pixels[location as usize..][..count as usize].to_vec()
+    } else {
+        let bytes: ConstVoidPtr = msg![env; data bytes];
+        env.mem
+            .bytes_at(bytes.cast::<u8>() + location, count)
+            .to_vec()
+    };
+    match env.mem.checked_bytes_at_mut(buffer, count) {
+        Ok(dest) => dest.copy_from_slice(&src),
+        Err(err) => log!("Warning: CFDataGetBytes() with bad buffer: {}", err),
+    }
 }
 
-fn assert_cgimage(env: &mut Environment, data: CFDataRef) {
+/// Converts `range` to a location and count, if it lies within data of
+/// `length` bytes.
+fn range_within(range: CFRange, length: GuestUSize) -> Option<(GuestUSize, GuestUSize)> {
+    let location: GuestUSize = range.location.try_into().ok()?;
+    let count: GuestUSize = range.length.try_into().ok()?;
+    if location.checked_add(count)? > length {
+        return None;
+    }
+    Some((location, count))
+}
+
+fn is_cgimage(env: &mut Environment, data: CFDataRef) -> bool {
     let data_class = ObjC::read_isa(data, &env.mem);
     let cgimage_class = env.objc.get_known_class("_touchHLE_CGImage", &mut env.mem);
-    assert!(env.objc.class_is_subclass_of(data_class, cgimage_class));
+    env.objc.class_is_subclass_of(data_class, cgimage_class)
 }
 
 pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFDataCreateWithBytesNoCopy(_, _, _, _)),
+    export_c_func!(CFDataCreateMutable(_, _)),
+    export_c_func!(CFDataAppendBytes(_, _, _)),
+    export_c_func!(CFDataGetBytePtr(_)),
     export_c_func!(CFDataGetLength(_)),
     export_c_func!(CFDataGetBytes(_, _, _)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::{can_take_bytes, kCFAllocatorDefault, range_within};
+    use crate::frameworks::core_foundation::CFRange;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_can_take_bytes() {
+        assert!(can_take_bytes(kCFAllocatorDefault));
+        // Stand-in for kCFAllocatorNull, which is some non-NULL pointer.
+        assert!(!can_take_bytes(Ptr::from_bits(0x1000)));
+    }
+
+    fn range(location: i32, length: i32) -> CFRange {
+        CFRange { location, length }
+    }
+
+    #[test]
+    fn test_range_within() {
+        assert_eq!(range_within(range(0, 4), 4), Some((0, 4)));
+        assert_eq!(range_within(range(1, 2), 4), Some((1, 2)));
+        assert_eq!(range_within(range(4, 0), 4), Some((4, 0)));
+        assert_eq!(range_within(range(3, 2), 4), None);
+        assert_eq!(range_within(range(5, 0), 4), None);
+        assert_eq!(range_within(range(-1, 2), 4), None);
+        assert_eq!(range_within(range(0, -1), 4), None);
+    }
+}
@@ -0,0 +1,77 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFDateFormatter`.
+//!
+//! Only ISO 8601 output is supported for now: the styles and locale passed to
+//! `CFDateFormatterCreate` are ignored.
+
+use super::cf_allocator::CFAllocatorRef;
+use super::cf_string::CFStringRef;
+use super::time::{absolute_time_to_gregorian_date, cf_gregorian_to_iso8601, CFAbsoluteTime};
+use super::{CFIndex, CFTypeRef};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::foundation::ns_string::from_rust_string;
+use crate::objc::{objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFDateFormatter is a CFType-based type, but in our implementation those are
+// just Objective-C types, so we need a class for it, but its name is not
+// visible anywhere.
+@implementation _touchHLE_CFDateFormatter: NSObject
+@end
+
+};
+
+struct CFDateFormatterHostObject;
+impl HostObject for CFDateFormatterHostObject {}
+
+pub type CFDateFormatterRef = CFTypeRef;
+pub type CFLocaleRef = CFTypeRef;
+pub type CFDateFormatterStyle = CFIndex;
+
+fn CFDateFormatterCreate(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    locale: CFLocaleRef,
+    date_style: CFDateFormatterStyle,
+    time_style: CFDateFormatterStyle,
+) -> CFDateFormatterRef {
+    log!(
+        "TODO: CFDateFormatterCreate({:?}, {}, {}) ignoring locale and styles, will use ISO 8601",
+        locale,
+        date_style,
+        time_style
+    );
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CFDateFormatter", &mut env.mem);
+    env.objc
+        .alloc_object(isa, Box::new(CFDateFormatterHostObject), &mut env.mem)
+}
+
+fn CFDateFormatterCreateStringWithAbsoluteTime(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    _formatter: CFDateFormatterRef,
+    at: CFAbsoluteTime,
+) -> CFStringRef {
+    let string = cf_gregorian_to_iso8601(absolute_time_to_gregorian_date(at));
+    log_dbg!(
+        "CFDateFormatterCreateStringWithAbsoluteTime(_, _, {}) => {:?}",
+        at,
+        string
+    );
+    from_rust_string(env, string)
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFDateFormatterCreate(_, _, _, _)),
+    export_c_func!(CFDateFormatterCreateStringWithAbsoluteTime(_, _, _)),
+];
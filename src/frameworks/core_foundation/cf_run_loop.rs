@@ -8,12 +8,32 @@
 //! This is not even toll-free bridged to `NSRunLoop` in Apple's implementation,
 //! but here it is the same type.
 
+use super::cf_allocator::{kCFAllocatorDefault, CFAllocatorRef};
+use super::time::{CFAbsoluteTime, CFAbsoluteTimeGetCurrent, CFTimeInterval};
+use super::CFIndex;
+use crate::abi::GuestFunction;
 use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant};
-use crate::objc::msg_class;
+use crate::frameworks::foundation::ns_timer;
+use crate::mem::{ConstPtr, ConstVoidPtr, MutVoidPtr, SafeRead};
+use crate::objc::{msg, msg_class};
 use crate::Environment;
 
 pub type CFRunLoopRef = super::CFTypeRef;
 pub type CFRunLoopMode = super::cf_string::CFStringRef;
+/// Toll-free bridged to `NSTimer`.
+pub type CFRunLoopTimerRef = super::CFTypeRef;
+type CFOptionFlags = u32;
+
+#[allow(dead_code)]
+#[repr(C, packed)]
+struct CFRunLoopTimerContext {
+    version: CFIndex,
+    info: MutVoidPtr,
+    retain: ConstVoidPtr,
+    release: ConstVoidPtr,
+    copy_description: ConstVoidPtr,
+}
+unsafe impl SafeRead for CFRunLoopTimerContext {}
 
 fn CFRunLoopGetCurrent(env: &mut Environment) -> CFRunLoopRef {
     msg_class![env; NSRunLoop currentRunLoop]
@@ -23,6 +43,49 @@ pub fn CFRunLoopGetMain(env: &mut Environment) -> CFRunLoopRef {
     msg_class![env; NSRunLoop mainRunLoop]
 }
 
+fn CFRunLoopTimerCreate(
+    env: &mut Environment,
+    allocator: CFAllocatorRef,
+    fire_date: CFAbsoluteTime,
+    interval: CFTimeInterval,
+    _flags: CFOptionFlags,
+    _order: CFIndex,
+    // void (*callout)(CFRunLoopTimerRef timer, void *info)
+    callout: GuestFunction,
+    context: ConstPtr<CFRunLoopTimerContext>,
+) -> CFRunLoopTimerRef {
+    assert!(allocator == kCFAllocatorDefault); // unimplemented
+    let info = if context.is_null() {
+        MutVoidPtr::null()
+    } else {
+        let context = env.mem.read(context);
+        assert!(context.version == 0);
+        if !context.retain.is_null() || !context.release.is_null() {
+            log!("TODO: CFRunLoopTimerCreate() context retain/release callbacks (ignored)");
+        }
+        context.info
+    };
+    let delay = fire_date - CFAbsoluteTimeGetCurrent(env);
+    ns_timer::new_cf_timer(env, delay, interval, callout, info)
+}
+
+fn CFRunLoopAddTimer(
+    env: &mut Environment,
+    rl: CFRunLoopRef,
+    timer: CFRunLoopTimerRef,
+    mode: CFRunLoopMode,
+) {
+    msg![env; rl addTimer:timer forMode:mode]
+}
+
+fn CFRunLoopTimerInvalidate(env: &mut Environment, timer: CFRunLoopTimerRef) {
+    msg![env; timer invalidate]
+}
+
+fn CFRunLoopTimerIsValid(env: &mut Environment, timer: CFRunLoopTimerRef) -> bool {
+    msg![env; timer isValid]
+}
+
 pub const kCFRunLoopCommonModes: &str = "kCFRunLoopCommonModes";
 pub const kCFRunLoopDefaultMode: &str = "kCFRunLoopDefaultMode";
 
@@ -40,4 +103,8 @@ pub const CONSTANTS: ConstantExports = &[
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFRunLoopGetCurrent()),
     export_c_func!(CFRunLoopGetMain()),
+    export_c_func!(CFRunLoopTimerCreate(_, _, _, _, _, _, _)),
+    export_c_func!(CFRunLoopAddTimer(_, _, _)),
+    export_c_func!(CFRunLoopTimerInvalidate(_)),
+    export_c_func!(CFRunLoopTimerIsValid(_)),
 ];
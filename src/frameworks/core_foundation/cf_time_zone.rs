@@ -0,0 +1,456 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `CFTimeZone`.
+//!
+//! Time zones are represented by a POSIX `TZ` rule string, like
+//! `EST5EDT,M3.2.0,M11.1.0`, which describes both the standard offset and when
+//! daylight saving time applies. Named zones get their rule from the footer of
+//! the host's TZif file for that zone, so no time zone database is bundled.
+//!
+//! Resources:
+//! - [POSIX description of the `TZ` format](https://pubs.opengroup.org/onlinepubs/9699919799/basedefs/V1_chap08.html#tag_08_03)
+//! - [RFC 8536](https://www.rfc-editor.org/rfc/rfc8536) (TZif), section 3.3 for the footer
+
+use super::cf_allocator::CFAllocatorRef;
+use super::cf_string::CFStringRef;
+use super::time::{
+    days_in_month, kCFAbsoluteTimeIntervalSince1970, CFAbsoluteTime, CFTimeInterval,
+};
+use super::CFTypeRef;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::foundation::ns_string::to_rust_string;
+use crate::objc::{nil, objc_classes, ClassExports, HostObject};
+use crate::Environment;
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CFTimeZone is a CFType-based type, but in our implementation those are just
+// Objective-C types, so we need a class for it, but its name is not visible
+// anywhere.
+// TODO: toll-free bridging with NSTimeZone
+@implementation _touchHLE_CFTimeZone: NSObject
+@end
+
+};
+
+struct CFTimeZoneHostObject {
+    rule: TimeZoneRule,
+}
+impl HostObject for CFTimeZoneHostObject {}
+
+pub type CFTimeZoneRef = CFTypeRef;
+
+/// Parsed form of a POSIX `TZ` rule string. Offsets are in seconds east of
+/// GMT, unlike in the string itself.
+#[derive(Debug, Clone, PartialEq)]
+struct TimeZoneRule {
+    std_offset: i32,
+    dst: Option<DstRule>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DstRule {
+    offset: i32,
+    /// Given in local standard time.
+    start: Transition,
+    /// Given in local daylight saving time.
+    end: Transition,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Transition {
+    date: TransitionDate,
+    /// Seconds after local midnight. This can be negative or more than a day.
+    time: i32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TransitionDate {
+    /// `Mm.w.d`: day `d` (0 is Sunday) of week `w` (5 means the last) of month
+    /// `m`.
+    MonthWeekDay { month: i8, week: i8, weekday: i8 },
+    /// `Jn`: day `n` (1 to 365) of the year, never counting February 29.
+    JulianNoLeap(u16),
+    /// `n`: day `n` (0 to 365) of the year, counting February 29.
+    Julian(u16),
+}
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Days from 1970-01-01 to a date in the proleptic Gregorian calendar.
+/// See Howard Hinnant's `days_from_civil`.
+fn days_from_civil(year: i32, month: i8, day: i8) -> i64 {
+    let year = year as i64 - (month <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Year of a time given in seconds since 1970-01-01. Inverse of
+/// [days_from_civil], but only for the year.
+fn year_of(timestamp: i64) -> i32 {
+    let days = timestamp.div_euclid(SECONDS_PER_DAY) + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153; // 0 is March
+    (year_of_era + era * 400 + (month_index >= 10) as i64) as i32
+}
+
+impl Transition {
+    /// The transition's local time in `year`, as seconds since 1970-01-01.
+    fn local_time_in(&self, year: i32) -> i64 {
+        let days = match self.date {
+            TransitionDate::MonthWeekDay {
+                month,
+                week,
+                weekday,
+            } => {
+                let first = days_from_civil(year, month, 1);
+                // 1970-01-01 was a Thursday.
+                let first_weekday = (first + 4).rem_euclid(7) as i8;
+                let mut day = 1 + (weekday - first_weekday).rem_euclid(7) + (week - 1) * 7;
+                while day > days_in_month(year, month) {
+                    day -= 7;
+                }
+                days_from_civil(year, month, day)
+            }
+            TransitionDate::JulianNoLeap(n) => {
+                let skip_leap_day = n >= 60 && days_in_month(year, 2) == 29;
+                days_from_civil(year, 1, 1) + n as i64 - 1 + skip_leap_day as i64
+            }
+            TransitionDate::Julian(n) => days_from_civil(year, 1, 1) + n as i64,
+        };
+        days * SECONDS_PER_DAY + self.time as i64
+    }
+}
+
+impl TimeZoneRule {
+    fn fixed(offset: i32) -> TimeZoneRule {
+        TimeZoneRule {
+            std_offset: offset,
+            dst: None,
+        }
+    }
+
+    /// Offset from GMT in seconds at `timestamp` (seconds since 1970-01-01).
+    fn offset_at(&self, timestamp: i64) -> i32 {
+        let Some(ref dst) = self.dst else {
+            return self.std_offset;
+        };
+        let year = year_of(timestamp + self.std_offset as i64);
+        let start = dst.start.local_time_in(year) - self.std_offset as i64;
+        let end = dst.end.local_time_in(year) - dst.offset as i64;
+        let in_dst = if start < end {
+            (start..end).contains(&timestamp)
+        } else {
+            // Southern hemisphere: DST spans the new year.
+            !(end..start).contains(&timestamp)
+        };
+        if in_dst {
+            dst.offset
+        } else {
+            self.std_offset
+        }
+    }
+
+    fn parse(rule: &str) -> Option<TimeZoneRule> {
+        let rest = skip_zone_name(rule)?;
+        let (std_offset, rest) = parse_time(rest)?;
+        // POSIX offsets are positive west of GMT.
+        let std_offset = -std_offset;
+        if rest.is_empty() {
+            return Some(TimeZoneRule::fixed(std_offset));
+        }
+
+        let rest = skip_zone_name(rest)?;
+        let (dst_offset, rest) = match rest.chars().next() {
+            Some(',') | None => (std_offset + 60 * 60, rest),
+            _ => {
+                let (offset, rest) = parse_time(rest)?;
+                (-offset, rest)
+            }
+        };
+        // There's no standard default for the rules, but this is the US rule
+        // that glibc uses.
+        let rules = if rest.is_empty() {
+            ",M3.2.0,M11.1.0"
+        } else {
+            rest
+        };
+        let (start, rest) = parse_transition(rules.strip_prefix(',')?)?;
+        let (end, rest) = parse_transition(rest.strip_prefix(',')?)?;
+        if !rest.is_empty() {
+            return None;
+        }
+        Some(TimeZoneRule {
+            std_offset,
+            dst: Some(DstRule {
+                offset: dst_offset,
+                start,
+                end,
+            }),
+        })
+    }
+}
+
+/// Skip the zone abbreviation at the start of a rule, e.g. `EST` or `<+09>`.
+fn skip_zone_name(rule: &str) -> Option<&str> {
+    if let Some(rest) = rule.strip_prefix('<') {
+        let (_name, rest) = rest.split_once('>')?;
+        Some(rest)
+    } else {
+        let len = rule
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rule.len());
+        if len < 3 {
+            return None;
+        }
+        Some(&rule[len..])
+    }
+}
+
+/// Parse an unsigned decimal number at the start of a string.
+fn parse_number(string: &str) -> Option<(i32, &str)> {
+    let len = string
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(string.len());
+    let number = string[..len].parse().ok()?;
+    Some((number, &string[len..]))
+}
+
+/// Parse a `[+|-]hh[:mm[:ss]]` time or offset, returning it in seconds.
+fn parse_time(string: &str) -> Option<(i32, &str)> {
+    let (sign, string) = match string.as_bytes().first() {
+        Some(b'-') => (-1, &string[1..]),
+        Some(b'+') => (1, &string[1..]),
+        _ => (1, string),
+    };
+    let (hours, mut rest) = parse_number(string)?;
+    let mut seconds = hours * 60 * 60;
+    for unit in [60, 1] {
+        let Some(after_colon) = rest.strip_prefix(':') else {
+            break;
+        };
+        let (value, after_value) = parse_number(after_colon)?;
+        seconds += value * unit;
+        rest = after_value;
+    }
+    Some((sign * seconds, rest))
+}
+
+/// Parse a `date[/time]` DST transition.
+fn parse_transition(string: &str) -> Option<(Transition, &str)> {
+    let (date, rest) = if let Some(rest) = string.strip_prefix('M') {
+        let (month, rest) = parse_number(rest)?;
+        let (week, rest) = parse_number(rest.strip_prefix('.')?)?;
+        let (weekday, rest) = parse_number(rest.strip_prefix('.')?)?;
+        if !(1..=12).contains(&month) || !(1..=5).contains(&week) || !(0..=6).contains(&weekday) {
+            return None;
+        }
+        let date = TransitionDate::MonthWeekDay {
+            month: month as i8,
+            week: week as i8,
+            weekday: weekday as i8,
+        };
+        (date, rest)
+    } else if let Some(rest) = string.strip_prefix('J') {
+        let (day, rest) = parse_number(rest)?;
+        if !(1..=365).contains(&day) {
+            return None;
+        }
+        (TransitionDate::JulianNoLeap(day as u16), rest)
+    } else {
+        let (day, rest) = parse_number(string)?;
+        if !(0..=365).contains(&day) {
+            return None;
+        }
+        (TransitionDate::Julian(day as u16), rest)
+    };
+    let (time, rest) = match rest.strip_prefix('/') {
+        Some(rest) => parse_time(rest)?,
+        None => (2 * 60 * 60, rest), // 02:00:00 is the default
+    };
+    Some((Transition { date, time }, rest))
+}
+
+/// Get the POSIX `TZ` rule from the footer of a TZif file (version 2 or
+/// later), which is the last line of the file.
+fn tzif_footer(file: &[u8]) -> Option<&str> {
+    if !file.starts_with(b"TZif") || file.get(4).copied().unwrap_or(0) < b'2' {
+        return None;
+    }
+    let file = file.strip_suffix(b"\n")?;
+    let footer_start = file.iter().rposition(|&b| b == b'\n')? + 1;
+    std::str::from_utf8(&file[footer_start..]).ok()
+}
+
+/// Read the rule for a named zone (e.g. `Europe/London`) from the host's time
+/// zone database.
+fn rule_for_name(name: &str) -> Option<TimeZoneRule> {
+    if name == "GMT" || name == "UTC" {
+        return Some(TimeZoneRule::fixed(0));
+    }
+    // Don't let the guest read arbitrary host files.
+    if name.is_empty()
+        || name.starts_with('/')
+        || name.split('/').any(|part| part.is_empty() || part == "..")
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "/_+-".contains(c))
+    {
+        return None;
+    }
+    let path = std::path::Path::new("/usr/share/zoneinfo").join(name);
+    let file = std::fs::read(path).ok()?;
+    TimeZoneRule::parse(tzif_footer(&file)?)
+}
+
+fn create(env: &mut Environment, rule: TimeZoneRule) -> CFTimeZoneRef {
+    let isa = env
+        .objc
+        .get_known_class("_touchHLE_CFTimeZone", &mut env.mem);
+    let host_object = Box::new(CFTimeZoneHostObject { rule });
+    env.objc.alloc_object(isa, host_object, &mut env.mem)
+}
+
+fn CFTimeZoneCopySystem(_env: &mut Environment) -> CFTimeZoneRef {
+    // touchHLE treats local time as GMT (see also localtime_r), and NULL is
+    // accepted as GMT by all the functions here.
+    // TODO: use the host's time zone?
+    nil
+}
+
+fn CFTimeZoneCreateWithTimeIntervalFromGMT(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    interval: CFTimeInterval,
+) -> CFTimeZoneRef {
+    if !interval.is_finite() || interval.abs() > 18.0 * 60.0 * 60.0 {
+        return nil;
+    }
+    create(env, TimeZoneRule::fixed(interval.round() as i32))
+}
+
+fn CFTimeZoneCreateWithName(
+    env: &mut Environment,
+    _allocator: CFAllocatorRef,
+    name: CFStringRef,
+    _try_abbrev: bool,
+) -> CFTimeZoneRef {
+    let name = to_rust_string(env, name);
+    let Some(rule) = rule_for_name(&name) else {
+        log!(
+            "CFTimeZoneCreateWithName() couldn't find time zone {:?}, returning NULL",
+            name
+        );
+        return nil;
+    };
+    log_dbg!("CFTimeZoneCreateWithName({:?}) => {:?}", name, rule);
+    create(env, rule)
+}
+
+/// Offset from GMT of a time zone at some point in time. `NULL` means GMT.
+pub fn seconds_from_gmt(env: &Environment, tz: CFTimeZoneRef, at: CFAbsoluteTime) -> i32 {
+    if tz.is_null() {
+        return 0;
+    }
+    let timestamp = (at + kCFAbsoluteTimeIntervalSince1970).floor() as i64;
+    env.objc
+        .borrow::<CFTimeZoneHostObject>(tz)
+        .rule
+        .offset_at(timestamp)
+}
+
+fn CFTimeZoneGetSecondsFromGMTForDate(
+    env: &mut Environment,
+    tz: CFTimeZoneRef,
+    at: CFAbsoluteTime,
+) -> CFTimeInterval {
+    seconds_from_gmt(env, tz, at).into()
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CFTimeZoneCopySystem()),
+    export_c_func!(CFTimeZoneCreateWithTimeIntervalFromGMT(_, _)),
+    export_c_func!(CFTimeZoneCreateWithName(_, _, _)),
+    export_c_func!(CFTimeZoneGetSecondsFromGMTForDate(_, _)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: i32 = 60 * 60;
+
+    #[test]
+    fn test_civil_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(2001, 1, 1), 11323);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(year_of(0), 1970);
+        assert_eq!(year_of(-1), 1969);
+        // 2024-12-31T23:59:59Z and a second later
+        assert_eq!(year_of(1735689599), 2024);
+        assert_eq!(year_of(1735689600), 2025);
+    }
+
+    #[test]
+    fn test_fixed_offsets() {
+        assert_eq!(TimeZoneRule::parse("UTC0"), Some(TimeZoneRule::fixed(0)));
+        assert_eq!(
+            TimeZoneRule::parse("<+0530>-5:30"),
+            Some(TimeZoneRule::fixed(5 * HOUR + 30 * 60))
+        );
+        assert_eq!(TimeZoneRule::parse("JST-9").unwrap().offset_at(0), 9 * HOUR);
+        assert_eq!(TimeZoneRule::parse("X1"), None);
+        assert_eq!(TimeZoneRule::parse("EST5EDT,M13.1.0,M11.1.0"), None);
+    }
+
+    #[test]
+    fn test_dst() {
+        let new_york = TimeZoneRule::parse("EST5EDT,M3.2.0,M11.1.0").unwrap();
+        // 2023-01-15T12:00:00Z and 2023-07-15T12:00:00Z
+        assert_eq!(new_york.offset_at(1673784000), -5 * HOUR);
+        assert_eq!(new_york.offset_at(1689422400), -4 * HOUR);
+        // DST began at 2023-03-12T02:00:00-05:00 and ended at
+        // 2023-11-05T02:00:00-04:00.
+        assert_eq!(new_york.offset_at(1678604399), -5 * HOUR);
+        assert_eq!(new_york.offset_at(1678604400), -4 * HOUR);
+        assert_eq!(new_york.offset_at(1699163999), -4 * HOUR);
+        assert_eq!(new_york.offset_at(1699164000), -5 * HOUR);
+
+        // The southern hemisphere has DST over the new year.
+        let sydney = TimeZoneRule::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").unwrap();
+        assert_eq!(sydney.offset_at(1673784000), 11 * HOUR);
+        assert_eq!(sydney.offset_at(1689422400), 10 * HOUR);
+
+        // Julian day rules, with an explicit DST offset.
+        let julian = TimeZoneRule::parse("AAA3BBB2,J60/0,300").unwrap();
+        // 2024-03-01T03:00:00Z is midnight local time on day J60 of a leap
+        // year, and 2024-01-15T12:00:00Z is before it.
+        assert_eq!(julian.offset_at(1709262000), -2 * HOUR);
+        assert_eq!(julian.offset_at(1709261999), -3 * HOUR);
+        assert_eq!(julian.offset_at(1705320000), -3 * HOUR);
+    }
+
+    #[test]
+    fn test_tzif_footer() {
+        let mut file = b"TZif2\0\0\0binary\ndata".to_vec();
+        file.extend_from_slice(b"\nEST5EDT,M3.2.0,M11.1.0\n");
+        assert_eq!(tzif_footer(&file), Some("EST5EDT,M3.2.0,M11.1.0"));
+        // Version 1 files have no footer.
+        assert_eq!(tzif_footer(b"TZif\0\0\0\nUTC0\n"), None);
+        assert_eq!(tzif_footer(b"not a TZif file\n"), None);
+    }
+}
@@ -5,18 +5,18 @@
  */
 //! Time things including `CFAbsoluteTime`.
 
+use super::cf_time_zone::{seconds_from_gmt, CFTimeZoneRef};
 use crate::abi::GuestArg;
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::core_foundation::CFTypeRef;
 use crate::frameworks::foundation::NSTimeInterval;
-use crate::libc::time::{time_t, timestamp_to_calendar_date};
+use crate::libc::time::{time_t, timestamp_to_calendar_date, tm};
 use crate::mem::SafeRead;
-use crate::objc::{msg_class, nil};
+use crate::objc::msg_class;
 use crate::{impl_GuestRet_for_large_struct, Environment};
 use std::time::SystemTime;
 
 pub type CFTimeInterval = NSTimeInterval;
-type CFAbsoluteTime = CFTimeInterval;
+pub type CFAbsoluteTime = CFTimeInterval;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(C, packed)]
@@ -53,46 +53,268 @@ impl GuestArg for CFGregorianDate {
     }
 }
 
-fn CFAbsoluteTimeGetCurrent(env: &mut Environment) -> CFAbsoluteTime {
-    // TODO: This should use "Jan 1 2001 00:00:00 GMT" as an absolute reference instead
-    let time: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
-    time
+/// Seconds from the UNIX epoch (1970-01-01) to the CF epoch (2001-01-01).
+pub const kCFAbsoluteTimeIntervalSince1970: CFTimeInterval = 978307200.0;
+
+pub type CFGregorianUnitFlags = u32; // CFOptionFlags
+#[allow(dead_code)]
+pub const kCFGregorianUnitsYears: CFGregorianUnitFlags = 1 << 0;
+pub const kCFGregorianUnitsMonths: CFGregorianUnitFlags = 1 << 1;
+pub const kCFGregorianUnitsDays: CFGregorianUnitFlags = 1 << 2;
+pub const kCFGregorianUnitsHours: CFGregorianUnitFlags = 1 << 3;
+pub const kCFGregorianUnitsMinutes: CFGregorianUnitFlags = 1 << 4;
+pub const kCFGregorianUnitsSeconds: CFGregorianUnitFlags = 1 << 5;
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
 }
 
-type CFTimeZoneRef = CFTypeRef;
+pub fn days_in_month(year: i32, month: i8) -> i8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
 
-fn CFTimeZoneCopySystem(_env: &mut Environment) -> CFTimeZoneRef {
-    // TODO: implement (nil seems to correspond to GMT)
-    nil
+/// Format a date as ISO 8601 (`YYYY-MM-DDThh:mm:ssZ`), assuming it is in GMT.
+/// Fractional seconds are included (to the millisecond) only if present.
+pub fn cf_gregorian_to_iso8601(date: CFGregorianDate) -> String {
+    let CFGregorianDate {
+        year,
+        month,
+        day,
+        hours,
+        minutes,
+        seconds,
+    } = date;
+    let millis = (seconds * 1000.0) as i64;
+    let (whole_seconds, millis) = (millis / 1000, millis % 1000);
+    let fraction = if millis != 0 {
+        format!(".{:03}", millis)
+    } else {
+        String::new()
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}Z",
+        year, month, day, hours, minutes, whole_seconds, fraction
+    )
 }
 
-fn CFAbsoluteTimeGetGregorianDate(
+/// Check whether the fields selected by `unit_flags` are in range.
+fn gregorian_date_is_valid(date: CFGregorianDate, unit_flags: CFGregorianUnitFlags) -> bool {
+    let CFGregorianDate {
+        year,
+        month,
+        day,
+        hours,
+        minutes,
+        seconds,
+    } = date;
+    // Any year is valid, so kCFGregorianUnitsYears doesn't need checking.
+    let month_valid = (1..=12).contains(&month);
+    if unit_flags & kCFGregorianUnitsMonths != 0 && !month_valid {
+        return false;
+    }
+    // The number of days depends on the month (and year), so an invalid month
+    // makes the day invalid too.
+    if unit_flags & kCFGregorianUnitsDays != 0
+        && !(month_valid && (1..=days_in_month(year, month)).contains(&day))
+    {
+        return false;
+    }
+    if unit_flags & kCFGregorianUnitsHours != 0 && !(0..24).contains(&hours) {
+        return false;
+    }
+    if unit_flags & kCFGregorianUnitsMinutes != 0 && !(0..60).contains(&minutes) {
+        return false;
+    }
+    if unit_flags & kCFGregorianUnitsSeconds != 0 && !(0.0..60.0).contains(&seconds) {
+        return false;
+    }
+    true
+}
+
+fn CFGregorianDateIsValid(
     _env: &mut Environment,
+    date: CFGregorianDate,
+    unit_flags: CFGregorianUnitFlags,
+) -> bool {
+    gregorian_date_is_valid(date, unit_flags)
+}
+
+pub fn CFAbsoluteTimeGetCurrent(env: &mut Environment) -> CFAbsoluteTime {
+    // TODO: This should use "Jan 1 2001 00:00:00 GMT" as an absolute reference instead
+    let time: NSTimeInterval = msg_class![env; NSProcessInfo systemUptime];
+    time
+}
+
+fn CFAbsoluteTimeGetGregorianDate(
+    env: &mut Environment,
     _at: CFAbsoluteTime,
     tz: CFTimeZoneRef,
 ) -> CFGregorianDate {
-    assert!(tz.is_null());
     log!(
         "TODO: CFAbsoluteTimeGetGregorianDate ignoring passed absolute time, using SystemTime::now"
     );
-    let time64 = SystemTime::now()
+    let now = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
-        .as_secs();
-    let time = time64 as time_t;
-    let tm = timestamp_to_calendar_date(time);
+        .as_secs_f64()
+        - kCFAbsoluteTimeIntervalSince1970;
+    let offset = seconds_from_gmt(env, tz, now);
+    absolute_time_to_gregorian_date(now + CFTimeInterval::from(offset))
+}
+
+/// Convert an absolute time to a calendar date in GMT.
+fn absolute_time_to_calendar_date(at: CFAbsoluteTime) -> tm {
+    timestamp_to_calendar_date((at + kCFAbsoluteTimeIntervalSince1970).floor() as time_t)
+}
+
+/// Convert an absolute time to a Gregorian date in GMT.
+pub fn absolute_time_to_gregorian_date(at: CFAbsoluteTime) -> CFGregorianDate {
+    let tm = absolute_time_to_calendar_date(at);
     CFGregorianDate {
         year: 1900 + tm.tm_year,
-        month: tm.tm_mon as i8,
+        // tm_mon has 0 as January
+        month: tm.tm_mon as i8 + 1,
         day: tm.tm_mday as i8,
         hours: tm.tm_hour as i8,
         minutes: tm.tm_min as i8,
-        seconds: tm.tm_sec.into(),
+        seconds: f64::from(tm.tm_sec) + (at - at.floor()),
+    }
+}
+
+/// Day of the week where 1 is Monday and 7 is Sunday, as CF does it.
+fn day_of_week(at: CFAbsoluteTime) -> i32 {
+    // tm_wday has 0 as Sunday
+    match absolute_time_to_calendar_date(at).tm_wday {
+        0 => 7,
+        day => day,
+    }
+}
+
+/// ISO 8601 week number (1 to 53), as CF does it: weeks start on Monday, and
+/// the first week of a year is the one containing its first Thursday.
+fn week_of_year(at: CFAbsoluteTime) -> i32 {
+    // Whether a year has 53 weeks depends on which weekday it starts on.
+    fn weeks_in_year(year: i32) -> i32 {
+        let p = |y: i32| (y + y.div_euclid(4) - y.div_euclid(100) + y.div_euclid(400)) % 7;
+        if p(year) == 4 || p(year - 1) == 3 {
+            53
+        } else {
+            52
+        }
+    }
+
+    let tm = absolute_time_to_calendar_date(at);
+    let year = 1900 + tm.tm_year;
+    let week = (tm.tm_yday + 1 - day_of_week(at) + 10) / 7;
+    if week < 1 {
+        // Last week of the previous year
+        weeks_in_year(year - 1)
+    } else if week > weeks_in_year(year) {
+        // First week of the next year
+        1
+    } else {
+        week
     }
 }
 
+fn CFAbsoluteTimeGetDayOfWeek(env: &mut Environment, at: CFAbsoluteTime, tz: CFTimeZoneRef) -> i32 {
+    let offset = seconds_from_gmt(env, tz, at);
+    day_of_week(at + CFTimeInterval::from(offset))
+}
+
+fn CFAbsoluteTimeGetWeekOfYear(
+    env: &mut Environment,
+    at: CFAbsoluteTime,
+    tz: CFTimeZoneRef,
+) -> i32 {
+    let offset = seconds_from_gmt(env, tz, at);
+    week_of_year(at + CFTimeInterval::from(offset))
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CFAbsoluteTimeGetCurrent()),
-    export_c_func!(CFTimeZoneCopySystem()),
     export_c_func!(CFAbsoluteTimeGetGregorianDate(_, _)),
+    export_c_func!(CFGregorianDateIsValid(_, _)),
+    export_c_func!(CFAbsoluteTimeGetDayOfWeek(_, _)),
+    export_c_func!(CFAbsoluteTimeGetWeekOfYear(_, _)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_iso8601() {
+        let date = CFGregorianDate {
+            year: 2009,
+            month: 6,
+            day: 17,
+            hours: 8,
+            minutes: 5,
+            seconds: 3.0,
+        };
+        assert_eq!(cf_gregorian_to_iso8601(date), "2009-06-17T08:05:03Z");
+        let date = CFGregorianDate {
+            seconds: 59.25,
+            ..date
+        };
+        assert_eq!(cf_gregorian_to_iso8601(date), "2009-06-17T08:05:59.250Z");
+    }
+    #[test]
+    fn test_absolute_time_to_gregorian_date() {
+        let date = absolute_time_to_gregorian_date(0.0);
+        assert_eq!(cf_gregorian_to_iso8601(date), "2001-01-01T00:00:00Z");
+        // 2009-06-17T08:05:03.5Z
+        let date = absolute_time_to_gregorian_date(266918703.5);
+        assert_eq!(cf_gregorian_to_iso8601(date), "2009-06-17T08:05:03.500Z");
+    }
+    #[test]
+    fn test_gregorian_date_is_valid() {
+        let is_valid = |year, month, day, flags| {
+            let date = CFGregorianDate {
+                year,
+                month,
+                day,
+                hours: 0,
+                minutes: 0,
+                seconds: 0.0,
+            };
+            gregorian_date_is_valid(date, flags)
+        };
+        let all = kCFGregorianUnitsYears
+            | kCFGregorianUnitsMonths
+            | kCFGregorianUnitsDays
+            | kCFGregorianUnitsHours
+            | kCFGregorianUnitsMinutes
+            | kCFGregorianUnitsSeconds;
+        assert!(is_valid(2008, 2, 29, all));
+        assert!(is_valid(2000, 2, 29, all));
+        assert!(!is_valid(2009, 2, 29, all));
+        assert!(!is_valid(1900, 2, 29, all));
+        assert!(!is_valid(2009, 4, 31, all));
+        assert!(!is_valid(2009, 13, 1, all));
+        // Only the selected units are checked.
+        assert!(is_valid(2009, 2, 32, kCFGregorianUnitsMonths));
+    }
+    #[test]
+    fn test_day_and_week() {
+        const DAY: CFAbsoluteTime = 24.0 * 60.0 * 60.0;
+        // 2001-01-01, the epoch, was a Monday in week 1.
+        assert_eq!(day_of_week(0.0), 1);
+        assert_eq!(week_of_year(0.0), 1);
+        // 2008-12-29 was a Monday in week 1 of 2009.
+        assert_eq!(day_of_week(2919.0 * DAY), 1);
+        assert_eq!(week_of_year(2919.0 * DAY), 1);
+        // 2010-01-03 was a Sunday in week 53 of 2009.
+        assert_eq!(day_of_week(3289.0 * DAY + 0.5), 7);
+        assert_eq!(week_of_year(3289.0 * DAY + 0.5), 53);
+        // 2000-12-31 was a Sunday in week 52.
+        assert_eq!(day_of_week(-1.0), 7);
+        assert_eq!(week_of_year(-1.0), 52);
+    }
+}
@@ -5,7 +5,9 @@
  */
 //! `CGBitmapContext.h`
 
-use super::cg_color_space::{kCGColorSpaceGenericRGB, CGColorSpaceHostObject, CGColorSpaceRef};
+use super::cg_color_space::{
+    kCGColorSpaceGenericGray, kCGColorSpaceGenericRGB, CGColorSpaceHostObject, CGColorSpaceRef,
+};
 use super::cg_context::{CGContextHostObject, CGContextRef, CGContextSubclass};
 use super::cg_image::{
     self, kCGBitmapAlphaInfoMask, kCGBitmapByteOrderMask, kCGImageAlphaFirst, kCGImageAlphaLast,
@@ -15,9 +17,9 @@ use super::cg_image::{
 };
 use super::{CGFloat, CGRect};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::image::{gamma_decode, gamma_encode};
+use crate::image::{gamma_decode, gamma_encode, Image, ScalingMode};
 use crate::mem::{GuestUSize, Mem, MutVoidPtr};
-use crate::objc::ObjC;
+use crate::objc::{nil, ObjC};
 use crate::Environment;
 
 #[derive(Copy, Clone)]
@@ -31,7 +33,7 @@ pub(super) struct CGBitmapContextData {
     alpha_info: CGImageAlphaInfo,
 }
 
-fn CGBitmapContextCreate(
+pub fn CGBitmapContextCreate(
     env: &mut Environment,
     data: MutVoidPtr,
     width: GuestUSize,
@@ -42,12 +44,18 @@ fn CGBitmapContextCreate(
     bitmap_info: u32,
 ) -> CGContextRef {
     assert!(!data.is_null()); // TODO: support memory allocation
-    assert!(bits_per_component == 8); // TODO: support other bit depths
-    assert!(components_for_rgb(bitmap_info).is_ok());
 
     let color_space = env.objc.borrow::<CGColorSpaceHostObject>(color_space).name;
-    // TODO: support other color spaces
-    assert!(color_space == kCGColorSpaceGenericRGB);
+    // TODO: support other bit depths and color spaces
+    if bits_per_component != 8 || components_for(color_space, bitmap_info).is_err() {
+        log!(
+            "CGBitmapContextCreate() with color space {}, {} bits per component, bitmap info {:#x} is unsupported, returning NULL",
+            color_space,
+            bits_per_component,
+            bitmap_info
+        );
+        return nil;
+    }
 
     let host_object = CGContextHostObject {
         subclass: CGContextSubclass::CGBitmapContext(CGBitmapContextData {
@@ -56,7 +64,7 @@ fn CGBitmapContextCreate(
             height,
             bits_per_component,
             bytes_per_row,
-            color_space: kCGColorSpaceGenericRGB,
+            color_space,
             alpha_info: bitmap_info & kCGBitmapAlphaInfoMask,
         }),
         // TODO: is this the correct default?
@@ -70,6 +78,24 @@ fn CGBitmapContextCreate(
         .alloc_object(isa, Box::new(host_object), &mut env.mem)
 }
 
+fn components_for(color_space: &str, bitmap_info: CGBitmapInfo) -> Result<GuestUSize, ()> {
+    match color_space {
+        kCGColorSpaceGenericRGB => components_for_rgb(bitmap_info),
+        kCGColorSpaceGenericGray => components_for_gray(bitmap_info),
+        _ => Err(()),
+    }
+}
+
+fn components_for_gray(bitmap_info: CGBitmapInfo) -> Result<GuestUSize, ()> {
+    // This is the only 8-bit grayscale format Core Graphics supports for
+    // bitmap contexts.
+    if bitmap_info == kCGImageAlphaNone {
+        Ok(1)
+    } else {
+        Err(())
+    }
+}
+
 fn components_for_rgb(bitmap_info: CGBitmapInfo) -> Result<GuestUSize, ()> {
     let byte_order = bitmap_info & kCGBitmapByteOrderMask;
     if byte_order != kCGImageByteOrderDefault && byte_order != kCGImageByteOrder32Big {
@@ -101,8 +127,7 @@ fn bytes_per_pixel(data: &CGBitmapContextData) -> GuestUSize {
         ..
     } = data;
     assert!(bits_per_component == 8);
-    assert!(color_space == kCGColorSpaceGenericRGB);
-    components_for_rgb(alpha_info).unwrap()
+    components_for(color_space, alpha_info).unwrap()
 }
 
 fn get_pixels<'a>(data: &CGBitmapContextData, mem: &'a mut Mem) -> &'a mut [u8] {
@@ -148,6 +173,12 @@ fn get_pixel(
     pixels: &mut [u8],
     first_component_idx: usize,
 ) -> (f32, f32, f32, f32) {
+    if data.color_space == kCGColorSpaceGenericGray {
+        // Always opaque, see components_for_gray().
+        let value = gamma_decode(pixels[first_component_idx] as f32 / 255.0);
+        return (value, value, value, 1.0);
+    }
+
     let pixel = match data.alpha_info {
         kCGImageAlphaNone => (
             pixels[first_component_idx] as f32 / 255.0,
@@ -231,6 +262,14 @@ fn put_pixel(
         _ => pixel,
     };
 
+    if data.color_space == kCGColorSpaceGenericGray {
+        // Relative luminance for sRGB primaries, which must be computed in
+        // linear RGB.
+        let luminance = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        pixels[first_component_idx] = (gamma_encode(luminance) * 255.0) as u8;
+        return;
+    }
+
     // Alpha is always linear.
     let (r, g, b) = (gamma_encode(r), gamma_encode(g), gamma_encode(b));
     match data.alpha_info {
@@ -361,6 +400,7 @@ pub(super) fn draw_image(
     rect: CGRect,
     image: CGImageRef,
 ) {
+    let alpha_only = cg_image::is_alpha_only(&env.objc, image);
     let image = cg_image::borrow_image(&env.objc, image);
 
     let mut drawer = CGBitmapContextDrawer::new(&env.objc, &mut env.mem, context);
@@ -369,6 +409,21 @@ pub(super) fn draw_image(
 
     // let _ = std::fs::write(format!("bitmap-{:?}-{:?}-before.data", (image as *const _ as *const ()), (drawer.width(), drawer.height())), &drawer.pixels);
 
+    draw_image_inner(&mut drawer, rect, image, alpha_only);
+
+    // let _ = std::fs::write(format!("bitmap-{:?}-{:?}-after.data", (image as *const _ as *const ()), (drawer.width(), drawer.height())), &drawer.pixels);
+}
+
+fn draw_image_inner(
+    drawer: &mut CGBitmapContextDrawer,
+    rect: CGRect,
+    image: &Image,
+    alpha_only: bool,
+) {
+    // Alpha-only images are drawn as coverage of the fill color, like glyphs
+    // are in UIFont's text drawing.
+    let fill_color = drawer.rgb_fill_color();
+
     // TODO: correct anti-aliasing
     let translation = drawer.translation();
     let origin = (translation.0 + rect.origin.x, translation.1 + rect.origin.y);
@@ -379,9 +434,27 @@ pub(super) fn draw_image(
     let dest_width = x_end - x_start;
     let dest_height = y_end - y_start;
 
+    // Nearest-neighbour sampling skips most of the pixels when shrinking an
+    // image, so filter it down to the destination size first.
+    let (image_width, image_height) = image.dimensions();
+    let shrinking = dest_width > 0
+        && dest_height > 0
+        && ((dest_width as u32) < image_width || (dest_height as u32) < image_height);
+    let downsampled;
+    let image = if shrinking {
+        downsampled = image.scaled_to(
+            (dest_width as u32).min(image_width),
+            (dest_height as u32).min(image_height),
+            ScalingMode::Bilinear,
+        );
+        &downsampled
+    } else {
+        image
+    };
     let (image_width, image_height) = image.dimensions();
 
-    // TODO: non-nearest-neighbour filtering? (what does CG actually do?)
+    // TODO: non-nearest-neighbour filtering when enlarging? (what does CG
+    // actually do?)
     for y in y_start..y_end {
         for x in x_start..x_end {
             // Note: this clamping needs to be done here, not above, so that
@@ -396,13 +469,112 @@ pub(super) fn draw_image(
             // Image is in top-to-bottom order, but the bitmap is bottom-to-top
             let texel_y = (image_height as f32 * (1.0 - texel_y)) as i32;
             if let Some(color) = image.get_pixel((texel_x, texel_y)) {
+                let color = if alpha_only {
+                    let coverage = color.3;
+                    let (r, g, b, a) = fill_color;
+                    (r * coverage, g * coverage, b * coverage, a * coverage)
+                } else {
+                    color
+                };
                 drawer.put_pixel((x, y), color)
             }
         }
     }
-
-    // let _ = std::fs::write(format!("bitmap-{:?}-{:?}-after.data", (image as *const _ as *const ()), (drawer.width(), drawer.height())), &drawer.pixels);
 }
 
 pub const FUNCTIONS: FunctionExports =
     &[export_c_func!(CGBitmapContextCreate(_, _, _, _, _, _, _))];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frameworks::core_graphics::{CGPoint, CGSize};
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_draw_alpha_only_image() {
+        // 2x1 RGBA bitmap, initially transparent
+        let mut pixels = vec![0u8; 8];
+        let mut drawer = CGBitmapContextDrawer {
+            bitmap_info: CGBitmapContextData {
+                data: Ptr::null(),
+                width: 2,
+                height: 1,
+                bits_per_component: 8,
+                bytes_per_row: 8,
+                color_space: kCGColorSpaceGenericRGB,
+                alpha_info: kCGImageAlphaPremultipliedLast,
+            },
+            rgb_fill_color: (1.0, 0.0, 0.0, 1.0),
+            translation: (0.0, 0.0),
+            pixels: &mut pixels,
+        };
+        // A glyph which covers the left pixel but not the right one
+        let glyph = Image::from_alpha_only(&[255, 0], (2, 1), 2);
+        let rect = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize {
+                width: 2.0,
+                height: 1.0,
+            },
+        };
+        draw_image_inner(&mut drawer, rect, &glyph, /* alpha_only: */ true);
+        assert_eq!(pixels, [255, 0, 0, 255, 0, 0, 0, 0]);
+
+        // Shrinking the glyph to one pixel averages the coverage, rather than
+        // picking one of the source pixels.
+        let mut pixels = vec![0u8; 4];
+        let mut drawer = CGBitmapContextDrawer {
+            bitmap_info: CGBitmapContextData {
+                data: Ptr::null(),
+                width: 1,
+                height: 1,
+                bits_per_component: 8,
+                bytes_per_row: 4,
+                color_space: kCGColorSpaceGenericRGB,
+                alpha_info: kCGImageAlphaPremultipliedLast,
+            },
+            rgb_fill_color: (1.0, 0.0, 0.0, 1.0),
+            translation: (0.0, 0.0),
+            pixels: &mut pixels,
+        };
+        let rect = CGRect {
+            origin: CGPoint { x: 0.0, y: 0.0 },
+            size: CGSize {
+                width: 1.0,
+                height: 1.0,
+            },
+        };
+        draw_image_inner(&mut drawer, rect, &glyph, /* alpha_only: */ true);
+        assert!((127..=128).contains(&pixels[3]));
+    }
+
+    #[test]
+    fn test_gray_bitmap() {
+        assert_eq!(
+            components_for(kCGColorSpaceGenericGray, kCGImageAlphaNone),
+            Ok(1)
+        );
+        assert!(components_for(kCGColorSpaceGenericGray, kCGImageAlphaPremultipliedLast).is_err());
+
+        // 3x1 gray bitmap with a byte of padding, initially mid-gray
+        let mut pixels = vec![128u8, 128, 128, 99];
+        let mut drawer = CGBitmapContextDrawer {
+            bitmap_info: CGBitmapContextData {
+                data: Ptr::null(),
+                width: 3,
+                height: 1,
+                bits_per_component: 8,
+                bytes_per_row: 4,
+                color_space: kCGColorSpaceGenericGray,
+                alpha_info: kCGImageAlphaNone,
+            },
+            rgb_fill_color: (0.0, 0.0, 0.0, 1.0),
+            translation: (0.0, 0.0),
+            pixels: &mut pixels,
+        };
+        drawer.put_pixel((0, 0), (1.0, 1.0, 1.0, 1.0));
+        drawer.put_pixel((1, 0), (0.0, 0.0, 0.0, 1.0));
+        assert_eq!(pixels, [255, 0, 128, 99]);
+    }
+}
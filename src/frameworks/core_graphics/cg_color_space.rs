@@ -9,7 +9,8 @@ use crate::dyld::{export_c_func, ConstantExports, FunctionExports, HostConstant}
 use crate::frameworks::core_foundation::cf_string::CFStringRef;
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
 use crate::frameworks::foundation::ns_string;
-use crate::objc::{msg, objc_classes, ClassExports, HostObject};
+use crate::mem::{ConstPtr, GuestUSize};
+use crate::objc::{msg, nil, objc_classes, ClassExports, HostObject};
 use crate::Environment;
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -26,44 +27,92 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 pub(super) struct CGColorSpaceHostObject {
     pub(super) name: &'static str,
+    /// Only for indexed color spaces: the color table, with the entries
+    /// converted to RGB if the base color space was grayscale.
+    pub(super) palette: Option<Vec<[u8; 3]>>,
 }
 impl HostObject for CGColorSpaceHostObject {}
 
-pub type CGColorSpaceRef = CFTypeRef;
+/// Indexed color spaces have no name, so this is used in place of one.
+/// It is not a real constant.
+pub const INDEXED_COLOR_SPACE: &str = "_touchHLE_indexed";
 
-pub fn CGColorSpaceCreateWithName(env: &mut Environment, name: CFStringRef) -> CGColorSpaceRef {
-    let generic_rgb = ns_string::get_static_str(env, kCGColorSpaceGenericRGB);
-    // TODO: support more color spaces
-    assert!(msg![env; name isEqualToString:generic_rgb]);
+pub type CGColorSpaceRef = CFTypeRef;
 
+fn create(
+    env: &mut Environment,
+    name: &'static str,
+    palette: Option<Vec<[u8; 3]>>,
+) -> CGColorSpaceRef {
     let isa = env
         .objc
         .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
-    env.objc.alloc_object(
-        isa,
-        Box::new(CGColorSpaceHostObject {
-            name: kCGColorSpaceGenericRGB,
-        }),
-        &mut env.mem,
-    )
+    let host_object = Box::new(CGColorSpaceHostObject { name, palette });
+    env.objc.alloc_object(isa, host_object, &mut env.mem)
+}
+
+pub fn CGColorSpaceCreateWithName(env: &mut Environment, name: CFStringRef) -> CGColorSpaceRef {
+    // TODO: support more color spaces
+    for known_name in [kCGColorSpaceGenericRGB, kCGColorSpaceGenericGray] {
+        let known_name_ns = ns_string::get_static_str(env, known_name);
+        if msg![env; name isEqualToString:known_name_ns] {
+            return create(env, known_name, None);
+        }
+    }
+    log!(
+        "CGColorSpaceCreateWithName() for unsupported color space {:?}, returning NULL",
+        ns_string::to_rust_string(env, name)
+    );
+    nil
 }
 
 pub fn CGColorSpaceCreateDeviceRGB(env: &mut Environment) -> CGColorSpaceRef {
     // TODO: figure out what characteristics kCGColorSpaceDeviceRGB actually has on an iPhone
-    let isa = env
-        .objc
-        .get_known_class("_touchHLE_CGColorSpace", &mut env.mem);
-    env.objc.alloc_object(
-        isa,
-        Box::new(CGColorSpaceHostObject {
-            name: kCGColorSpaceGenericRGB,
-        }),
-        &mut env.mem,
-    )
+    create(env, kCGColorSpaceGenericRGB, None)
 }
 
 fn CGColorSpaceCreateDeviceGray(env: &mut Environment) -> CGColorSpaceRef {
-    CGColorSpaceCreateDeviceRGB(env)
+    // TODO: figure out what characteristics kCGColorSpaceDeviceGray actually has on an iPhone
+    create(env, kCGColorSpaceGenericGray, None)
+}
+
+fn CGColorSpaceCreateIndexed(
+    env: &mut Environment,
+    base_space: CGColorSpaceRef,
+    last_index: GuestUSize,
+    color_table: ConstPtr<u8>,
+) -> CGColorSpaceRef {
+    if last_index > 255 {
+        return nil;
+    }
+    if base_space.is_null() {
+        return nil;
+    }
+    let base_space = env.objc.borrow::<CGColorSpaceHostObject>(base_space).name;
+    let components = match base_space {
+        kCGColorSpaceGenericRGB => 3,
+        kCGColorSpaceGenericGray => 1,
+        _ => {
+            // This includes indexed color spaces, which can't be a base.
+            log!(
+                "CGColorSpaceCreateIndexed() with unsupported base {}, returning NULL",
+                base_space
+            );
+            return nil;
+        }
+    };
+    let entry_count = last_index + 1;
+    let palette = env
+        .mem
+        .bytes_at(color_table, entry_count * components)
+        .chunks_exact(components as usize)
+        .map(|entry| match *entry {
+            [r, g, b] => [r, g, b],
+            [v] => [v, v, v],
+            _ => unreachable!(),
+        })
+        .collect();
+    create(env, INDEXED_COLOR_SPACE, Some(palette))
 }
 
 pub fn CGColorSpaceRelease(env: &mut Environment, cs: CGColorSpaceRef) {
@@ -80,16 +129,24 @@ pub fn CGColorSpaceRetain(env: &mut Environment, cs: CGColorSpaceRef) -> CGColor
 }
 
 pub const kCGColorSpaceGenericRGB: &str = "kCGColorSpaceGenericRGB";
+pub const kCGColorSpaceGenericGray: &str = "kCGColorSpaceGenericGray";
 
-pub const CONSTANTS: ConstantExports = &[(
-    "_kCGColorSpaceGenericRGB",
-    HostConstant::NSString(kCGColorSpaceGenericRGB),
-)];
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_kCGColorSpaceGenericRGB",
+        HostConstant::NSString(kCGColorSpaceGenericRGB),
+    ),
+    (
+        "_kCGColorSpaceGenericGray",
+        HostConstant::NSString(kCGColorSpaceGenericGray),
+    ),
+];
 
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGColorSpaceCreateWithName(_)),
     export_c_func!(CGColorSpaceCreateDeviceRGB()),
     export_c_func!(CGColorSpaceCreateDeviceGray()),
+    export_c_func!(CGColorSpaceCreateIndexed(_, _, _)),
     export_c_func!(CGColorSpaceRetain(_)),
     export_c_func!(CGColorSpaceRelease(_)),
 ];
@@ -5,16 +5,127 @@
  */
 //! `CGDataProvider.h`
 
+use crate::abi::GuestFunction;
 use crate::dyld::FunctionExports;
 use crate::export_c_func;
-use crate::frameworks::core_foundation::{CFDataRef, CFRetain, CFTypeRef};
+use crate::frameworks::core_foundation::{CFDataRef, CFRelease, CFRetain, CFTypeRef};
+use crate::mem::{ConstVoidPtr, GuestUSize, MutVoidPtr};
+use crate::objc::{id, msg_class, objc_classes, ClassExports, HostObject, ObjC};
 use crate::Environment;
 
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+// CGDataProvider seems to be a CFType-based type, but in our implementation
+// those are just Objective-C types, so we need a class for it, but its name is
+// not visible anywhere.
+@implementation _touchHLE_CGDataProvider: NSObject
+
+- (())dealloc {
+    let &CGDataProviderHostObject {
+        info,
+        data,
+        size,
+        release_data,
+    } = env.objc.borrow(this);
+    if let Some(release_data) = release_data {
+        let () = release_data.call_from_host(env, (info, data, size));
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+@end
+
+};
+
+struct CGDataProviderHostObject {
+    info: MutVoidPtr,
+    data: ConstVoidPtr,
+    size: GuestUSize,
+    release_data: Option<GuestFunction>,
+}
+impl HostObject for CGDataProviderHostObject {}
+
 pub type CGDataProviderRef = CFTypeRef;
 
+fn CGDataProviderCreateWithData(
+    env: &mut Environment,
+    info: MutVoidPtr,
+    data: ConstVoidPtr,
+    size: GuestUSize,
+    // void (*release_data)(void *info, const void *data, size_t size)
+    release_data: GuestFunction,
+) -> CGDataProviderRef {
+    let release_data = if release_data.addr_with_thumb_bit() != 0 {
+        Some(release_data)
+    } else {
+        None
+    };
+    let host_object = Box::new(CGDataProviderHostObject {
+        info,
+        data,
+        size,
+        release_data,
+    });
+    let class = env
+        .objc
+        .get_known_class("_touchHLE_CGDataProvider", &mut env.mem);
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+pub fn CGDataProviderRelease(env: &mut Environment, provider: CGDataProviderRef) {
+    if !provider.is_null() {
+        CFRelease(env, provider);
+    }
+}
+pub fn CGDataProviderRetain(
+    env: &mut Environment,
+    provider: CGDataProviderRef,
+) -> CGDataProviderRef {
+    if !provider.is_null() {
+        CFRetain(env, provider)
+    } else {
+        provider
+    }
+}
+
+/// Returns [true] if the object is a data provider created by
+/// `CGDataProviderCreateWithData`, rather than a `CGImage` standing in for one
+/// (see [CGDataProviderCopyData]).
+fn is_data_provider(env: &mut Environment, provider: CGDataProviderRef) -> bool {
+    let provider_class = ObjC::read_isa(provider, &env.mem);
+    let data_provider_class = env
+        .objc
+        .get_known_class("_touchHLE_CGDataProvider", &mut env.mem);
+    env.objc
+        .class_is_subclass_of(provider_class, data_provider_class)
+}
+
+/// Shortcut for use by `CGImageCreate`: get the bytes provided by a data
+/// provider. Returns [None] for other kinds of provider, e.g. a `CGImage`
+/// from `CGImageGetDataProvider`.
+pub fn borrow_bytes<'a>(env: &'a mut Environment, provider: CGDataProviderRef) -> Option<&'a [u8]> {
+    if !is_data_provider(env, provider) {
+        return None; // TODO: other kinds of provider
+    }
+    let &CGDataProviderHostObject { data, size, .. } = env.objc.borrow(provider);
+    Some(env.mem.bytes_at(data.cast(), size))
+}
+
 fn CGDataProviderCopyData(env: &mut Environment, provider: CGDataProviderRef) -> CFDataRef {
+    if is_data_provider(env, provider) {
+        let &CGDataProviderHostObject { data, size, .. } = env.objc.borrow(provider);
+        let ns_data: id = msg_class![env; NSData dataWithBytes:(data.cast_mut()) length:size];
+        return CFRetain(env, ns_data);
+    }
     // TODO: proper copy data once we have proper CGDataProviderRef
     CFRetain(env, provider)
 }
 
-pub const FUNCTIONS: FunctionExports = &[export_c_func!(CGDataProviderCopyData(_))];
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGDataProviderCreateWithData(_, _, _, _)),
+    export_c_func!(CGDataProviderRelease(_)),
+    export_c_func!(CGDataProviderRetain(_)),
+    export_c_func!(CGDataProviderCopyData(_)),
+];
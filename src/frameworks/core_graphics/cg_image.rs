@@ -5,14 +5,18 @@
  */
 //! `CGImage.h`
 
-use super::cg_color_space::{kCGColorSpaceGenericRGB, CGColorSpaceCreateWithName, CGColorSpaceRef};
+use super::cg_color_space::{
+    kCGColorSpaceGenericGray, kCGColorSpaceGenericRGB, CGColorSpaceCreateWithName,
+    CGColorSpaceHostObject, CGColorSpaceRef, INDEXED_COLOR_SPACE,
+};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_foundation::{CFRelease, CFRetain, CFTypeRef};
-use crate::frameworks::core_graphics::cg_data_provider::CGDataProviderRef;
+use crate::frameworks::core_graphics::cg_data_provider::{self, CGDataProviderRef};
+use crate::frameworks::core_graphics::CGFloat;
 use crate::frameworks::foundation::ns_string;
-use crate::image::Image;
-use crate::mem::GuestUSize;
-use crate::objc::{objc_classes, ClassExports, HostObject, ObjC};
+use crate::image::{AlphaMode, ChannelOrder, Image};
+use crate::mem::{ConstPtr, GuestUSize};
+use crate::objc::{nil, objc_classes, ClassExports, HostObject, ObjC};
 use crate::Environment;
 
 pub type CGImageAlphaInfo = u32;
@@ -30,7 +34,6 @@ pub const kCGImageByteOrderMask: CGImageByteOrderInfo = 0x7000;
 pub const kCGImageByteOrderDefault: CGImageByteOrderInfo = 0 << 12;
 #[allow(dead_code)]
 pub const kCGImageByteOrder16Little: CGImageByteOrderInfo = 1 << 12;
-#[allow(dead_code)]
 pub const kCGImageByteOrder32Little: CGImageByteOrderInfo = 2 << 12;
 #[allow(dead_code)]
 pub const kCGImageByteOrder16Big: CGImageByteOrderInfo = 3 << 12;
@@ -55,11 +58,17 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 struct CGImageHostObject {
     image: Image,
+    /// If [true], the image only has an alpha channel, and should be drawn as
+    /// coverage of the current fill color. The RGB channels of [Self::image]
+    /// are black in that case.
+    alpha_only: bool,
+    /// Name of the color space the image was created with. [Self::image] is
+    /// always RGBA regardless.
+    color_space: &'static str,
 }
 impl HostObject for CGImageHostObject {}
 
-// TODO: CGImageCreate family. Currently the accessor on UIImage is the only way
-//       to create this type.
+pub type CGColorRenderingIntent = i32;
 
 pub type CGImageRef = CFTypeRef;
 pub fn CGImageRelease(env: &mut Environment, c: CGImageRef) {
@@ -78,7 +87,11 @@ pub fn CGImageRetain(env: &mut Environment, c: CGImageRef) -> CGImageRef {
 /// Shortcut for use by `UIImage`: directly construct a `CGImage` instance from
 /// an [Image] instance.
 pub fn from_image(env: &mut Environment, image: Image) -> CGImageRef {
-    let host_obj = Box::new(CGImageHostObject { image });
+    let host_obj = Box::new(CGImageHostObject {
+        image,
+        alpha_only: false,
+        color_space: kCGColorSpaceGenericRGB,
+    });
     let class = env.objc.get_known_class("_touchHLE_CGImage", &mut env.mem);
     env.objc.alloc_object(class, host_obj, &mut env.mem)
 }
@@ -89,19 +102,179 @@ pub fn borrow_image(objc: &ObjC, image: CGImageRef) -> &Image {
     &objc.borrow::<CGImageHostObject>(image).image
 }
 
-fn CGImageGetAlphaInfo(_env: &mut Environment, _image: CGImageRef) -> CGImageAlphaInfo {
+/// Shortcut for use by `CGBitmapContext` etc: is this an alpha-only image,
+/// which should be drawn as coverage of the fill color?
+pub fn is_alpha_only(objc: &ObjC, image: CGImageRef) -> bool {
+    objc.borrow::<CGImageHostObject>(image).alpha_only
+}
+
+fn CGImageCreate(
+    env: &mut Environment,
+    width: GuestUSize,
+    height: GuestUSize,
+    bits_per_component: GuestUSize,
+    bits_per_pixel: GuestUSize,
+    bytes_per_row: GuestUSize,
+    color_space: CGColorSpaceRef,
+    bitmap_info: CGBitmapInfo,
+    provider: CGDataProviderRef,
+    decode: ConstPtr<CGFloat>,
+    _should_interpolate: bool,
+    _intent: CGColorRenderingIntent,
+) -> CGImageRef {
+    let alpha_info = bitmap_info & kCGBitmapAlphaInfoMask;
+    // The color space is irrelevant (and usually NULL) for alpha-only images.
+    let (color_space, palette) = if alpha_info == kCGImageAlphaOnly {
+        (None, None)
+    } else if color_space.is_null() {
+        log!("CGImageCreate() with NULL color space for an image with color, returning NULL");
+        return nil;
+    } else {
+        let host_object = env.objc.borrow::<CGColorSpaceHostObject>(color_space);
+        (Some(host_object.name), host_object.palette.clone())
+    };
+
+    // TODO: support other formats (and color spaces)
+    let is_gray = color_space == Some(kCGColorSpaceGenericGray) && alpha_info == kCGImageAlphaNone;
+    let is_indexed = palette.is_some() && alpha_info == kCGImageAlphaNone;
+    let rgba_format = if color_space == Some(kCGColorSpaceGenericRGB) && bits_per_pixel == 32 {
+        raw_format_for_rgba(bitmap_info)
+    } else {
+        None
+    };
+    let supported = decode.is_null()
+        && bits_per_component == 8
+        && (rgba_format.is_some()
+            || (bitmap_info & kCGBitmapByteOrderMask == kCGImageByteOrderDefault
+                && (color_space.is_none() || is_gray || is_indexed)
+                && bits_per_pixel == 8));
+    if !supported {
+        log!(
+            "TODO: CGImageCreate() with color space {:?}, bitmap info {:#x}, {} bits per component, {} bits per pixel, decode array {:?} is unsupported, returning NULL",
+            color_space,
+            bitmap_info,
+            bits_per_component,
+            bits_per_pixel,
+            decode
+        );
+        return nil;
+    }
+
+    if provider.is_null() {
+        log!("CGImageCreate() with NULL data provider, returning NULL");
+        return nil;
+    }
+    let Some(len) = data_len(width, height, bits_per_pixel, bytes_per_row) else {
+        log!(
+            "CGImageCreate() with {} bytes per row for a {}-pixel-wide image with {} bits per pixel, returning NULL",
+            bytes_per_row,
+            width,
+            bits_per_pixel
+        );
+        return nil;
+    };
+    let Some(bytes) = cg_data_provider::borrow_bytes(env, provider) else {
+        log!(
+            "TODO: CGImageCreate() with data provider {:?} that isn't from CGDataProviderCreateWithData(), returning NULL",
+            provider
+        );
+        return nil;
+    };
+    if bytes.len() < len {
+        log!(
+            "CGImageCreate() with {} bytes of data for a {}x{} image with {} bytes per row, returning NULL",
+            bytes.len(),
+            width,
+            height,
+            bytes_per_row
+        );
+        return nil;
+    }
+    let image = if let Some((order, alpha_mode)) = rgba_format {
+        Image::from_raw(bytes, (width, height), bytes_per_row, order, alpha_mode)
+    } else if let Some(palette) = palette {
+        Image::from_indexed(bytes, &palette, (width, height), bytes_per_row)
+    } else if is_gray {
+        Image::from_gray(bytes, (width, height), bytes_per_row)
+    } else {
+        Image::from_alpha_only(bytes, (width, height), bytes_per_row)
+    };
+
+    let host_obj = Box::new(CGImageHostObject {
+        image,
+        alpha_only: color_space.is_none(),
+        // Indexed images are expanded to RGB, and there's no way to recreate
+        // an indexed color space from a name anyway.
+        color_space: match color_space {
+            None | Some(INDEXED_COLOR_SPACE) => kCGColorSpaceGenericRGB,
+            Some(name) => name,
+        },
+    });
+    let class = env.objc.get_known_class("_touchHLE_CGImage", &mut env.mem);
+    env.objc.alloc_object(class, host_obj, &mut env.mem)
+}
+
+/// Get the number of bytes of pixel data needed for an image with these
+/// parameters. Returns [None] if `bytes_per_row` is too small to fit a row.
+fn data_len(
+    width: GuestUSize,
+    height: GuestUSize,
+    bits_per_pixel: GuestUSize,
+    bytes_per_row: GuestUSize,
+) -> Option<usize> {
+    let row_size = width as usize * (bits_per_pixel / 8) as usize;
+    if (bytes_per_row as usize) < row_size {
+        return None;
+    }
+    Some(bytes_per_row as usize * (height as usize).saturating_sub(1) + row_size)
+}
+
+/// Get the channel order and alpha meaning for 32-bit RGB(A) pixel data with
+/// some [CGBitmapInfo]. Returns [None] if it's not supported.
+fn raw_format_for_rgba(bitmap_info: CGBitmapInfo) -> Option<(ChannelOrder, AlphaMode)> {
+    let alpha_info = bitmap_info & kCGBitmapAlphaInfoMask;
+    let byte_order = bitmap_info & kCGBitmapByteOrderMask;
+    if (alpha_info | byte_order) != bitmap_info {
+        return None; // TODO: handle other cases (float)
+    }
+    // "First" and "last" refer to the order within a 32-bit integer, so the
+    // little-endian order reverses them in memory.
+    let (alpha_first, alpha_mode) = match alpha_info {
+        kCGImageAlphaPremultipliedLast => (false, AlphaMode::Premultiplied),
+        kCGImageAlphaPremultipliedFirst => (true, AlphaMode::Premultiplied),
+        kCGImageAlphaLast => (false, AlphaMode::Straight),
+        kCGImageAlphaFirst => (true, AlphaMode::Straight),
+        kCGImageAlphaNoneSkipLast => (false, AlphaMode::Ignored),
+        kCGImageAlphaNoneSkipFirst => (true, AlphaMode::Ignored),
+        _ => return None,
+    };
+    let order = match (byte_order, alpha_first) {
+        (kCGImageByteOrderDefault | kCGImageByteOrder32Big, false) => ChannelOrder::Rgba,
+        (kCGImageByteOrderDefault | kCGImageByteOrder32Big, true) => ChannelOrder::Argb,
+        (kCGImageByteOrder32Little, false) => ChannelOrder::Abgr,
+        (kCGImageByteOrder32Little, true) => ChannelOrder::Bgra,
+        _ => return None,
+    };
+    Some((order, alpha_mode))
+}
+
+fn CGImageGetAlphaInfo(env: &mut Environment, image: CGImageRef) -> CGImageAlphaInfo {
+    if is_alpha_only(&env.objc, image) {
+        return kCGImageAlphaOnly;
+    }
     // our Image type always returns premultiplied RGBA
     // (the premultiplied part must match what the real UIImage does, but
     // considering CgBI's design, maybe the order doesn't?)
     kCGImageAlphaPremultipliedLast
 }
 
-fn CGImageGetColorSpace(env: &mut Environment, _image: CGImageRef) -> CGColorSpaceRef {
+fn CGImageGetColorSpace(env: &mut Environment, image: CGImageRef) -> CGColorSpaceRef {
     // Caller must release
     // FIXME: what if a loaded image is not sRGB?
 
-    let srgb_name = ns_string::get_static_str(env, kCGColorSpaceGenericRGB);
-    CGColorSpaceCreateWithName(env, srgb_name)
+    let name = env.objc.borrow::<CGImageHostObject>(image).color_space;
+    let name = ns_string::get_static_str(env, name);
+    CGColorSpaceCreateWithName(env, name)
 }
 
 fn CGImageGetWidth(env: &mut Environment, image: CGImageRef) -> GuestUSize {
@@ -129,6 +302,7 @@ fn CGImageGetDataProvider(_env: &mut Environment, image: CGImageRef) -> CGDataPr
 }
 
 pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(CGImageCreate(_, _, _, _, _, _, _, _, _, _, _)),
     export_c_func!(CGImageRelease(_)),
     export_c_func!(CGImageRetain(_)),
     export_c_func!(CGImageGetAlphaInfo(_)),
@@ -137,3 +311,36 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(CGImageGetHeight(_)),
     export_c_func!(CGImageGetDataProvider(_)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw_format_for_rgba() {
+        assert_eq!(
+            raw_format_for_rgba(kCGImageAlphaPremultipliedLast),
+            Some((ChannelOrder::Rgba, AlphaMode::Premultiplied))
+        );
+        assert_eq!(
+            raw_format_for_rgba(kCGImageAlphaPremultipliedFirst | kCGImageByteOrder32Little),
+            Some((ChannelOrder::Bgra, AlphaMode::Premultiplied))
+        );
+        assert_eq!(
+            raw_format_for_rgba(kCGImageAlphaNoneSkipLast | kCGImageByteOrder32Big),
+            Some((ChannelOrder::Rgba, AlphaMode::Ignored))
+        );
+        assert_eq!(raw_format_for_rgba(kCGImageAlphaNone), None);
+    }
+
+    #[test]
+    fn test_data_len() {
+        // The last row doesn't need padding.
+        assert_eq!(data_len(3, 2, 32, 16), Some(28));
+        assert_eq!(data_len(3, 2, 8, 3), Some(6));
+        assert_eq!(data_len(3, 0, 8, 3), Some(3));
+        // Rows can't overlap.
+        assert_eq!(data_len(3, 2, 32, 11), None);
+        assert_eq!(data_len(4, 1, 8, 3), None);
+    }
+}
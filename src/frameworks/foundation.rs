@@ -11,6 +11,10 @@
 //! Being aware of this concept will make common types like `NSArray` and
 //! `NSString` easier to understand.
 
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
+use crate::mem::SafeRead;
+use std::time::Duration;
+
 pub mod ns_array;
 pub mod ns_autorelease_pool;
 pub mod ns_bundle;
@@ -19,6 +23,7 @@ pub mod ns_coder;
 pub mod ns_data;
 pub mod ns_dictionary;
 pub mod ns_enumerator;
+pub mod ns_error;
 pub mod ns_file_manager;
 pub mod ns_keyed_unarchiver;
 pub mod ns_locale;
@@ -34,6 +39,8 @@ pub mod ns_string;
 pub mod ns_thread;
 pub mod ns_timer;
 pub mod ns_url;
+pub mod ns_url_request;
+pub mod ns_url_response;
 pub mod ns_user_defaults;
 pub mod ns_value;
 
@@ -47,7 +54,9 @@ pub struct State {
     ns_null: ns_null::State,
     ns_run_loop: ns_run_loop::State,
     ns_string: ns_string::State,
+    ns_thread: ns_thread::State,
     ns_user_defaults: ns_user_defaults::State,
+    ns_value: ns_value::State,
 }
 
 pub type NSInteger = i32;
@@ -61,6 +70,42 @@ pub const NSOrderedDescending: NSComparisonResult = 1;
 /// Number of seconds.
 pub type NSTimeInterval = f64;
 
+/// Convert an [NSTimeInterval] to a [Duration] that can be safely slept for or
+/// added to an [std::time::Instant]. Negative intervals and NaN become zero,
+/// and huge ones (e.g. until `[NSDate distantFuture]`) are capped at a
+/// century, which is as good as forever for a guest app.
+pub fn time_interval_to_duration(interval: NSTimeInterval) -> Duration {
+    const MAX_INTERVAL: NSTimeInterval = 100.0 * 365.0 * 24.0 * 60.0 * 60.0;
+    if interval.is_nan() {
+        return Duration::ZERO;
+    }
+    Duration::from_secs_f64(interval.clamp(0.0, MAX_INTERVAL))
+}
+
+/// `NSRange`, a range of indices (usually of UTF-16 code units in a string).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(C, packed)]
+pub struct NSRange {
+    pub location: NSUInteger,
+    pub length: NSUInteger,
+}
+unsafe impl SafeRead for NSRange {}
+impl_GuestRet_for_large_struct!(NSRange);
+impl GuestArg for NSRange {
+    const REG_COUNT: usize = 2;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        NSRange {
+            location: GuestArg::from_regs(&regs[0..1]),
+            length: GuestArg::from_regs(&regs[1..2]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.location.to_regs(&mut regs[0..1]);
+        self.length.to_regs(&mut regs[1..2]);
+    }
+}
+
 /// Utility to help with implementing the `hash` method, which various classes
 /// in Foundation have to do.
 fn hash_helper<T: std::hash::Hash>(hashable: &T) -> NSUInteger {
@@ -73,3 +118,18 @@ fn hash_helper<T: std::hash::Hash>(hashable: &T) -> NSUInteger {
     let hash_u64: u64 = hasher.finish();
     (hash_u64 as u32) ^ ((hash_u64 >> 32) as u32)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_interval_to_duration() {
+        assert_eq!(time_interval_to_duration(1.5), Duration::from_millis(1500));
+        assert_eq!(time_interval_to_duration(-1.0), Duration::ZERO);
+        assert_eq!(time_interval_to_duration(f64::NAN), Duration::ZERO);
+        let forever = time_interval_to_duration(f64::INFINITY);
+        assert_eq!(time_interval_to_duration(1e300), forever);
+        assert!(std::time::Instant::now().checked_add(forever).is_some());
+    }
+}
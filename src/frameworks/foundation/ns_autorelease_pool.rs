@@ -6,16 +6,25 @@
 //! `NSAutoreleasePool`.
 
 use crate::objc::{id, msg, objc_classes, release, ClassExports, HostObject, NSZonePtr};
-use crate::Environment;
+use crate::{Environment, ThreadID};
+use std::collections::HashMap;
 
 #[derive(Default)]
 pub struct State {
-    pool_stack: Vec<id>,
+    /// Each thread has its own stack of pools.
+    pool_stacks: HashMap<ThreadID, Vec<id>>,
 }
 impl State {
     fn get(env: &mut Environment) -> &mut Self {
         &mut env.framework_state.foundation.ns_autorelease_pool
     }
+    fn current_pool_stack(env: &mut Environment) -> &mut Vec<id> {
+        let current_thread = env.current_thread;
+        Self::get(env)
+            .pool_stacks
+            .entry(current_thread)
+            .or_default()
+    }
 }
 
 struct NSAutoreleasePoolHostObject {
@@ -38,7 +47,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 + (())addObject:(id)obj {
-    if let Some(current_pool) = State::get(env).pool_stack.last().copied() {
+    if let Some(current_pool) = State::current_pool_stack(env).last().copied() {
         msg![env; current_pool addObject:obj]
     } else {
         log_dbg!("Warning: no active NSAutoreleasePool, leaking {:?}", obj);
@@ -46,8 +55,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 }
 
 - (id)init {
-    assert!(env.current_thread == 0); // TODO: per-thread stacks
-    State::get(env).pool_stack.push(this);
+    State::current_pool_stack(env).push(this);
     log_dbg!("New pool: {:?}", this);
     this
 }
@@ -71,7 +79,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())dealloc {
     log_dbg!("Draining pool: {:?}", this);
-    let pop_res = State::get(env).pool_stack.pop();
+    let pop_res = State::current_pool_stack(env).pop();
     assert!(pop_res == Some(this));
     let host_obj: &mut NSAutoreleasePoolHostObject = env.objc.borrow_mut(this);
     let objects = std::mem::take(&mut host_obj.objects);
@@ -5,18 +5,28 @@
  */
 //! `NSData` and `NSMutableData`.
 
+use super::ns_error::{
+    set_error_out_param, NSCocoaErrorDomain, NSFileReadNoSuchFileError, NSURLErrorDomain,
+    NSURLErrorNotConnectedToInternet,
+};
 use super::ns_string::to_rust_string;
-use super::NSUInteger;
+use super::{NSInteger, NSUInteger};
 use crate::fs::GuestPath;
-use crate::mem::{ConstVoidPtr, MutVoidPtr, Ptr};
+use crate::mem::{ConstVoidPtr, Mem, MutPtr, MutVoidPtr, Ptr};
 use crate::objc::{
-    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
 };
 use crate::Environment;
 
+pub type NSDataReadingOptions = NSUInteger;
+
 struct NSDataHostObject {
     bytes: MutVoidPtr,
     length: NSUInteger,
+    /// Size of the allocation `bytes` points to. This can be larger than
+    /// `length` for `NSMutableData`, so appending doesn't always reallocate.
+    capacity: NSUInteger,
 }
 impl HostObject for NSDataHostObject {}
 
@@ -31,6 +41,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let host_object = Box::new(NSDataHostObject {
         bytes: Ptr::null(),
         length: 0,
+        capacity: 0,
     });
     env.objc.alloc_object(this, host_object, &mut env.mem)
 }
@@ -49,6 +60,20 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
++ (id)dataWithContentsOfURL:(id)url { // NSURL*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithContentsOfURL:url];
+    autorelease(env, new)
+}
+
++ (id)dataWithContentsOfURL:(id)url // NSURL*
+                    options:(NSDataReadingOptions)options
+                      error:(MutPtr<id>)error { // NSError**
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithContentsOfURL:url options:options error:error];
+    autorelease(env, new)
+}
+
 // Calling the standard `init` is also allowed, in which case we just get data
 // of size 0.
 
@@ -58,6 +83,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     assert!(host_object.bytes.is_null() && host_object.length == 0);
     host_object.bytes = bytes;
     host_object.length = length;
+    host_object.capacity = length;
     this
 }
 
@@ -69,6 +95,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     env.mem.memmove(alloc, bytes.cast_const(), length);
     host_object.bytes = alloc;
     host_object.length = length;
+    host_object.capacity = length;
     this
 }
 
@@ -80,13 +107,40 @@ pub const CLASSES: ClassExports = objc_classes! {
         return nil;
     };
     let size = bytes.len().try_into().unwrap();
-    let alloc = env.mem.alloc(size);
-    let slice = env.mem.bytes_at_mut(alloc.cast(), size);
-    slice.copy_from_slice(&bytes);
+    let alloc = env.mem.alloc_and_write_bytes(&bytes).cast();
 
     let host_object = env.objc.borrow_mut::<NSDataHostObject>(this);
     host_object.bytes = alloc;
     host_object.length = size;
+    host_object.capacity = size;
+    this
+}
+
+- (id)initWithContentsOfURL:(id)url { // NSURL*
+    if !msg![env; url isFileURL] {
+        // TODO: fetch from the network once there's networking
+        log!(
+            "TODO: [(NSData*){:?} initWithContentsOfURL:{:?}] for non-file URL, returning nil",
+            this,
+            url
+        );
+        release(env, this);
+        return nil;
+    }
+    let path: id = msg![env; url path];
+    msg![env; this initWithContentsOfFile:path]
+}
+
+- (id)initWithContentsOfURL:(id)url // NSURL*
+                    options:(NSDataReadingOptions)_options
+                      error:(MutPtr<id>)error { // NSError**
+    // The options are only hints about caching and memory mapping.
+    let is_file_url: bool = msg![env; url isFileURL];
+    let this: id = msg![env; this initWithContentsOfURL:url];
+    if this == nil {
+        let (domain, code) = read_error_for_url(is_file_url);
+        set_error_out_param(env, error, domain, code);
+    }
     this
 }
 
@@ -109,6 +163,7 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())dealloc {
     let &NSDataHostObject { bytes, .. } = env.objc.borrow(this);
     if !bytes.is_null() {
+        // This also zeroes the bytes, so nothing sensitive is left behind.
         env.mem.free(bytes);
     }
     env.objc.dealloc_object(this, &mut env.mem)
@@ -128,11 +183,127 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @end
 
+@implementation NSMutableData: NSData
+
++ (id)dataWithCapacity:(NSUInteger)capacity {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithCapacity:capacity];
+    autorelease(env, new)
+}
+
+- (id)initWithCapacity:(NSUInteger)capacity {
+    let bytes = if capacity == 0 {
+        Ptr::null()
+    } else {
+        env.mem.alloc(capacity)
+    };
+    let host_object = env.objc.borrow_mut::<NSDataHostObject>(this);
+    assert!(host_object.bytes.is_null() && host_object.length == 0);
+    host_object.bytes = bytes;
+    host_object.capacity = capacity;
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    let &NSDataHostObject { bytes, length, .. } = env.objc.borrow(this);
+    let new: id = msg_class![env; NSData alloc];
+    if length == 0 {
+        // The pointer may be NULL in this case.
+        return msg![env; new init];
+    }
+    msg![env; new initWithBytes:bytes length:length]
+}
+
+- (MutVoidPtr)mutableBytes {
+    env.objc.borrow::<NSDataHostObject>(this).bytes
+}
+
+- (())appendBytes:(ConstVoidPtr)bytes
+           length:(NSUInteger)length {
+    let host_object = env.objc.borrow_mut::<NSDataHostObject>(this);
+    append_bytes(&mut env.mem, host_object, bytes, length);
+}
+
+// TODO: setLength:, appendData:, replaceBytesInRange:withBytes: etc
+
+@end
+
 };
 
+/// Host part of `appendBytes:length:`. When the allocation is too small, its
+/// size is at least doubled, so that appending many small chunks is cheap.
+fn append_bytes(
+    mem: &mut Mem,
+    host_object: &mut NSDataHostObject,
+    bytes: ConstVoidPtr,
+    length: NSUInteger,
+) {
+    if length == 0 {
+        return;
+    }
+    let new_length = host_object.length.checked_add(length).unwrap();
+    if new_length > host_object.capacity {
+        let new_capacity = new_length.max(host_object.capacity.saturating_mul(2));
+        let new_bytes = mem.alloc(new_capacity);
+        if !host_object.bytes.is_null() {
+            mem.memmove(
+                new_bytes,
+                host_object.bytes.cast_const(),
+                host_object.length,
+            );
+            mem.free(host_object.bytes);
+        }
+        host_object.bytes = new_bytes;
+        host_object.capacity = new_capacity;
+    }
+    let end: MutPtr<u8> = host_object.bytes.cast::<u8>() + host_object.length;
+    mem.memmove(end.cast(), bytes, length);
+    host_object.length = new_length;
+}
+
 pub fn to_rust_slice(env: &mut Environment, data: id) -> &[u8] {
     let borrowed_data = env.objc.borrow::<NSDataHostObject>(data);
     assert!(!borrowed_data.bytes.is_null() && borrowed_data.length != 0);
     env.mem
         .bytes_at(borrowed_data.bytes.cast(), borrowed_data.length)
 }
+
+/// Get the error domain and code for failing to read the contents of a URL.
+/// Only file URLs can be read at the moment, so any other kind fails as if
+/// offline.
+fn read_error_for_url(is_file_url: bool) -> (&'static str, NSInteger) {
+    if is_file_url {
+        (NSCocoaErrorDomain, NSFileReadNoSuchFileError)
+    } else {
+        (NSURLErrorDomain, NSURLErrorNotConnectedToInternet)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_bytes() {
+        let mut mem = Mem::new();
+        let mut data = NSDataHostObject {
+            bytes: Ptr::null(),
+            length: 0,
+            capacity: 0,
+        };
+        let hello = mem.alloc_and_write_bytes(b"Hello, ");
+        let world = mem.alloc_and_write_bytes(b"world!");
+        append_bytes(&mut mem, &mut data, hello.cast_const().cast(), 7);
+        append_bytes(&mut mem, &mut data, world.cast_const().cast(), 6);
+        assert_eq!(data.length, 13);
+        assert!(data.capacity >= 14);
+        assert_eq!(mem.bytes_at(data.bytes.cast(), 13), b"Hello, world!");
+    }
+
+    #[test]
+    fn test_read_error_for_url() {
+        assert_eq!(read_error_for_url(true), ("NSCocoaErrorDomain", 260));
+        assert_eq!(read_error_for_url(false), ("NSURLErrorDomain", -1009));
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSError`.
+
+use super::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use super::NSInteger;
+use crate::dyld::{ConstantExports, HostConstant};
+use crate::mem::MutPtr;
+use crate::objc::{
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr,
+};
+use crate::Environment;
+
+pub const NSCocoaErrorDomain: &str = "NSCocoaErrorDomain";
+pub const NSURLErrorDomain: &str = "NSURLErrorDomain";
+pub const NSLocalizedDescriptionKey: &str = "NSLocalizedDescription";
+
+/// `NSCocoaErrorDomain` code for a file that doesn't exist.
+pub const NSFileReadNoSuchFileError: NSInteger = 260;
+/// `NSURLErrorDomain` code used for all network requests, since there's no
+/// networking.
+pub const NSURLErrorNotConnectedToInternet: NSInteger = -1009;
+
+pub const CONSTANTS: ConstantExports = &[
+    (
+        "_NSCocoaErrorDomain",
+        HostConstant::NSString(NSCocoaErrorDomain),
+    ),
+    (
+        "_NSURLErrorDomain",
+        HostConstant::NSString(NSURLErrorDomain),
+    ),
+    (
+        "_NSLocalizedDescriptionKey",
+        HostConstant::NSString(NSLocalizedDescriptionKey),
+    ),
+];
+
+#[derive(Default)]
+struct NSErrorHostObject {
+    /// Strong reference
+    domain: id,
+    code: NSInteger,
+    /// Strong reference, may be `nil`.
+    user_info: id,
+}
+impl HostObject for NSErrorHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSError: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::<NSErrorHostObject>::default();
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)errorWithDomain:(id)domain // NSString*
+                 code:(NSInteger)code
+             userInfo:(id)user_info { // NSDictionary*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithDomain:domain code:code userInfo:user_info];
+    autorelease(env, new)
+}
+
+- (id)initWithDomain:(id)domain // NSString*
+                code:(NSInteger)code
+            userInfo:(id)user_info { // NSDictionary*
+    let domain: id = msg![env; domain copy];
+    let user_info: id = msg![env; user_info copy];
+    *env.objc.borrow_mut(this) = NSErrorHostObject {
+        domain,
+        code,
+        user_info,
+    };
+    this
+}
+
+- (())dealloc {
+    let &NSErrorHostObject {
+        domain, user_info, ..
+    } = env.objc.borrow(this);
+    release(env, domain);
+    release(env, user_info);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (id)domain {
+    env.objc.borrow::<NSErrorHostObject>(this).domain
+}
+
+- (NSInteger)code {
+    env.objc.borrow::<NSErrorHostObject>(this).code
+}
+
+- (id)userInfo {
+    env.objc.borrow::<NSErrorHostObject>(this).user_info
+}
+
+- (id)localizedDescription {
+    let &NSErrorHostObject {
+        domain,
+        code,
+        user_info,
+    } = env.objc.borrow(this);
+    if user_info != nil {
+        let key = get_static_str(env, NSLocalizedDescriptionKey);
+        let description: id = msg![env; user_info objectForKey:key];
+        if description != nil {
+            return description;
+        }
+    }
+    let domain = to_rust_string(env, domain);
+    let description = default_localized_description(&domain, code);
+    let description = from_rust_string(env, description);
+    autorelease(env, description)
+}
+
+@end
+
+};
+
+/// The description used when the user info doesn't provide one. Apple's
+/// wording differs between domains, but this is the generic fallback.
+fn default_localized_description(domain: &str, code: NSInteger) -> String {
+    format!(
+        "The operation couldn\u{2019}t be completed. ({} error {}.)",
+        domain, code
+    )
+}
+
+/// Write a new autoreleased `NSError` to an `NSError**` out-parameter, if the
+/// caller provided one.
+pub fn set_error_out_param(
+    env: &mut Environment,
+    out_param: MutPtr<id>,
+    domain: &'static str,
+    code: NSInteger,
+) {
+    if out_param.is_null() {
+        return;
+    }
+    let domain = get_static_str(env, domain);
+    let error: id = msg_class![env; NSError errorWithDomain:domain code:code userInfo:nil];
+    env.mem.write(out_param, error);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_localized_description() {
+        assert_eq!(
+            default_localized_description(NSCocoaErrorDomain, NSFileReadNoSuchFileError),
+            "The operation couldn\u{2019}t be completed. (NSCocoaErrorDomain error 260.)"
+        );
+        assert_eq!(
+            default_localized_description(NSURLErrorDomain, NSURLErrorNotConnectedToInternet),
+            "The operation couldn\u{2019}t be completed. (NSURLErrorDomain error -1009.)"
+        );
+    }
+}
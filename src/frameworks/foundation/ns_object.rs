@@ -18,8 +18,8 @@ use super::ns_string::to_rust_string;
 use super::NSUInteger;
 use crate::mem::MutVoidPtr;
 use crate::objc::{
-    id, msg, msg_class, msg_send, objc_classes, Class, ClassExports, NSZonePtr, ObjC,
-    TrivialHostObject, SEL,
+    id, msg, msg_class, msg_send, objc_classes, remove_associated_objects, Class, ClassExports,
+    NSZonePtr, ObjC, TrivialHostObject, SEL,
 };
 
 pub const CLASSES: ClassExports = objc_classes! {
@@ -74,6 +74,8 @@ pub const CLASSES: ClassExports = objc_classes! {
     log_dbg!("[{:?} release]", this);
     if env.objc.decrement_refcount(this) {
         () = msg![env; this dealloc];
+        // Apple's runtime also does this after the dealloc methods have run.
+        remove_associated_objects(env, this);
     }
 }
 - (id)autorelease {
@@ -116,6 +118,11 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; this copyWithZone:(MutVoidPtr::null())]
 }
 
+// Helper for NSMutableCopying
+- (id)mutableCopy {
+    msg![env; this mutableCopyWithZone:(MutVoidPtr::null())]
+}
+
 
 // NSKeyValueCoding
 - (())setValue:(id)value
@@ -90,8 +90,13 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (())addTimer:(id)timer // NSTimer*
        forMode:(NSRunLoopMode)mode {
     let default_mode = ns_string::get_static_str(env, NSDefaultRunLoopMode);
-    // TODO: handle other modes
-    assert!(msg![env; mode isEqualToString:default_mode]);
+    let common_modes = ns_string::get_static_str(env, NSRunLoopCommonModes);
+    // TODO: handle other modes. The common modes include the default mode, and
+    // that's the only one we run in, so they're equivalent for now.
+    assert!(
+        msg![env; mode isEqualToString:default_mode] ||
+        msg![env; mode isEqualToString:common_modes]
+    );
 
     log_dbg!("Adding timer {:?} to run loop {:?}", timer, this);
 
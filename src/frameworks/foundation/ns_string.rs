@@ -8,6 +8,7 @@
 mod path_algorithms;
 
 use super::ns_array;
+use super::ns_error::{set_error_out_param, NSCocoaErrorDomain, NSFileReadNoSuchFileError};
 use super::{
     NSComparisonResult, NSOrderedAscending, NSOrderedDescending, NSOrderedSame, NSUInteger,
 };
@@ -19,8 +20,8 @@ use crate::fs::GuestPath;
 use crate::mach_o::MachO;
 use crate::mem::{guest_size_of, ConstPtr, Mem, MutPtr, Ptr, SafeRead};
 use crate::objc::{
-    autorelease, id, msg, msg_class, nil, objc_classes, retain, Class, ClassExports, HostObject,
-    NSZonePtr, ObjC,
+    autorelease, id, msg, msg_class, nil, objc_classes, release, retain, Class, ClassExports,
+    HostObject, NSZonePtr, ObjC,
 };
 use crate::Environment;
 use std::borrow::Cow;
@@ -779,11 +780,13 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)initWithContentsOfFile:(id)path // NSString*
                     encoding:(NSStringEncoding)encoding
                        error:(MutPtr<id>)error { // NSError**
-    assert!(error.is_null()); // TODO: error handling
-
     // TODO: avoid copy?
     let path = to_rust_string(env, path);
-    let bytes = env.fs.read(GuestPath::new(&path)).unwrap();
+    let Ok(bytes) = env.fs.read(GuestPath::new(&path)) else {
+        set_error_out_param(env, error, NSCocoaErrorDomain, NSFileReadNoSuchFileError);
+        release(env, this);
+        return nil;
+    };
 
     let host_object = StringHostObject::decode(Cow::Owned(bytes), encoding);
 
@@ -5,7 +5,52 @@
  */
 //! `NSThread`.
 
-use crate::objc::{id, objc_classes, ClassExports};
+use super::{time_interval_to_duration, NSTimeInterval};
+use crate::abi::GuestFunction;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::objc::{
+    id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports, HostObject,
+    NSZonePtr, SEL,
+};
+use crate::{Environment, ThreadID};
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct State {
+    /// `NSThread` objects for running threads, keyed by thread ID.
+    ///
+    /// The key is the same [ThreadID] as `env.current_thread`, which is also
+    /// what the rest of the emulator (e.g. the waiter lists of semaphores and
+    /// mutexes) uses to identify a thread. So `[NSThread currentThread]` on
+    /// some thread always corresponds to the [ThreadID] recorded for anything
+    /// that thread blocks on. There is at most one object per thread, and it
+    /// is removed when the thread exits.
+    ns_threads: HashMap<ThreadID, id>,
+    /// Guest function for [_touchHLE_NSThreadInvocationHelper], created on
+    /// first use.
+    invocation_helper: Option<GuestFunction>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.framework_state.foundation.ns_thread
+    }
+}
+
+struct NSThreadHostObject {
+    /// Strong reference
+    target: id,
+    selector: Option<SEL>,
+    /// Strong reference
+    object: id,
+    /// Set by `start`.
+    thread: Option<ThreadID>,
+    finished: bool,
+    /// Strong reference
+    name: id,
+    /// Strong reference, created on first use.
+    thread_dictionary: id,
+}
+impl HostObject for NSThreadHostObject {}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -13,6 +58,29 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation NSThread: NSObject
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSThreadHostObject {
+        target: nil,
+        selector: None,
+        object: nil,
+        thread: None,
+        finished: false,
+        name: nil,
+        thread_dictionary: nil,
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (())detachNewThreadSelector:(SEL)selector
+                     toTarget:(id)target
+                   withObject:(id)object {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithTarget:target selector:selector object:object];
+    let _: () = msg![env; new start];
+    // The thread keeps its own reference until it finishes.
+    release(env, new);
+}
+
 + (f64)threadPriority {
     log!("TODO: [NSThread threadPriority] (not implemented yet)");
     1.0
@@ -23,15 +91,202 @@ pub const CLASSES: ClassExports = objc_classes! {
     true
 }
 
++ (())sleepForTimeInterval:(NSTimeInterval)ti {
+    log_dbg!("[NSThread sleepForTimeInterval:{:?}]", ti);
+    env.sleep(time_interval_to_duration(ti));
+}
+
++ (())sleepUntilDate:(id)date {
+    let ti: NSTimeInterval = msg![env; date timeIntervalSinceNow];
+    log_dbg!("[NSThread sleepUntilDate:{:?}] => sleeping for {:?}s", date, ti);
+    env.sleep(time_interval_to_duration(ti));
+}
+
++ (bool)isMainThread {
+    env.current_thread == 0
+}
+
++ (id)mainThread {
+    object_for_thread(env, 0)
+}
+
 + (id)currentThread {
-    // Simple hack to make the `setThreadPriority:` work as an instance method
-    // (it's both a class and an instance method). Must be replaced if we ever
-    // need to support other methods.
+    let current_thread = env.current_thread;
+    object_for_thread(env, current_thread)
+}
+
+- (id)initWithTarget:(id)target selector:(SEL)selector object:(id)object {
+    retain(env, target);
+    retain(env, object);
+    let host_object = env.objc.borrow_mut::<NSThreadHostObject>(this);
+    host_object.target = target;
+    host_object.selector = Some(selector);
+    host_object.object = object;
     this
 }
 
-// TODO: construction etc
+- (())start {
+    // TODO: throw proper exception?
+    assert!(env.objc.borrow::<NSThreadHostObject>(this).thread.is_none());
+    start_thread(env, this);
+}
+
+// Subclasses can override this instead of providing a target and selector.
+- (())main {
+    let &NSThreadHostObject {
+        target,
+        selector,
+        object,
+        ..
+    } = env.objc.borrow(this);
+    let Some(selector) = selector else {
+        return;
+    };
+    log_dbg!(
+        "NSThread {:?} sending {:?} message to {:?}",
+        this,
+        selector.as_str(&env.mem),
+        target,
+    );
+    let _: () = msg_send(env, (target, selector, object));
+}
+
+- (bool)isExecuting {
+    let &NSThreadHostObject {
+        thread, finished, ..
+    } = env.objc.borrow(this);
+    thread.is_some() && !finished
+}
+
+- (bool)isFinished {
+    env.objc.borrow::<NSThreadHostObject>(this).finished
+}
+
+- (bool)isMainThread {
+    env.objc.borrow::<NSThreadHostObject>(this).thread == Some(0)
+}
+
+- (f64)threadPriority {
+    log!("TODO: [{:?} threadPriority] (not implemented yet)", this);
+    1.0
+}
+
+- (bool)setThreadPriority:(f64)priority {
+    log!("TODO: [{:?} setThreadPriority:{:?}] (ignored)", this, priority);
+    true
+}
+
+- (id)name {
+    env.objc.borrow::<NSThreadHostObject>(this).name
+}
+
+- (())setName:(id)name { // NSString*
+    let name: id = msg![env; name copy];
+    let old_name = std::mem::replace(
+        &mut env.objc.borrow_mut::<NSThreadHostObject>(this).name,
+        name,
+    );
+    release(env, old_name);
+}
+
+- (id)threadDictionary {
+    let existing = env.objc.borrow::<NSThreadHostObject>(this).thread_dictionary;
+    if existing != nil {
+        return existing;
+    }
+    let dict: id = msg_class![env; NSMutableDictionary new];
+    env.objc.borrow_mut::<NSThreadHostObject>(this).thread_dictionary = dict;
+    dict
+}
+
+- (())dealloc {
+    let &NSThreadHostObject {
+        target,
+        object,
+        name,
+        thread_dictionary,
+        ..
+    } = env.objc.borrow(this);
+    release(env, target);
+    release(env, object);
+    release(env, name);
+    release(env, thread_dictionary);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
 
 @end
 
 };
+
+/// Get the `NSThread` object for a thread, creating it if necessary. Threads
+/// not created by `NSThread`, like the main thread, get one on first use.
+fn object_for_thread(env: &mut Environment, thread: ThreadID) -> id {
+    if let Some(&ns_thread) = State::get(env).ns_threads.get(&thread) {
+        return ns_thread;
+    }
+    // Released by thread_exited(), except for the main thread's object.
+    let class = env.objc.get_known_class("NSThread", &mut env.mem);
+    let ns_thread: id = msg![env; class alloc];
+    env.objc.borrow_mut::<NSThreadHostObject>(ns_thread).thread = Some(thread);
+    State::get(env).ns_threads.insert(thread, ns_thread);
+    ns_thread
+}
+
+/// Called when a thread finishes (while it can still run guest code), to
+/// release an `NSThread` object created for it by [object_for_thread]. Threads
+/// started by `NSThread` have already had theirs removed by
+/// [_touchHLE_NSThreadInvocationHelper].
+pub fn thread_exited(env: &mut Environment, thread: ThreadID) {
+    let Some(ns_thread) = State::get(env).ns_threads.remove(&thread) else {
+        return;
+    };
+    env.objc
+        .borrow_mut::<NSThreadHostObject>(ns_thread)
+        .finished = true;
+    release(env, ns_thread);
+}
+
+/// Spawn a guest thread that will send `main` to the `NSThread`.
+fn start_thread(env: &mut Environment, ns_thread: id) {
+    let helper = if let Some(helper) = State::get(env).invocation_helper {
+        helper
+    } else {
+        let helper = env
+            .dyld
+            .create_proc_address(
+                &mut env.mem,
+                &mut env.cpu,
+                "__touchHLE_NSThreadInvocationHelper",
+            )
+            .unwrap();
+        State::get(env).invocation_helper = Some(helper);
+        helper
+    };
+
+    // Released by the helper when the thread finishes.
+    retain(env, ns_thread);
+    let thread_id = env.new_thread(helper, ns_thread.cast());
+    env.objc.borrow_mut::<NSThreadHostObject>(ns_thread).thread = Some(thread_id);
+    State::get(env).ns_threads.insert(thread_id, ns_thread);
+    log_dbg!(
+        "Started NSThread {:?} (thread ID: {})",
+        ns_thread,
+        thread_id
+    );
+}
+
+/// Start routine for threads created by `NSThread`, not visible to the guest.
+fn _touchHLE_NSThreadInvocationHelper(env: &mut Environment, ns_thread: id) {
+    let pool: id = msg_class![env; NSAutoreleasePool new];
+    let _: () = msg![env; ns_thread main];
+    release(env, pool);
+
+    env.objc
+        .borrow_mut::<NSThreadHostObject>(ns_thread)
+        .finished = true;
+    let current_thread = env.current_thread;
+    State::get(env).ns_threads.remove(&current_thread);
+    release(env, ns_thread);
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(_touchHLE_NSThreadInvocationHelper(_))];
@@ -6,8 +6,10 @@
 //! `NSTimer`.
 
 use super::ns_run_loop::NSDefaultRunLoopMode;
-use super::NSTimeInterval;
 use super::{ns_run_loop, ns_string};
+use super::{time_interval_to_duration, NSTimeInterval};
+use crate::abi::{CallFromHost, GuestFunction};
+use crate::mem::MutVoidPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, msg_send, nil, objc_classes, release, retain, ClassExports,
     HostObject, SEL,
@@ -19,9 +21,7 @@ struct NSTimerHostObject {
     ns_interval: NSTimeInterval,
     /// Copy of `ns_interval` in Rust's type for time intervals. Keep in sync!
     rust_interval: Duration,
-    /// Strong reference
-    target: id,
-    selector: SEL,
+    action: TimerAction,
     /// Strong reference
     user_info: id,
     repeats: bool,
@@ -31,6 +31,18 @@ struct NSTimerHostObject {
 }
 impl HostObject for NSTimerHostObject {}
 
+/// What a timer does when it fires.
+#[derive(Copy, Clone)]
+enum TimerAction {
+    /// Send a message. The target is a strong reference.
+    Message { target: id, selector: SEL },
+    /// Call a callout, for timers created by `CFRunLoopTimerCreate`.
+    CFCallout {
+        callout: GuestFunction,
+        info: MutVoidPtr,
+    },
+}
+
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
@@ -43,8 +55,8 @@ pub const CLASSES: ClassExports = objc_classes! {
                    selector:(SEL)selector
                    userInfo:(id)user_info
                     repeats:(bool)repeats {
-    let ns_interval = ns_interval.max(0.0001);
-    let rust_interval = Duration::from_secs_f64(ns_interval);
+    let rust_interval = time_interval_to_duration(ns_interval.max(0.0001));
+    let ns_interval = rust_interval.as_secs_f64();
 
     retain(env, target);
     retain(env, user_info);
@@ -52,8 +64,7 @@ pub const CLASSES: ClassExports = objc_classes! {
     let host_object = Box::new(NSTimerHostObject {
         ns_interval,
         rust_interval,
-        target,
-        selector,
+        action: TimerAction::Message { target, selector },
         user_info,
         repeats,
         due_by: Some(Instant::now().checked_add(rust_interval).unwrap()),
@@ -94,11 +105,13 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 - (())dealloc {
     let &NSTimerHostObject {
-        target,
+        action,
         user_info,
         ..
     } = env.objc.borrow(this);
-    release(env, target);
+    if let TimerAction::Message { target, .. } = action {
+        release(env, target);
+    }
     release(env, user_info);
     env.objc.dealloc_object(this, &mut env.mem)
 }
@@ -133,6 +146,45 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 };
 
+/// For use by `CFRunLoopTimerCreate`: create a timer that calls a guest
+/// function when it fires. The first firing is after `delay`, and it repeats
+/// if `interval` is greater than zero. The timer is owned by the caller.
+pub fn new_cf_timer(
+    env: &mut Environment,
+    delay: NSTimeInterval,
+    interval: NSTimeInterval,
+    callout: GuestFunction,
+    info: MutVoidPtr,
+) -> id {
+    let repeats = interval > 0.0;
+    let rust_interval = time_interval_to_duration(interval.max(0.0001));
+    let ns_interval = rust_interval.as_secs_f64();
+    let delay = time_interval_to_duration(delay);
+
+    let host_object = Box::new(NSTimerHostObject {
+        ns_interval,
+        rust_interval,
+        action: TimerAction::CFCallout { callout, info },
+        user_info: nil,
+        repeats,
+        due_by: Some(Instant::now().checked_add(delay).unwrap()),
+        run_loop: nil,
+    });
+    let class = env.objc.get_known_class("NSTimer", &mut env.mem);
+    let new = env.objc.alloc_object(class, host_object, &mut env.mem);
+
+    log_dbg!(
+        "New {} CF timer {:?}, interval {}s, callout {:?}, info {:?}",
+        if repeats { "repeating" } else { "single-use" },
+        new,
+        ns_interval,
+        callout,
+        info,
+    );
+
+    new
+}
+
 /// For use by `NSRunLoop`
 pub(super) fn set_run_loop(env: &mut Environment, timer: id, run_loop: id) {
     let host_object = env.objc.borrow_mut::<NSTimerHostObject>(timer);
@@ -140,16 +192,43 @@ pub(super) fn set_run_loop(env: &mut Environment, timer: id, run_loop: id) {
     host_object.run_loop = run_loop;
 }
 
+/// Work out when a repeating timer that was due at `due_by` and is firing at
+/// `now` should next fire. Also returns how many intervals that is ahead of
+/// `due_by`.
+fn next_due_by(due_by: Instant, now: Instant, interval: Duration) -> (Instant, u32) {
+    // When rescheduling a repeating timer, the next firing should be based on
+    // when the timer should have fired, not when it actually fired, so that
+    // there is no drift over time.
+    //
+    // For example, if a timer has an interval of 60s and starts at 00:00, the
+    // first firing would be scheduled for 01:00, and the second firing should
+    // be scheduled for 02:00, even if the first firing was at 01:01.
+    //
+    // However: if the timer handling is delayed past a whole interval, it
+    // should not try to catch up. For example, if the first firing is
+    // scheduled for 01:00 but happens at 02:30, then the next firing should be
+    // scheduled for 03:00.
+    let overdue_by = now.duration_since(due_by);
+    // TODO: Use `.div_duration_f64()` once that is stabilized.
+    let advance_by = (overdue_by.as_secs_f64() / interval.as_secs_f64())
+        .max(1.0)
+        .ceil();
+    assert!(advance_by == (advance_by as u32) as f64);
+    let advance_by = advance_by as u32;
+    let new_due_by = due_by
+        .checked_add(interval.checked_mul(advance_by).unwrap())
+        .unwrap();
+    (new_due_by, advance_by)
+}
+
 /// For use by `NSRunLoop`: check if a timer is due to fire and fire it if
 /// necessary.
 ///
 /// Returns the next firing time, if any.
 pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant> {
     let &NSTimerHostObject {
-        ns_interval,
         rust_interval,
-        target,
-        selector,
+        action,
         repeats,
         due_by,
         run_loop,
@@ -174,47 +253,83 @@ pub(super) fn handle_timer(env: &mut Environment, timer: id) -> Option<Instant>
     // Advancing the timer before sending its message seems like a good idea
     // considering this function is potentially re-entrant.
     let new_due_by = if repeats {
-        // When rescheduling a repeating timer, the next firing should be based
-        // on when the timer should have fired, not when it actually fired, so
-        // that there is no drift over time.
-        //
-        // For example, if a timer has an interval of 60s and starts at 00:00,
-        // the first firing would be scheduled for 01:00, and the second firing
-        // should be scheduled for 02:00, even if the first firing was at 01:01.
-        //
-        // However: if the timer handling is delayed past a whole interval, it
-        // should not try to catch up. For example, if the first firing is
-        // scheduled for 01:00 but happens at 02:30, then the next firing should
-        // be scheduled for 03:00.
-        // TODO: Use `.div_duration_f64()` once that is stabilized.
-        let advance_by = (overdue_by.as_secs_f64() / ns_interval).max(1.0).ceil();
-        assert!(advance_by == (advance_by as u32) as f64);
-        let advance_by = advance_by as u32;
+        let (new_due_by, advance_by) = next_due_by(due_by, now, rust_interval);
         if advance_by > 1 {
             log_dbg!("Warning: Timer {:?} is lagging. It is overdue by {}s and has missed {} interval(s)!", timer, overdue_by.as_secs_f64(), advance_by - 1);
         }
-        let advance_by = rust_interval.checked_mul(advance_by).unwrap();
-        Some(due_by.checked_add(advance_by).unwrap())
+        Some(new_due_by)
     } else {
         ns_run_loop::remove_timer(env, run_loop, timer);
         None
     };
     env.objc.borrow_mut::<NSTimerHostObject>(timer).due_by = new_due_by;
 
-    log_dbg!(
-        "Timer {:?} fired, sending {:?} message to {:?}",
-        timer,
-        selector.as_str(&env.mem),
-        target
-    );
-
     let pool: id = msg_class![env; NSAutoreleasePool new];
 
-    // Signature should be `- (void)timerDidFire:(NSTimer *)which`.
-    let _: () = msg_send(env, (target, selector, timer));
+    match action {
+        TimerAction::Message { target, selector } => {
+            log_dbg!(
+                "Timer {:?} fired, sending {:?} message to {:?}",
+                timer,
+                selector.as_str(&env.mem),
+                target
+            );
+            // Signature should be `- (void)timerDidFire:(NSTimer *)which`.
+            let _: () = msg_send(env, (target, selector, timer));
+        }
+        TimerAction::CFCallout { callout, info } => {
+            log_dbg!(
+                "Timer {:?} fired, calling callout {:?} with info {:?}",
+                timer,
+                callout,
+                info
+            );
+            // Signature is `void (*)(CFRunLoopTimerRef timer, void *info)`.
+            let () = callout.call_from_host(env, (timer, info));
+        }
+    }
 
     release(env, timer);
     release(env, pool);
 
     new_due_by
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repeating_timer() {
+        // Simulate run loop iterations every 4ms with a 10ms repeating timer,
+        // counting how often it fires.
+        let interval = Duration::from_millis(10);
+        let start = Instant::now();
+        let mut due_by = start + interval;
+        let mut fired = 0;
+        for i in 1..=25 {
+            let now = start + Duration::from_millis(4 * i);
+            if due_by <= now {
+                fired += 1;
+                let (new_due_by, advance_by) = next_due_by(due_by, now, interval);
+                assert_eq!(advance_by, 1);
+                due_by = new_due_by;
+            }
+        }
+        // 100ms passed, so it fired at 10ms, 20ms, ..., 100ms.
+        assert_eq!(fired, 10);
+        assert_eq!(due_by, start + Duration::from_millis(110));
+    }
+
+    #[test]
+    fn test_lagging_timer() {
+        // A 60s timer due at 01:00 that only gets to fire at 02:30 should next
+        // fire at 03:00, not try to catch up.
+        let interval = Duration::from_secs(60);
+        let due_by = Instant::now() + interval;
+        let now = due_by + Duration::from_secs(90);
+        let (new_due_by, advance_by) = next_due_by(due_by, now, interval);
+        assert_eq!(advance_by, 2);
+        assert_eq!(new_due_by, due_by + Duration::from_secs(120));
+    }
+}
@@ -94,6 +94,10 @@ pub const CLASSES: ClassExports = objc_classes! {
     }
 }
 
+- (bool)isFileURL {
+    matches!(env.objc.borrow(this), NSURLHostObject::FileURL { .. })
+}
+
 - (id)absoluteURL {
     // FIXME: don't assume URL is already absolute
     let &NSURLHostObject::OtherURL { ns_string } = env.objc.borrow(this) else {
@@ -0,0 +1,309 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLRequest` and `NSMutableURLRequest`.
+
+use super::ns_dictionary::dict_from_keys_and_objects;
+use super::ns_string::{get_static_str, to_rust_string};
+use super::{NSTimeInterval, NSUInteger};
+use crate::objc::{
+    autorelease, id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+
+pub type NSURLRequestCachePolicy = NSUInteger;
+pub const NSURLRequestUseProtocolCachePolicy: NSURLRequestCachePolicy = 0;
+
+/// Default for `timeoutInterval`, in seconds.
+const DEFAULT_TIMEOUT_INTERVAL: NSTimeInterval = 60.0;
+
+#[derive(Default)]
+struct NSURLRequestHostObject {
+    /// Strong reference
+    url: id,
+    /// Strong reference, `nil` means `GET`.
+    http_method: id,
+    /// Strong reference
+    http_body: id,
+    /// Strong references to the field names and values. Field names are
+    /// case-insensitive, so this isn't a dictionary.
+    headers: Vec<(id, id)>,
+    /// TODO: use this once there's networking
+    cache_policy: NSURLRequestCachePolicy,
+    /// TODO: use this once there's networking
+    timeout_interval: NSTimeInterval,
+}
+impl HostObject for NSURLRequestHostObject {}
+impl NSURLRequestHostObject {
+    /// A request with Apple's defaults for everything.
+    fn new() -> Self {
+        NSURLRequestHostObject {
+            cache_policy: NSURLRequestUseProtocolCachePolicy,
+            timeout_interval: DEFAULT_TIMEOUT_INTERVAL,
+            ..Default::default()
+        }
+    }
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLRequest: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSURLRequestHostObject::new());
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
++ (id)requestWithURL:(id)url { // NSURL*
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url];
+    autorelease(env, new)
+}
+
++ (id)requestWithURL:(id)url // NSURL*
+         cachePolicy:(NSURLRequestCachePolicy)cache_policy
+     timeoutInterval:(NSTimeInterval)timeout_interval {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithURL:url
+                                cachePolicy:cache_policy
+                            timeoutInterval:timeout_interval];
+    autorelease(env, new)
+}
+
+- (id)initWithURL:(id)url { // NSURL*
+    let url: id = msg![env; url copy];
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).url = url;
+    this
+}
+
+- (id)initWithURL:(id)url // NSURL*
+      cachePolicy:(NSURLRequestCachePolicy)cache_policy
+  timeoutInterval:(NSTimeInterval)timeout_interval {
+    let this: id = msg![env; this initWithURL:url];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    host_object.cache_policy = cache_policy;
+    host_object.timeout_interval = timeout_interval;
+    this
+}
+
+- (())dealloc {
+    let host_object: NSURLRequestHostObject = std::mem::take(env.objc.borrow_mut(this));
+    release(env, host_object.url);
+    release(env, host_object.http_method);
+    release(env, host_object.http_body);
+    for (field, value) in host_object.headers {
+        release(env, field);
+        release(env, value);
+    }
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    // Immutable, so no need to make a new object.
+    retain(env, this)
+}
+
+// NSMutableCopying implementation
+- (id)mutableCopyWithZone:(NSZonePtr)_zone {
+    copy_request(env, this, "NSMutableURLRequest")
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLRequestHostObject>(this).url
+}
+
+- (id)HTTPMethod {
+    let method = env.objc.borrow::<NSURLRequestHostObject>(this).http_method;
+    if method == nil {
+        get_static_str(env, "GET")
+    } else {
+        method
+    }
+}
+
+- (id)HTTPBody {
+    env.objc.borrow::<NSURLRequestHostObject>(this).http_body
+}
+
+- (NSURLRequestCachePolicy)cachePolicy {
+    env.objc.borrow::<NSURLRequestHostObject>(this).cache_policy
+}
+
+- (NSTimeInterval)timeoutInterval {
+    env.objc.borrow::<NSURLRequestHostObject>(this).timeout_interval
+}
+
+- (id)allHTTPHeaderFields {
+    let headers = env.objc.borrow::<NSURLRequestHostObject>(this).headers.clone();
+    if headers.is_empty() {
+        return nil;
+    }
+    let dict = dict_from_keys_and_objects(env, &headers);
+    autorelease(env, dict)
+}
+
+- (id)valueForHTTPHeaderField:(id)field { // NSString*
+    match find_header(env, this, field) {
+        Some(idx) => env.objc.borrow::<NSURLRequestHostObject>(this).headers[idx].1,
+        None => nil,
+    }
+}
+
+// TODO: more constructors, more accessors
+
+@end
+
+@implementation NSMutableURLRequest: NSURLRequest
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    copy_request(env, this, "NSURLRequest")
+}
+
+- (())setURL:(id)url { // NSURL*
+    let url: id = msg![env; url copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    let old_url = std::mem::replace(&mut host_object.url, url);
+    release(env, old_url);
+}
+
+- (())setHTTPMethod:(id)method { // NSString*
+    let method: id = msg![env; method copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    let old_method = std::mem::replace(&mut host_object.http_method, method);
+    release(env, old_method);
+}
+
+- (())setHTTPBody:(id)body { // NSData*
+    let body: id = msg![env; body copy];
+    let host_object = env.objc.borrow_mut::<NSURLRequestHostObject>(this);
+    let old_body = std::mem::replace(&mut host_object.http_body, body);
+    release(env, old_body);
+}
+
+- (())setCachePolicy:(NSURLRequestCachePolicy)cache_policy {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).cache_policy = cache_policy;
+}
+
+- (())setTimeoutInterval:(NSTimeInterval)timeout_interval {
+    env.objc.borrow_mut::<NSURLRequestHostObject>(this).timeout_interval = timeout_interval;
+}
+
+- (())setValue:(id)value // NSString*
+forHTTPHeaderField:(id)field { // NSString*
+    let existing = find_header(env, this, field);
+    if let Some(idx) = existing {
+        let (old_field, old_value) = env
+            .objc
+            .borrow_mut::<NSURLRequestHostObject>(this)
+            .headers
+            .remove(idx);
+        release(env, old_field);
+        release(env, old_value);
+    }
+    if value == nil {
+        return;
+    }
+    let field: id = msg![env; field copy];
+    let value: id = msg![env; value copy];
+    env.objc
+        .borrow_mut::<NSURLRequestHostObject>(this)
+        .headers
+        .push((field, value));
+}
+
+@end
+
+};
+
+/// Find the index of a header field, ignoring case.
+fn find_header(env: &mut Environment, request: id, field: id) -> Option<usize> {
+    let field = to_rust_string(env, field);
+    let headers = env
+        .objc
+        .borrow::<NSURLRequestHostObject>(request)
+        .headers
+        .clone();
+    header_index(&headers, &field, |name| to_rust_string(env, name))
+}
+
+/// Find the index of a header field in a list of field names and values,
+/// ignoring case. `name_of` gets a field name as a string.
+fn header_index<T: Copy, S: AsRef<str>>(
+    headers: &[(T, T)],
+    field: &str,
+    mut name_of: impl FnMut(T) -> S,
+) -> Option<usize> {
+    headers
+        .iter()
+        .position(|&(name, _)| name_of(name).as_ref().eq_ignore_ascii_case(field))
+}
+
+/// Make a new request of the named class with the same contents as `request`.
+/// Everything is copied, so that mutating one request (or the objects it was
+/// given) can't affect the other.
+fn copy_request(env: &mut Environment, request: id, class_name: &str) -> id {
+    let class = env.objc.get_known_class(class_name, &mut env.mem);
+    let new: id = msg![env; class alloc];
+
+    let &NSURLRequestHostObject {
+        url,
+        http_method,
+        http_body,
+        cache_policy,
+        timeout_interval,
+        ..
+    } = env.objc.borrow(request);
+    let old_headers = env
+        .objc
+        .borrow::<NSURLRequestHostObject>(request)
+        .headers
+        .clone();
+    let mut headers = Vec::with_capacity(old_headers.len());
+    for (field, value) in old_headers {
+        let field: id = msg![env; field copy];
+        let value: id = msg![env; value copy];
+        headers.push((field, value));
+    }
+    let url: id = msg![env; url copy];
+    let http_method: id = msg![env; http_method copy];
+    let http_body: id = msg![env; http_body copy];
+    *env.objc.borrow_mut(new) = NSURLRequestHostObject {
+        url,
+        http_method,
+        http_body,
+        headers,
+        cache_policy,
+        timeout_interval,
+    };
+    new
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_defaults() {
+        let request = NSURLRequestHostObject::new();
+        assert_eq!(request.cache_policy, NSURLRequestUseProtocolCachePolicy);
+        assert_eq!(request.timeout_interval, 60.0);
+        assert_eq!(request.http_method, nil);
+        assert!(request.headers.is_empty());
+    }
+
+    #[test]
+    fn test_header_index() {
+        let headers = [("Content-Type", "text/plain"), ("X-Custom", "1")];
+        let index = |field| header_index(&headers, field, |name| name);
+        assert_eq!(index("Content-Type"), Some(0));
+        assert_eq!(index("content-type"), Some(0));
+        assert_eq!(index("X-CUSTOM"), Some(1));
+        assert_eq!(index("Content-Length"), None);
+    }
+}
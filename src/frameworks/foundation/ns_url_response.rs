@@ -0,0 +1,200 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `NSURLResponse` and `NSHTTPURLResponse`.
+
+use super::ns_dictionary::DictionaryHostObject;
+use super::ns_string::{from_rust_string, to_rust_string};
+use super::NSInteger;
+use crate::objc::{
+    id, msg, nil, objc_classes, release, retain, ClassExports, HostObject, NSZonePtr,
+};
+use crate::Environment;
+
+/// Value of `expectedContentLength` when the length isn't known.
+const NSURLResponseUnknownLength: i64 = -1;
+
+#[derive(Default)]
+struct NSURLResponseHostObject {
+    /// Strong reference
+    url: id,
+    /// Strong reference
+    mime_type: id,
+    expected_content_length: i64,
+    /// Strong reference
+    text_encoding_name: id,
+    /// Only used by `NSHTTPURLResponse`.
+    status_code: NSInteger,
+    /// Strong reference. Only used by `NSHTTPURLResponse`.
+    header_fields: id,
+}
+impl HostObject for NSURLResponseHostObject {}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation NSURLResponse: NSObject
+
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSURLResponseHostObject {
+        expected_content_length: NSURLResponseUnknownLength,
+        ..Default::default()
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+- (id)initWithURL:(id)url // NSURL*
+         MIMEType:(id)mime_type // NSString*
+expectedContentLength:(NSInteger)length
+ textEncodingName:(id)text_encoding_name { // NSString*
+    let url: id = msg![env; url copy];
+    let mime_type: id = msg![env; mime_type copy];
+    let text_encoding_name: id = msg![env; text_encoding_name copy];
+    let host_object = env.objc.borrow_mut::<NSURLResponseHostObject>(this);
+    host_object.url = url;
+    host_object.mime_type = mime_type;
+    host_object.expected_content_length = length.into();
+    host_object.text_encoding_name = text_encoding_name;
+    this
+}
+
+- (())dealloc {
+    let &NSURLResponseHostObject {
+        url,
+        mime_type,
+        text_encoding_name,
+        header_fields,
+        ..
+    } = env.objc.borrow(this);
+    release(env, url);
+    release(env, mime_type);
+    release(env, text_encoding_name);
+    release(env, header_fields);
+    env.objc.dealloc_object(this, &mut env.mem)
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
+- (id)URL {
+    env.objc.borrow::<NSURLResponseHostObject>(this).url
+}
+
+- (id)MIMEType {
+    env.objc.borrow::<NSURLResponseHostObject>(this).mime_type
+}
+
+- (i64)expectedContentLength {
+    env.objc.borrow::<NSURLResponseHostObject>(this).expected_content_length
+}
+
+- (id)textEncodingName {
+    env.objc.borrow::<NSURLResponseHostObject>(this).text_encoding_name
+}
+
+@end
+
+@implementation NSHTTPURLResponse: NSURLResponse
+
+- (id)initWithURL:(id)url // NSURL*
+       statusCode:(NSInteger)status_code
+      HTTPVersion:(id)_http_version // NSString*
+     headerFields:(id)header_fields { // NSDictionary*
+    let url: id = msg![env; url copy];
+    let header_fields: id = msg![env; header_fields copy];
+
+    // The MIME type and length come from the headers.
+    // TODO: text encoding name (charset parameter of Content-Type)
+    let mut mime_type = nil;
+    let mut expected_content_length = NSURLResponseUnknownLength;
+    if header_fields != nil {
+        let content_type = find_header(env, header_fields, "Content-Type");
+        if content_type != nil {
+            let content_type = to_rust_string(env, content_type);
+            let essence = mime_type_essence(&content_type).to_string();
+            mime_type = from_rust_string(env, essence);
+        }
+        let content_length = find_header(env, header_fields, "Content-Length");
+        if content_length != nil {
+            let content_length = to_rust_string(env, content_length);
+            if let Some(length) = parse_content_length(&content_length) {
+                expected_content_length = length;
+            }
+        }
+    }
+
+    let host_object = env.objc.borrow_mut::<NSURLResponseHostObject>(this);
+    host_object.url = url;
+    host_object.mime_type = mime_type;
+    host_object.expected_content_length = expected_content_length;
+    host_object.status_code = status_code;
+    host_object.header_fields = header_fields;
+    this
+}
+
+- (NSInteger)statusCode {
+    env.objc.borrow::<NSURLResponseHostObject>(this).status_code
+}
+
+- (id)allHeaderFields {
+    env.objc.borrow::<NSURLResponseHostObject>(this).header_fields
+}
+
+@end
+
+};
+
+/// Look up the value of a header field, ignoring case like HTTP does.
+fn find_header(env: &mut Environment, header_fields: id, field: &str) -> id {
+    let keys: Vec<id> = env
+        .objc
+        .borrow::<DictionaryHostObject>(header_fields)
+        .iter_keys()
+        .collect();
+    for key in keys {
+        if to_rust_string(env, key).eq_ignore_ascii_case(field) {
+            return msg![env; header_fields objectForKey:key];
+        }
+    }
+    nil
+}
+
+/// Get the MIME type from a `Content-Type` header value, without parameters
+/// like `charset`.
+fn mime_type_essence(content_type: &str) -> &str {
+    content_type.split(';').next().unwrap().trim()
+}
+
+/// Parse a `Content-Length` header value. Returns [None] if it isn't a valid
+/// length.
+fn parse_content_length(content_length: &str) -> Option<i64> {
+    let length: u64 = content_length.trim().parse().ok()?;
+    length.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mime_type_essence() {
+        assert_eq!(mime_type_essence("text/html"), "text/html");
+        assert_eq!(mime_type_essence("text/html; charset=utf-8"), "text/html");
+        assert_eq!(mime_type_essence(" image/png ;q=1"), "image/png");
+        assert_eq!(mime_type_essence(""), "");
+    }
+
+    #[test]
+    fn test_parse_content_length() {
+        assert_eq!(parse_content_length("1234"), Some(1234));
+        assert_eq!(parse_content_length(" 0 "), Some(0));
+        assert_eq!(parse_content_length("-1"), None);
+        assert_eq!(parse_content_length("12 bytes"), None);
+        assert_eq!(parse_content_length("18446744073709551615"), None);
+    }
+}
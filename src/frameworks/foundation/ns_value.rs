@@ -5,29 +5,271 @@
  */
 //! The `NSValue` class cluster, including `NSNumber`.
 
-use super::{NSUInteger, NSInteger};
+use super::{ns_string, NSInteger, NSUInteger};
+use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::libc::stdio::printf;
+use crate::mem::{guest_size_of, ConstPtr, MutVoidPtr, SafeRead};
 use crate::objc::{
     autorelease, id, msg, msg_class, objc_classes, retain, Class, ClassExports, HostObject,
     NSZonePtr,
 };
+use crate::Environment;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct State {
+    /// Guest C strings returned by `objCType`, keyed by their contents.
+    objc_type_strings: HashMap<&'static str, ConstPtr<u8>>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.framework_state.foundation.ns_value
+    }
+}
+
+/// Objective-C type encodings (as produced by `@encode()`) for the types that
+/// can be boxed in an `NSValue`.
+const CG_POINT_ENCODING: &str = "{CGPoint=ff}";
+const CG_SIZE_ENCODING: &str = "{CGSize=ff}";
+const CG_RECT_ENCODING: &str = "{CGRect={CGPoint=ff}{CGSize=ff}}";
+const POINTER_ENCODING: &str = "^v";
+const OBJECT_ENCODING: &str = "@";
+
+/// Host object for a plain `NSValue`: a copy of the boxed value's bytes, plus
+/// the Objective-C type encoding describing them.
+struct NSValueHostObject {
+    bytes: Vec<u8>,
+    objc_type: &'static str,
+}
+impl HostObject for NSValueHostObject {}
+impl NSValueHostObject {
+    fn new<T: SafeRead>(value: T, objc_type: &'static str) -> NSValueHostObject {
+        let mut bytes = vec![0u8; guest_size_of::<T>() as usize];
+        // This is safe because the Vec is exactly the size of T, and the write
+        // is unaligned.
+        unsafe { bytes.as_mut_ptr().cast::<T>().write_unaligned(value) };
+        NSValueHostObject { bytes, objc_type }
+    }
+
+    fn get<T: SafeRead>(&self, objc_type: &'static str) -> T {
+        // TODO: Apple's implementation is more lenient about this.
+        assert_eq!(
+            self.objc_type, objc_type,
+            "NSValue type mismatch: {} requested, {} stored",
+            objc_type, self.objc_type
+        );
+        assert!(self.bytes.len() == guest_size_of::<T>() as usize);
+        // This is safe because of the size check above, because the read is
+        // unaligned, and because SafeRead types have no invalid bit patterns.
+        unsafe { self.bytes.as_ptr().cast::<T>().read_unaligned() }
+    }
+}
 
 enum NSNumberHostObject {
     Bool(bool),
+    Char(i8),
+    UnsignedChar(u8),
+    Short(i16),
+    UnsignedShort(u16),
     Int(i32),
+    /// `long` is 32-bit on iPhone OS, but has its own type encoding.
+    Long(i32),
+    UnsignedLong(u32),
+    LongLong(i64),
+    UnsignedLongLong(u64),
+    Double(f64),
 }
 impl HostObject for NSNumberHostObject {}
+impl NSNumberHostObject {
+    /// Get the Objective-C type encoding for the stored type.
+    fn objc_type(&self) -> &'static str {
+        match self {
+            // BOOL is a typedef for signed char.
+            NSNumberHostObject::Bool(_) => "c",
+            NSNumberHostObject::Char(_) => "c",
+            NSNumberHostObject::UnsignedChar(_) => "C",
+            NSNumberHostObject::Short(_) => "s",
+            NSNumberHostObject::UnsignedShort(_) => "S",
+            NSNumberHostObject::Int(_) => "i",
+            NSNumberHostObject::Long(_) => "l",
+            NSNumberHostObject::UnsignedLong(_) => "L",
+            NSNumberHostObject::LongLong(_) => "q",
+            NSNumberHostObject::UnsignedLongLong(_) => "Q",
+            NSNumberHostObject::Double(_) => "d",
+        }
+    }
+
+    /// Get the value if it is an integer (or boolean). This is wide enough to
+    /// hold any of the integer types without loss, so the value accessors can
+    /// truncate it the way a C cast would.
+    fn integer_value(&self) -> Option<i128> {
+        Some(match *self {
+            NSNumberHostObject::Bool(value) => value.into(),
+            NSNumberHostObject::Char(value) => value.into(),
+            NSNumberHostObject::UnsignedChar(value) => value.into(),
+            NSNumberHostObject::Short(value) => value.into(),
+            NSNumberHostObject::UnsignedShort(value) => value.into(),
+            NSNumberHostObject::Int(value) => value.into(),
+            NSNumberHostObject::Long(value) => value.into(),
+            NSNumberHostObject::UnsignedLong(value) => value.into(),
+            NSNumberHostObject::LongLong(value) => value.into(),
+            NSNumberHostObject::UnsignedLongLong(value) => value.into(),
+            NSNumberHostObject::Double(_) => return None,
+        })
+    }
+
+    /// Get the value as a double, like `doubleValue`.
+    fn double_value(&self) -> f64 {
+        match *self {
+            NSNumberHostObject::Double(value) => value,
+            _ => self.integer_value().unwrap() as f64,
+        }
+    }
+
+    /// Get the value in a form suitable for hashing. Numbers that are equal
+    /// must also have equal hashes, regardless of which type they were created
+    /// with (e.g. `YES` and `1`), so everything is converted to a double.
+    /// Large 64-bit integers can't be represented exactly, but that just means
+    /// some unequal numbers share a hash.
+    fn canonical_value(&self) -> f64 {
+        let value = self.double_value();
+        // Make sure -0.0 and 0.0 hash the same.
+        if value == 0.0 {
+            0.0
+        } else {
+            value
+        }
+    }
+
+    /// Compare two numbers' values, ignoring which types they were created
+    /// with. Integers are compared exactly.
+    fn value_equals(&self, other: &Self) -> bool {
+        match (self.integer_value(), other.integer_value()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.double_value() == other.double_value(),
+        }
+    }
+
+    /// Format the number the way `stringValue` does.
+    fn to_rust_string(&self) -> String {
+        match *self {
+            // Apple's implementation uses `%0.16g`.
+            NSNumberHostObject::Double(value) => printf::format_g(value, 16),
+            // Apple's implementation formats booleans as integers.
+            _ => self.integer_value().unwrap().to_string(),
+        }
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
-// NSValue is an abstract class. None of the things it should provide are
-// implemented here yet (TODO).
+// NSValue is the superclass of NSNumber, but it can also be used directly to
+// box arbitrary values.
 @implementation NSValue: NSObject
 
++ (id)allocWithZone:(NSZonePtr)_zone {
+    let host_object = Box::new(NSValueHostObject {
+        bytes: Vec::new(),
+        objc_type: "",
+    });
+    env.objc.alloc_object(this, host_object, &mut env.mem)
+}
+
+// These are really part of UIKit (NSValue(UIGeometryExtensions)), but it's
+// simpler to keep them with the rest of NSValue.
++ (id)valueWithCGPoint:(CGPoint)point {
+    let host_object = Box::new(NSValueHostObject::new(point, CG_POINT_ENCODING));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithCGSize:(CGSize)size {
+    let host_object = Box::new(NSValueHostObject::new(size, CG_SIZE_ENCODING));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithCGRect:(CGRect)rect {
+    let host_object = Box::new(NSValueHostObject::new(rect, CG_RECT_ENCODING));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
++ (id)valueWithPointer:(MutVoidPtr)pointer {
+    let host_object = Box::new(NSValueHostObject::new(pointer, POINTER_ENCODING));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
++ (id)valueWithNonretainedObject:(id)object {
+    // As the name suggests, the object is not retained.
+    let host_object = Box::new(NSValueHostObject::new(object, OBJECT_ENCODING));
+    let new = env.objc.alloc_object(this, host_object, &mut env.mem);
+    autorelease(env, new)
+}
+
 // NSCopying implementation
 - (id)copyWithZone:(NSZonePtr)_zone {
-    retain(env, this)
+    let host_object = env.objc.borrow::<NSValueHostObject>(this);
+    let host_object = Box::new(NSValueHostObject {
+        bytes: host_object.bytes.clone(),
+        objc_type: host_object.objc_type,
+    });
+    let class: Class = msg![env; this class];
+    env.objc.alloc_object(class, host_object, &mut env.mem)
+}
+
+- (CGPoint)CGPointValue {
+    env.objc.borrow::<NSValueHostObject>(this).get(CG_POINT_ENCODING)
+}
+- (CGSize)CGSizeValue {
+    env.objc.borrow::<NSValueHostObject>(this).get(CG_SIZE_ENCODING)
+}
+- (CGRect)CGRectValue {
+    env.objc.borrow::<NSValueHostObject>(this).get(CG_RECT_ENCODING)
+}
+
+- (MutVoidPtr)pointerValue {
+    let host_object = env.objc.borrow::<NSValueHostObject>(this);
+    if host_object.objc_type == OBJECT_ENCODING {
+        host_object.get::<id>(OBJECT_ENCODING).cast()
+    } else {
+        host_object.get(POINTER_ENCODING)
+    }
+}
+- (id)nonretainedObjectValue {
+    env.objc.borrow::<NSValueHostObject>(this).get(OBJECT_ENCODING)
+}
+
+- (NSUInteger)hash {
+    super::hash_helper(&env.objc.borrow::<NSValueHostObject>(this).bytes)
+}
+- (bool)isEqualTo:(id)other {
+    if this == other {
+        return true;
+    }
+    let value_class: Class = msg_class![env; NSValue class];
+    let number_class: Class = msg_class![env; NSNumber class];
+    if !msg![env; other isKindOfClass:value_class] ||
+        msg![env; other isKindOfClass:number_class] {
+        return false;
+    }
+    let a = env.objc.borrow::<NSValueHostObject>(this);
+    let b = env.objc.borrow::<NSValueHostObject>(other);
+    a.objc_type == b.objc_type && a.bytes == b.bytes
+}
+- (bool)isEqualToValue:(id)other {
+    msg![env; this isEqualTo:other]
+}
+
+- (ConstPtr<u8>)objCType {
+    let objc_type = env.objc.borrow::<NSValueHostObject>(this).objc_type;
+    objc_type_cstr(env, objc_type)
+}
+
+- (())getValue:(MutVoidPtr)buffer {
+    let bytes = &env.objc.borrow::<NSValueHostObject>(this).bytes;
+    let len = bytes.len() as u32;
+    env.mem.bytes_at_mut(buffer.cast(), len).copy_from_slice(bytes);
 }
 
 @end
@@ -62,7 +304,57 @@ pub const CLASSES: ClassExports = objc_classes! {
     autorelease(env, new)
 }
 
-// TODO: types other than booleans
++ (id)numberWithDouble:(f64)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithDouble:value];
+    autorelease(env, new)
+}
+
++ (id)numberWithChar:(i8)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithChar:value];
+    autorelease(env, new)
+}
++ (id)numberWithUnsignedChar:(u8)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithUnsignedChar:value];
+    autorelease(env, new)
+}
+
++ (id)numberWithShort:(i16)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithShort:value];
+    autorelease(env, new)
+}
++ (id)numberWithUnsignedShort:(u16)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithUnsignedShort:value];
+    autorelease(env, new)
+}
+
++ (id)numberWithLong:(i32)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithLong:value];
+    autorelease(env, new)
+}
++ (id)numberWithUnsignedLong:(u32)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithUnsignedLong:value];
+    autorelease(env, new)
+}
+
++ (id)numberWithLongLong:(i64)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithLongLong:value];
+    autorelease(env, new)
+}
++ (id)numberWithUnsignedLongLong:(u64)value {
+    let new: id = msg![env; this alloc];
+    let new: id = msg![env; new initWithUnsignedLongLong:value];
+    autorelease(env, new)
+}
+
+// TODO: floats
 
 - (id)initWithBool:(bool)value {
     *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::Bool(
@@ -78,11 +370,57 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)initWithChar:(i8)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::Char(value);
+    this
+}
+- (id)initWithUnsignedChar:(u8)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::UnsignedChar(value);
+    this
+}
+
+- (id)initWithShort:(i16)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::Short(value);
+    this
+}
+- (id)initWithUnsignedShort:(u16)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::UnsignedShort(value);
+    this
+}
+
+- (id)initWithLong:(i32)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::Long(value);
+    this
+}
+- (id)initWithUnsignedLong:(u32)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::UnsignedLong(value);
+    this
+}
+
+- (id)initWithLongLong:(i64)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::LongLong(value);
+    this
+}
+- (id)initWithUnsignedLongLong:(u64)value {
+    *env.objc.borrow_mut(this) = NSNumberHostObject::UnsignedLongLong(value);
+    this
+}
+
+- (id)initWithDouble:(f64)value {
+    *env.objc.borrow_mut::<NSNumberHostObject>(this) = NSNumberHostObject::Double(
+        value,
+    );
+    this
+}
+
+// NSCopying implementation
+- (id)copyWithZone:(NSZonePtr)_zone {
+    retain(env, this)
+}
+
 - (NSUInteger)hash {
-    match env.objc.borrow(this) {
-         &NSNumberHostObject::Bool(value) => super::hash_helper(&value),
-         &NSNumberHostObject::Int(value) => super::hash_helper(&value),
-    }
+    let value = env.objc.borrow::<NSNumberHostObject>(this).canonical_value();
+    super::hash_helper(&value.to_bits())
 }
 - (bool)isEqualTo:(id)other {
     if this == other {
@@ -92,21 +430,56 @@ pub const CLASSES: ClassExports = objc_classes! {
     if !msg![env; other isKindOfClass:class] {
         return false;
     }
-     match env.objc.borrow(this) {
-         &NSNumberHostObject::Bool(a) => {
-             let b = if let &NSNumberHostObject::Bool(b) = env.objc.borrow(other) { b } else { unreachable!() };
-             a == b
-         },
-         &NSNumberHostObject::Int(a) => {
-             let b = if let &NSNumberHostObject::Int(b) = env.objc.borrow(other) { b } else { unreachable!() };
-             a == b
-         },
-    }
+    let a = env.objc.borrow::<NSNumberHostObject>(this);
+    let b = env.objc.borrow::<NSNumberHostObject>(other);
+    a.value_equals(b)
+}
+
+- (id)stringValue {
+    let string = env.objc.borrow::<NSNumberHostObject>(this).to_rust_string();
+    let string = ns_string::from_rust_string(env, string);
+    autorelease(env, string)
+}
+- (id)description {
+    msg![env; this stringValue]
+}
+
+- (ConstPtr<u8>)objCType {
+    let objc_type = env.objc.borrow::<NSNumberHostObject>(this).objc_type();
+    objc_type_cstr(env, objc_type)
 }
 
 - (NSInteger)integerValue {
-    let value = if let &NSNumberHostObject::Int(value) = env.objc.borrow(this) { value } else { todo!() };
-    value
+    integer_value(env, this) as NSInteger
+}
+
+- (i8)charValue {
+    integer_value(env, this) as i8
+}
+- (u8)unsignedCharValue {
+    integer_value(env, this) as u8
+}
+- (i16)shortValue {
+    integer_value(env, this) as i16
+}
+- (u16)unsignedShortValue {
+    integer_value(env, this) as u16
+}
+- (i32)longValue {
+    integer_value(env, this) as i32
+}
+- (u32)unsignedLongValue {
+    integer_value(env, this) as u32
+}
+- (i64)longLongValue {
+    integer_value(env, this) as i64
+}
+- (u64)unsignedLongLongValue {
+    integer_value(env, this) as u64
+}
+
+- (f64)doubleValue {
+    env.objc.borrow::<NSNumberHostObject>(this).double_value()
 }
 
 // TODO: accessors etc
@@ -114,3 +487,148 @@ pub const CLASSES: ClassExports = objc_classes! {
 @end
 
 };
+
+/// Get an `NSNumber`'s value for one of the integer accessors, which truncate
+/// (or, for doubles, round towards zero) like a C cast.
+fn integer_value(env: &mut Environment, number: id) -> i128 {
+    let host_object = env.objc.borrow::<NSNumberHostObject>(number);
+    host_object
+        .integer_value()
+        .unwrap_or_else(|| host_object.double_value() as i128)
+}
+
+/// Get a guest C string for an Objective-C type encoding. These are cached and
+/// never freed, like the constant strings `@encode()` would produce.
+fn objc_type_cstr(env: &mut Environment, objc_type: &'static str) -> ConstPtr<u8> {
+    if let Some(&existing) = State::get(env).objc_type_strings.get(objc_type) {
+        return existing;
+    }
+    let new = env
+        .mem
+        .alloc_and_write_cstr(objc_type.as_bytes())
+        .cast_const();
+    State::get(env).objc_type_strings.insert(objc_type, new);
+    new
+}
+
+/// Shortcut for host code (e.g. `%@` formatting): if `object` is an `NSNumber`,
+/// get the text its `stringValue` would have, without allocating a guest
+/// `NSString`. Returns [None] for any other kind of object.
+pub fn number_to_rust_string(env: &mut Environment, object: id) -> Option<String> {
+    let class: Class = msg_class![env; NSNumber class];
+    if !msg![env; object isKindOfClass:class] {
+        return None;
+    }
+    Some(
+        env.objc
+            .borrow::<NSNumberHostObject>(object)
+            .to_rust_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_cg_rect_round_trip() {
+        let rect = CGRect {
+            origin: CGPoint { x: 1.5, y: -2.0 },
+            size: CGSize {
+                width: 320.0,
+                height: 480.0,
+            },
+        };
+        let value = NSValueHostObject::new(rect, CG_RECT_ENCODING);
+        assert_eq!(value.bytes.len(), 16);
+        assert_eq!(value.get::<CGRect>(CG_RECT_ENCODING), rect);
+    }
+
+    #[test]
+    fn test_pointers_in_array() {
+        let a: MutVoidPtr = Ptr::from_bits(0x1000);
+        let b: MutVoidPtr = Ptr::from_bits(0xdeadbeef);
+        let values = vec![
+            NSValueHostObject::new(a, POINTER_ENCODING),
+            NSValueHostObject::new(b, POINTER_ENCODING),
+        ];
+        assert_eq!(values[0].get::<MutVoidPtr>(POINTER_ENCODING), a);
+        assert_eq!(values[1].get::<MutVoidPtr>(POINTER_ENCODING), b);
+        assert_ne!(values[0].bytes, values[1].bytes);
+    }
+
+    #[test]
+    fn test_number_to_rust_string() {
+        assert_eq!(NSNumberHostObject::Int(-42).to_rust_string(), "-42");
+        assert_eq!(
+            NSNumberHostObject::Int(i32::MIN).to_rust_string(),
+            "-2147483648"
+        );
+        assert_eq!(NSNumberHostObject::Bool(true).to_rust_string(), "1");
+        assert_eq!(NSNumberHostObject::Bool(false).to_rust_string(), "0");
+        // Doubles are formatted like printf's `%.16g`.
+        for (value, expected) in [
+            (1e300, "1e+300"),
+            (0.1 + 0.2, "0.3"),
+            (0.5, "0.5"),
+            (-3.0, "-3"),
+            (1e-5, "1e-05"),
+            (1.0 / 3.0, "0.3333333333333333"),
+        ] {
+            let string = NSNumberHostObject::Double(value).to_rust_string();
+            assert_eq!(string, expected);
+            assert_eq!(string, printf::format_g(value, 16));
+        }
+    }
+
+    #[test]
+    fn test_objc_type() {
+        assert_eq!(NSNumberHostObject::Double(0.5).objc_type(), "d");
+        assert_eq!(NSNumberHostObject::Int(1).objc_type(), "i");
+        assert_eq!(NSNumberHostObject::Bool(true).objc_type(), "c");
+        let point = NSValueHostObject::new(CGPoint { x: 0.0, y: 0.0 }, CG_POINT_ENCODING);
+        assert_eq!(point.objc_type, "{CGPoint=ff}");
+    }
+
+    #[test]
+    fn test_sized_integers() {
+        let number = NSNumberHostObject::LongLong(i64::MIN + 1);
+        assert_eq!(number.objc_type(), "q");
+        assert_eq!(number.integer_value().unwrap() as i64, i64::MIN + 1);
+        assert_eq!(number.to_rust_string(), "-9223372036854775807");
+        let number = NSNumberHostObject::UnsignedLongLong(u64::MAX);
+        assert_eq!(number.integer_value().unwrap() as u64, u64::MAX);
+        // Truncation works like a C cast.
+        assert_eq!(number.integer_value().unwrap() as i8, -1);
+        assert_eq!(NSNumberHostObject::UnsignedChar(200).objc_type(), "C");
+        assert_eq!(NSNumberHostObject::Short(-300).to_rust_string(), "-300");
+        // These are too close together to be distinguished as doubles.
+        let a = NSNumberHostObject::LongLong(1 << 60);
+        let b = NSNumberHostObject::UnsignedLongLong((1 << 60) + 1);
+        assert!(!a.value_equals(&b));
+        assert!(a.value_equals(&NSNumberHostObject::Double((1u64 << 60) as f64)));
+    }
+
+    #[test]
+    fn test_cross_type_equality() {
+        let a = NSNumberHostObject::Bool(true).canonical_value();
+        let b = NSNumberHostObject::Int(1).canonical_value();
+        let c = NSNumberHostObject::Double(1.0).canonical_value();
+        assert!(a == b && b == c);
+        let hash = |value: f64| super::super::hash_helper(&value.to_bits());
+        assert_eq!(hash(a), hash(b));
+        assert_eq!(hash(b), hash(c));
+        assert_eq!(
+            hash(NSNumberHostObject::Double(-0.0).canonical_value()),
+            hash(NSNumberHostObject::Int(0).canonical_value())
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_type_mismatch() {
+        let value = NSValueHostObject::new(CGPoint { x: 1.0, y: 2.0 }, CG_POINT_ENCODING);
+        let _: CGSize = value.get(CG_SIZE_ENCODING);
+    }
+}
@@ -28,6 +28,7 @@ pub mod ui_image_view;
 pub mod ui_nib;
 pub mod ui_responder;
 pub mod ui_screen;
+pub mod ui_slider;
 pub mod ui_text_field;
 pub mod ui_touch;
 pub mod ui_view;
@@ -47,6 +48,7 @@ pub struct State {
     ui_font: ui_font::State,
     ui_graphics: ui_graphics::State,
     ui_screen: ui_screen::State,
+    ui_text_view: ui_text_view::State,
     ui_touch: ui_touch::State,
     ui_view: ui_view::State,
     pub ui_window: ui_window::State,
@@ -72,6 +74,7 @@ pub fn handle_events(env: &mut Environment) -> Option<Instant> {
             Event::TouchDown(..) | Event::TouchMove(..) | Event::TouchUp(..) => {
                 ui_touch::handle_event(env, event)
             }
+            Event::TextInput(..) | Event::TextBackspace => ui_text_view::handle_event(env, event),
             Event::AppWillResignActive => {
                 // Getting this event means touchHLE is becoming inactive, e.g.
                 // due to switching apps. The obvious way to handle this would
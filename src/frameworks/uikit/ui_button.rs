@@ -5,18 +5,274 @@
  */
 //! `UIButton`.
 
-use crate::objc::{id, objc_classes, ClassExports};
+use super::ui_control::{UIControlState, UIControlStateNormal};
+use super::ui_geometry::UIEdgeInsets;
+use super::ui_view::{UIControlSubclass, UIViewHostObject, UIViewSubclass};
+use crate::frameworks::core_graphics::CGRect;
+use crate::frameworks::foundation::NSInteger;
+use crate::objc::{
+    autorelease, id, msg, msg_class, msg_super, nil, objc_classes, release, retain, ClassExports,
+};
+use crate::Environment;
+use std::collections::HashMap;
+
+pub type UIButtonType = NSInteger;
+pub const UIButtonTypeCustom: UIButtonType = 0;
+pub const UIButtonTypeRoundedRect: UIButtonType = 1;
+
+#[derive(Default)]
+pub(super) struct UIButtonData {
+    button_type: UIButtonType,
+    /// `NSString*` for each state. Strong references.
+    titles: HashMap<UIControlState, id>,
+    /// `UIImage*` for each state. Strong references.
+    images: HashMap<UIControlState, id>,
+    /// `UIImage*` for each state. Strong references.
+    background_images: HashMap<UIControlState, id>,
+    /// `UIColor*` for each state. Strong references.
+    title_colors: HashMap<UIControlState, id>,
+    /// `UILabel*`, created on first use. Strong reference.
+    title_label: id,
+    content_edge_insets: UIEdgeInsets,
+    title_edge_insets: UIEdgeInsets,
+    /// TODO: use this once the image is drawn
+    image_edge_insets: UIEdgeInsets,
+}
+
+/// Look up the value for a control state, falling back to the value for
+/// `UIControlStateNormal` if there isn't one for that state, like UIKit does.
+fn value_for_state<T: Copy>(map: &HashMap<UIControlState, T>, state: UIControlState) -> Option<T> {
+    map.get(&state)
+        .or_else(|| map.get(&UIControlStateNormal))
+        .copied()
+}
+
+/// Set or (if `value` is `nil`) remove the value for a control state in one of
+/// the maps in [UIButtonData], retaining the new value and releasing the old
+/// one.
+fn set_value_for_state(
+    env: &mut Environment,
+    button: id,
+    map: fn(&mut UIButtonData) -> &mut HashMap<UIControlState, id>,
+    state: UIControlState,
+    value: id,
+) {
+    retain(env, value);
+    let map = map(button_data(env, button));
+    let value = if value == nil { None } else { Some(value) };
+    if let Some(old) = replace_value_for_state(map, state, value) {
+        release(env, old);
+    }
+}
+
+/// Set or (if `value` is [None]) remove the value for a control state,
+/// returning the old value.
+fn replace_value_for_state<T>(
+    map: &mut HashMap<UIControlState, T>,
+    state: UIControlState,
+    value: Option<T>,
+) -> Option<T> {
+    match value {
+        Some(value) => map.insert(state, value),
+        None => map.remove(&state),
+    }
+}
+
+fn button_data(env: &mut Environment, button: id) -> &mut UIButtonData {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(button);
+    let UIViewSubclass::UIControl {
+        subclass: UIControlSubclass::UIButton(ref mut data),
+        ..
+    } = host_obj.subclass
+    else {
+        panic!();
+    };
+    data
+}
+
+/// Make the title label (if there is one) show the title for the current state.
+fn update_title_label(env: &mut Environment, button: id) {
+    let label = button_data(env, button).title_label;
+    if label == nil {
+        return;
+    }
+    let title: id = msg![env; button currentTitle];
+    () = msg![env; label setText:title];
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
 (env, this, _cmd);
 
 @implementation UIButton: UIControl
-// TODO
-@end
 
-@implementation UISlider: UIControl
-// TODO
++ (id)buttonWithType:(UIButtonType)button_type {
+    // Rounded-rect buttons are really a private subclass.
+    let new: id = if button_type == UIButtonTypeRoundedRect {
+        msg_class![env; UIRoundedRectButton alloc]
+    } else {
+        msg![env; this alloc]
+    };
+    let new: id = msg![env; new init];
+    button_data(env, new).button_type = button_type;
+    autorelease(env, new)
+}
+
+- (id)init {
+    let this: id = msg_super![env; this init];
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UIControl { ref mut subclass, .. } = host_obj.subclass else {
+        panic!();
+    };
+    *subclass = UIControlSubclass::UIButton(UIButtonData {
+        button_type: UIButtonTypeCustom,
+        ..Default::default()
+    });
+    this
+}
+
+- (())dealloc {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UIControl { ref mut subclass, .. } = host_obj.subclass else {
+        panic!();
+    };
+    let UIControlSubclass::UIButton(data) = std::mem::take(subclass) else {
+        panic!();
+    };
+    for map in [data.titles, data.images, data.background_images, data.title_colors] {
+        for (_state, object) in map {
+            release(env, object);
+        }
+    }
+    release(env, data.title_label);
+    msg_super![env; this dealloc]
+}
+
+- (())setTitle:(id)title // NSString*
+      forState:(UIControlState)state {
+    let title: id = msg![env; title copy];
+    set_value_for_state(env, this, |data| &mut data.titles, state, title);
+    release(env, title);
+    update_title_label(env, this);
+}
+
+- (id)titleForState:(UIControlState)state {
+    value_for_state(&button_data(env, this).titles, state).unwrap_or(nil)
+}
+
+- (())setImage:(id)image // UIImage*
+      forState:(UIControlState)state {
+    set_value_for_state(env, this, |data| &mut data.images, state, image);
+}
+
+- (id)imageForState:(UIControlState)state {
+    value_for_state(&button_data(env, this).images, state).unwrap_or(nil)
+}
+
+- (())setBackgroundImage:(id)image // UIImage*
+                forState:(UIControlState)state {
+    set_value_for_state(env, this, |data| &mut data.background_images, state, image);
+}
+
+- (id)backgroundImageForState:(UIControlState)state {
+    value_for_state(&button_data(env, this).background_images, state).unwrap_or(nil)
+}
+
+- (())setTitleColor:(id)color // UIColor*
+           forState:(UIControlState)state {
+    set_value_for_state(env, this, |data| &mut data.title_colors, state, color);
+}
+
+- (id)titleColorForState:(UIControlState)state {
+    value_for_state(&button_data(env, this).title_colors, state).unwrap_or(nil)
+}
+
+// Values for the current state
+- (id)currentTitle {
+    let state: UIControlState = msg![env; this state];
+    msg![env; this titleForState:state]
+}
+- (id)currentImage {
+    let state: UIControlState = msg![env; this state];
+    msg![env; this imageForState:state]
+}
+- (id)currentBackgroundImage {
+    let state: UIControlState = msg![env; this state];
+    msg![env; this backgroundImageForState:state]
+}
+- (id)currentTitleColor {
+    let state: UIControlState = msg![env; this state];
+    msg![env; this titleColorForState:state]
+}
+
+- (id)titleLabel {
+    let existing = button_data(env, this).title_label;
+    if existing != nil {
+        return existing;
+    }
+    let label: id = msg_class![env; UILabel new];
+    () = msg![env; this addSubview:label];
+    button_data(env, this).title_label = label;
+    update_title_label(env, this);
+    label
+}
+
+- (UIEdgeInsets)contentEdgeInsets {
+    button_data(env, this).content_edge_insets
+}
+- (())setContentEdgeInsets:(UIEdgeInsets)insets {
+    button_data(env, this).content_edge_insets = insets;
+}
+- (UIEdgeInsets)titleEdgeInsets {
+    button_data(env, this).title_edge_insets
+}
+- (())setTitleEdgeInsets:(UIEdgeInsets)insets {
+    button_data(env, this).title_edge_insets = insets;
+}
+- (UIEdgeInsets)imageEdgeInsets {
+    button_data(env, this).image_edge_insets
+}
+- (())setImageEdgeInsets:(UIEdgeInsets)insets {
+    button_data(env, this).image_edge_insets = insets;
+}
+
+- (())layoutSubviews {
+    let &mut UIButtonData {
+        title_label,
+        content_edge_insets,
+        title_edge_insets,
+        ..
+    } = button_data(env, this);
+    if title_label == nil {
+        return;
+    }
+    // TODO: alignment, sizing the label to fit, room for the image
+    let bounds: CGRect = msg![env; this bounds];
+    let frame = title_edge_insets.inset_rect(content_edge_insets.inset_rect(bounds));
+    () = msg![env; title_label setFrame:frame];
+}
+
+// The title label shows the title for the current state, so it needs updating
+// whenever the state changes.
+- (())setEnabled:(bool)enabled {
+    () = msg_super![env; this setEnabled:enabled];
+    update_title_label(env, this);
+}
+- (())setSelected:(bool)selected {
+    () = msg_super![env; this setSelected:selected];
+    update_title_label(env, this);
+}
+- (())setHighlighted:(bool)highlighted {
+    () = msg_super![env; this setHighlighted:highlighted];
+    update_title_label(env, this);
+}
+
+- (UIButtonType)buttonType {
+    button_data(env, this).button_type
+}
+
+// TODO: drawing
+
 @end
 
 @implementation UIRoundedRectButton: UIButton
@@ -36,3 +292,52 @@ pub const CLASSES: ClassExports = objc_classes! {
 @end
 
 };
+
+#[cfg(test)]
+mod tests {
+    use super::super::ui_control::{UIControlStateHighlighted, UIControlStateSelected};
+    use super::*;
+
+    #[test]
+    fn test_value_for_state() {
+        let mut map = HashMap::new();
+        assert_eq!(value_for_state(&map, UIControlStateNormal), None::<u32>);
+        map.insert(UIControlStateNormal, 1);
+        map.insert(UIControlStateHighlighted, 2);
+        assert_eq!(value_for_state(&map, UIControlStateNormal), Some(1));
+        assert_eq!(value_for_state(&map, UIControlStateHighlighted), Some(2));
+        assert_eq!(value_for_state(&map, UIControlStateSelected), Some(1));
+    }
+
+    #[test]
+    fn test_value_for_current_state() {
+        // currentTitle etc look up the control's whole state, which can have
+        // several flags set.
+        let selected = UIControlStateSelected;
+        let both = UIControlStateSelected | UIControlStateHighlighted;
+        let mut titles = HashMap::new();
+        titles.insert(UIControlStateNormal, "normal");
+        titles.insert(selected, "selected");
+        assert_eq!(value_for_state(&titles, selected), Some("selected"));
+        // There's no merging of flags, only the fallback to the normal state.
+        assert_eq!(value_for_state(&titles, both), Some("normal"));
+        titles.insert(both, "both");
+        assert_eq!(value_for_state(&titles, both), Some("both"));
+    }
+
+    #[test]
+    fn test_replace_value_for_state() {
+        let (normal, highlighted) = (UIControlStateNormal, UIControlStateHighlighted);
+        let mut colors = HashMap::new();
+        replace_value_for_state(&mut colors, normal, Some('k'));
+        replace_value_for_state(&mut colors, highlighted, Some('w'));
+        assert_eq!(value_for_state(&colors, highlighted), Some('w'));
+        // The old value is handed back so it can be released.
+        let old = replace_value_for_state(&mut colors, highlighted, Some('r'));
+        assert_eq!(old, Some('w'));
+        // Removing a state's value makes it fall back to the normal one.
+        let old = replace_value_for_state(&mut colors, highlighted, None);
+        assert_eq!(old, Some('r'));
+        assert_eq!(value_for_state(&colors, highlighted), Some('k'));
+    }
+}
@@ -5,7 +5,34 @@
  */
 //! `UIControl`.
 
-use crate::objc::{objc_classes, ClassExports};
+use super::ui_view::{UIControlSubclass, UIViewHostObject, UIViewSubclass};
+use crate::frameworks::core_graphics::{CGPoint, CGRect};
+use crate::frameworks::foundation::NSUInteger;
+use crate::objc::{id, msg, msg_send, msg_super, nil, objc_classes, ClassExports, SEL};
+use crate::Environment;
+
+pub type UIControlState = NSUInteger;
+pub const UIControlStateNormal: UIControlState = 0;
+pub const UIControlStateHighlighted: UIControlState = 1 << 0;
+pub const UIControlStateDisabled: UIControlState = 1 << 1;
+pub const UIControlStateSelected: UIControlState = 1 << 2;
+
+/// Set or clear one of the flags in a [UIControlState] bitmask.
+fn set_state_flag(state: &mut UIControlState, flag: UIControlState, on: bool) {
+    if on {
+        *state |= flag;
+    } else {
+        *state &= !flag;
+    }
+}
+
+pub type UIControlEvents = NSUInteger;
+pub const UIControlEventTouchDown: UIControlEvents = 1 << 0;
+pub const UIControlEventTouchDragInside: UIControlEvents = 1 << 2;
+pub const UIControlEventTouchDragOutside: UIControlEvents = 1 << 3;
+pub const UIControlEventTouchUpInside: UIControlEvents = 1 << 6;
+pub const UIControlEventTouchUpOutside: UIControlEvents = 1 << 7;
+pub const UIControlEventValueChanged: UIControlEvents = 1 << 12;
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -13,7 +40,344 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 // abstract class
 @implementation UIControl: UIView
-// TODO
+
+- (id)init {
+    let this: id = msg_super![env; this init];
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    host_obj.subclass = UIViewSubclass::UIControl {
+        targets: Vec::new(),
+        state: UIControlStateNormal,
+        tracking: false,
+        subclass: UIControlSubclass::UIControl,
+    };
+    this
+}
+
+- (())dealloc {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let subclass = std::mem::take(&mut host_obj.subclass);
+    let UIViewSubclass::UIControl { subclass, .. } = subclass else {
+        panic!();
+    };
+    // This assert forces subclasses to clean up their data in their dealloc
+    // implementation, like for UIView.
+    assert!(matches!(subclass, UIControlSubclass::UIControl));
+    msg_super![env; this dealloc]
+}
+
+- (())addTarget:(id)target
+         action:(SEL)action
+forControlEvents:(UIControlEvents)events {
+    add_target(targets_mut(env, this), target, action, events);
+}
+
+- (())removeTarget:(id)target
+            action:(SEL)action // may be NULL
+  forControlEvents:(UIControlEvents)events {
+    // A nil target or NULL action matches everything.
+    let target: Option<id> = if target == nil { None } else { Some(target) };
+    let action: Option<SEL> = if action.is_null() { None } else { Some(action) };
+    remove_target(targets_mut(env, this), target, action, events);
+}
+
+- (bool)isEnabled {
+    *state_mut(env, this) & UIControlStateDisabled == 0
+}
+- (())setEnabled:(bool)enabled {
+    set_state_flag(state_mut(env, this), UIControlStateDisabled, !enabled);
+}
+
+- (bool)isSelected {
+    *state_mut(env, this) & UIControlStateSelected != 0
+}
+- (())setSelected:(bool)selected {
+    set_state_flag(state_mut(env, this), UIControlStateSelected, selected);
+}
+
+- (bool)isHighlighted {
+    *state_mut(env, this) & UIControlStateHighlighted != 0
+}
+- (())setHighlighted:(bool)highlighted {
+    set_state_flag(state_mut(env, this), UIControlStateHighlighted, highlighted);
+}
+
+- (UIControlState)state {
+    *state_mut(env, this)
+}
+
+- (())sendActionsForControlEvents:(UIControlEvents)events {
+    send_actions(env, this, events, nil);
+}
+
+- (())sendAction:(SEL)action
+              to:(id)target
+        forEvent:(id)event { // UIEvent*
+    // A nil target means the first object in the responder chain that
+    // implements the action.
+    // TODO: start from the first responder rather than the control itself
+    let mut target = target;
+    if target == nil {
+        let mut responder = this;
+        while responder != nil {
+            let responds: bool = msg![env; responder respondsToSelector:action];
+            if responds {
+                target = responder;
+                break;
+            }
+            responder = msg![env; responder nextResponder];
+        }
+        if target == nil {
+            log_dbg!("[{:?} sendAction:{} to:nil forEvent:{:?}]: no responder handles the action", this, action.as_str(&env.mem), event);
+            return;
+        }
+    }
+    // The action can take zero, one (sender) or two (sender, event)
+    // arguments. Passing extra arguments is harmless.
+    let _: () = msg_send(env, (target, action, this, event));
+}
+
+- (bool)isTracking {
+    *tracking_mut(env, this)
+}
+
+// Subclasses override these to customize tracking. Returning NO from begin or
+// continue stops tracking for the rest of the touch.
+- (bool)beginTrackingWithTouch:(id)_touch // UITouch*
+                     withEvent:(id)_event { // UIEvent*
+    true
+}
+- (bool)continueTrackingWithTouch:(id)_touch // UITouch*
+                        withEvent:(id)_event { // UIEvent*
+    true
+}
+- (())endTrackingWithTouch:(id)_touch // UITouch*
+                 withEvent:(id)_event { // UIEvent*
+}
+
+- (())touchesBegan:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    () = msg![env; this setHighlighted:true];
+    let tracking: bool = msg![env; this beginTrackingWithTouch:touch withEvent:event];
+    *tracking_mut(env, this) = tracking;
+    // TODO: UIControlEventTouchDownRepeat
+    send_actions(env, this, UIControlEventTouchDown, event);
+}
+
+- (())touchesMoved:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    let inside = touch_is_inside(env, this, touch);
+    () = msg![env; this setHighlighted:inside];
+    if *tracking_mut(env, this) {
+        let tracking: bool = msg![env; this continueTrackingWithTouch:touch withEvent:event];
+        *tracking_mut(env, this) = tracking;
+    }
+    send_actions(env, this, touch_drag_events(inside), event);
+}
+
+- (())touchesEnded:(id)touches // NSSet* of UITouch*
+         withEvent:(id)event { // UIEvent*
+    let touch: id = msg![env; touches anyObject];
+    () = msg![env; this setHighlighted:false];
+    if std::mem::take(tracking_mut(env, this)) {
+        () = msg![env; this endTrackingWithTouch:touch withEvent:event];
+    }
+    let inside = touch_is_inside(env, this, touch);
+    send_actions(env, this, touch_up_events(inside), event);
+}
+
 @end
 
 };
+
+fn targets_mut(env: &mut Environment, control: id) -> &mut Vec<(id, SEL, UIControlEvents)> {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(control);
+    let UIViewSubclass::UIControl {
+        ref mut targets, ..
+    } = host_obj.subclass
+    else {
+        panic!();
+    };
+    targets
+}
+
+fn state_mut(env: &mut Environment, control: id) -> &mut UIControlState {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(control);
+    let UIViewSubclass::UIControl { ref mut state, .. } = host_obj.subclass else {
+        panic!();
+    };
+    state
+}
+
+fn tracking_mut(env: &mut Environment, control: id) -> &mut bool {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(control);
+    let UIViewSubclass::UIControl {
+        ref mut tracking, ..
+    } = host_obj.subclass
+    else {
+        panic!();
+    };
+    tracking
+}
+
+/// Register `action` on `target` for `events`, adding to the events for that
+/// pair if it was already registered. Generic so it can be tested without
+/// real objects and selectors.
+fn add_target<T: PartialEq, A: PartialEq>(
+    targets: &mut Vec<(T, A, UIControlEvents)>,
+    target: T,
+    action: A,
+    events: UIControlEvents,
+) {
+    if let Some(existing) = targets
+        .iter_mut()
+        .find(|(t, a, _)| *t == target && *a == action)
+    {
+        existing.2 |= events;
+    } else {
+        targets.push((target, action, events));
+    }
+}
+
+/// Unregister `events` for matching registrations, and forget registrations
+/// left without events. [None] matches any target or action.
+fn remove_target<T: PartialEq, A: PartialEq>(
+    targets: &mut Vec<(T, A, UIControlEvents)>,
+    target: Option<T>,
+    action: Option<A>,
+    events: UIControlEvents,
+) {
+    for (t, a, mask) in targets.iter_mut() {
+        if target.as_ref().map_or(true, |target| t == target)
+            && action.as_ref().map_or(true, |action| a == action)
+        {
+            *mask &= !events;
+        }
+    }
+    targets.retain(|&(_, _, mask)| mask != 0);
+}
+
+/// Get the target-action pairs registered for any of `events`, in
+/// registration order.
+fn actions_for_events<T: Copy, A: Copy>(
+    targets: &[(T, A, UIControlEvents)],
+    events: UIControlEvents,
+) -> Vec<(T, A)> {
+    targets
+        .iter()
+        .filter(|&&(_, _, mask)| mask & events != 0)
+        .map(|&(target, action, _)| (target, action))
+        .collect()
+}
+
+fn touch_up_events(inside: bool) -> UIControlEvents {
+    if inside {
+        UIControlEventTouchUpInside
+    } else {
+        UIControlEventTouchUpOutside
+    }
+}
+
+fn touch_drag_events(inside: bool) -> UIControlEvents {
+    if inside {
+        UIControlEventTouchDragInside
+    } else {
+        UIControlEventTouchDragOutside
+    }
+}
+
+/// Send the actions registered for any of `events`, unless the control is
+/// disabled.
+pub(super) fn send_actions(env: &mut Environment, control: id, events: UIControlEvents, event: id) {
+    let enabled: bool = msg![env; control isEnabled];
+    if !enabled {
+        return;
+    }
+    // Copy the list in case an action adds or removes targets.
+    let actions = actions_for_events(targets_mut(env, control), events);
+    for (target, action) in actions {
+        let _: () = msg![env; control sendAction:action to:target forEvent:event];
+    }
+}
+
+fn touch_is_inside(env: &mut Environment, control: id, touch: id) -> bool {
+    let location: CGPoint = msg![env; touch locationInView:control];
+    let bounds: CGRect = msg![env; control bounds];
+    rect_contains_point(bounds, location)
+}
+
+fn rect_contains_point(rect: CGRect, point: CGPoint) -> bool {
+    point.x >= rect.origin.x
+        && point.y >= rect.origin.y
+        && point.x < rect.origin.x + rect.size.width
+        && point.y < rect.origin.y + rect.size.height
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frameworks::core_graphics::CGSize;
+
+    #[test]
+    fn test_set_state_flag() {
+        let mut state = UIControlStateNormal;
+        set_state_flag(&mut state, UIControlStateDisabled, true);
+        set_state_flag(&mut state, UIControlStateSelected, true);
+        set_state_flag(&mut state, UIControlStateHighlighted, true);
+        assert_eq!(
+            state,
+            UIControlStateDisabled | UIControlStateSelected | UIControlStateHighlighted
+        );
+        set_state_flag(&mut state, UIControlStateDisabled, false);
+        set_state_flag(&mut state, UIControlStateHighlighted, false);
+        assert_eq!(state, UIControlStateSelected);
+    }
+
+    #[test]
+    fn test_add_remove_target() {
+        let (down, up) = (UIControlEventTouchDown, UIControlEventTouchUpInside);
+        let mut targets = Vec::new();
+        add_target(&mut targets, 1, 'a', down);
+        add_target(&mut targets, 1, 'a', up);
+        add_target(&mut targets, 2, 'b', up);
+        assert_eq!(targets, [(1, 'a', down | up), (2, 'b', up)]);
+
+        remove_target(&mut targets, Some(1), Some('a'), down);
+        assert_eq!(targets, [(1, 'a', up), (2, 'b', up)]);
+        // Wildcards match every target and action.
+        remove_target(&mut targets, None, None, up);
+        assert!(targets.is_empty());
+    }
+
+    #[test]
+    fn test_actions_for_events() {
+        let (up_inside, up_outside) = (UIControlEventTouchUpInside, UIControlEventTouchUpOutside);
+        let targets = [
+            (1, 'a', up_inside),
+            (2, 'b', UIControlEventTouchDown | UIControlEventValueChanged),
+            (3, 'c', up_inside | up_outside),
+        ];
+        let actions = |events| actions_for_events(&targets, events);
+        assert_eq!(actions(touch_up_events(true)), [(1, 'a'), (3, 'c')]);
+        assert_eq!(actions(touch_up_events(false)), [(3, 'c')]);
+        assert!(actions(touch_drag_events(true)).is_empty());
+        assert_eq!(actions(UIControlEventValueChanged), [(2, 'b')]);
+    }
+
+    #[test]
+    fn test_rect_contains_point() {
+        let rect = CGRect {
+            origin: CGPoint { x: 10.0, y: 20.0 },
+            size: CGSize {
+                width: 100.0,
+                height: 50.0,
+            },
+        };
+        let point = |x, y| CGPoint { x, y };
+        assert!(rect_contains_point(rect, point(10.0, 20.0)));
+        assert!(rect_contains_point(rect, point(109.5, 69.5)));
+        assert!(!rect_contains_point(rect, point(110.0, 30.0)));
+        assert!(!rect_contains_point(rect, point(50.0, 19.5)));
+    }
+}
@@ -2,12 +2,69 @@
 //!
 //! See also [crate::frameworks::core_graphics::cg_geometry].
 
+use crate::abi::{impl_GuestRet_for_large_struct, GuestArg};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::frameworks::foundation::ns_string;
+use crate::mem::SafeRead;
 use crate::objc::{autorelease, id};
 use crate::Environment;
 
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+#[repr(C, packed)]
+pub struct UIEdgeInsets {
+    pub top: CGFloat,
+    pub left: CGFloat,
+    pub bottom: CGFloat,
+    pub right: CGFloat,
+}
+unsafe impl SafeRead for UIEdgeInsets {}
+impl_GuestRet_for_large_struct!(UIEdgeInsets);
+impl GuestArg for UIEdgeInsets {
+    const REG_COUNT: usize = 4;
+
+    fn from_regs(regs: &[u32]) -> Self {
+        UIEdgeInsets {
+            top: GuestArg::from_regs(&regs[0..1]),
+            left: GuestArg::from_regs(&regs[1..2]),
+            bottom: GuestArg::from_regs(&regs[2..3]),
+            right: GuestArg::from_regs(&regs[3..4]),
+        }
+    }
+    fn to_regs(self, regs: &mut [u32]) {
+        self.top.to_regs(&mut regs[0..1]);
+        self.left.to_regs(&mut regs[1..2]);
+        self.bottom.to_regs(&mut regs[2..3]);
+        self.right.to_regs(&mut regs[3..4]);
+    }
+}
+impl UIEdgeInsets {
+    /// Equivalent of `UIEdgeInsetsInsetRect` (which is an inline function, so
+    /// it doesn't need exporting).
+    pub fn inset_rect(self, rect: CGRect) -> CGRect {
+        let UIEdgeInsets {
+            top,
+            left,
+            bottom,
+            right,
+        } = self;
+        let CGRect {
+            origin: CGPoint { x, y },
+            size: CGSize { width, height },
+        } = rect;
+        CGRect {
+            origin: CGPoint {
+                x: x + left,
+                y: y + top,
+            },
+            size: CGSize {
+                width: width - left - right,
+                height: height - top - bottom,
+            },
+        }
+    }
+}
+
 // Apple's documentation says all of these return zeroes if the input is not
 // well-formed.
 pub fn CGPointFromString(env: &mut Environment, string: id) -> CGPoint {
@@ -50,3 +107,38 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(NSStringFromCGSize(_)),
     export_c_func!(NSStringFromCGRect(_)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edge_insets() {
+        let insets = UIEdgeInsets {
+            top: 1.0,
+            left: 2.0,
+            bottom: 3.0,
+            right: 4.0,
+        };
+        let mut regs = [0; 4];
+        insets.to_regs(&mut regs);
+        assert_eq!(<UIEdgeInsets as GuestArg>::from_regs(&regs), insets);
+
+        let rect = CGRect {
+            origin: CGPoint { x: 10.0, y: 20.0 },
+            size: CGSize {
+                width: 100.0,
+                height: 50.0,
+            },
+        };
+        let expected = CGRect {
+            origin: CGPoint { x: 12.0, y: 21.0 },
+            size: CGSize {
+                width: 94.0,
+                height: 46.0,
+            },
+        };
+        assert_eq!(insets.inset_rect(rect), expected);
+        assert_eq!(UIEdgeInsets::default().inset_rect(rect), rect);
+    }
+}
@@ -5,15 +5,18 @@
  */
 //! `UIImage`.
 
+use crate::dyld::{export_c_func, FunctionExports};
 use crate::frameworks::core_graphics::cg_image::{self, CGImageRef, CGImageRelease};
-use crate::frameworks::core_graphics::CGSize;
-use crate::frameworks::foundation::{ns_data, ns_string, NSInteger};
+use crate::frameworks::core_graphics::{CGFloat, CGSize};
+use crate::frameworks::foundation::{ns_data, ns_string, NSInteger, NSUInteger};
 use crate::fs::GuestPath;
 use crate::image::Image;
+use crate::mem::MutVoidPtr;
 use crate::objc::{
     autorelease, id, msg, msg_class, nil, objc_classes, release, ClassExports, HostObject,
     NSZonePtr,
 };
+use crate::Environment;
 
 struct UIImageHostObject {
     cg_image: CGImageRef,
@@ -90,15 +93,37 @@ pub const CLASSES: ClassExports = objc_classes! {
     0 // UIImageOrientationUp
 }
 
+- (CGFloat)scale {
+    // TODO: @2x images
+    1.0
+}
+
 - (CGSize)size {
+    // The size is in points, not pixels.
+    let scale: CGFloat = msg![env; this scale];
     let image = env.objc.borrow::<UIImageHostObject>(this).cg_image;
     let (width, height) = cg_image::borrow_image(&env.objc, image).dimensions();
     CGSize {
-        width: width as _,
-        height: height as _,
+        width: width as CGFloat / scale,
+        height: height as CGFloat / scale,
     }
 }
 
 @end
 
 };
+
+/// Returns an autoreleased `NSData*` containing the image encoded as PNG.
+fn UIImagePNGRepresentation(env: &mut Environment, image: id) -> id {
+    if image == nil {
+        return nil;
+    }
+    let cg_image = env.objc.borrow::<UIImageHostObject>(image).cg_image;
+    let png = cg_image::borrow_image(&env.objc, cg_image).encode_png();
+
+    let length: NSUInteger = png.len().try_into().unwrap();
+    let bytes: MutVoidPtr = env.mem.alloc_and_write_bytes(&png).cast();
+    msg_class![env; NSData dataWithBytesNoCopy:bytes length:length]
+}
+
+pub const FUNCTIONS: FunctionExports = &[export_c_func!(UIImagePNGRepresentation(_))];
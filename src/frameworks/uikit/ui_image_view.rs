@@ -8,7 +8,86 @@
 use super::ui_view::{UIViewHostObject, UIViewSubclass};
 use crate::frameworks::core_graphics::cg_image::CGImageRef;
 use crate::frameworks::core_graphics::{CGPoint, CGRect, CGSize};
-use crate::objc::{id, msg, msg_super, nil, objc_classes, release, retain, ClassExports};
+use crate::frameworks::foundation::{NSInteger, NSTimeInterval, NSUInteger};
+use crate::objc::{
+    id, msg, msg_class, msg_super, nil, objc_classes, release, retain, ClassExports,
+};
+use crate::Environment;
+
+#[derive(Default)]
+pub(super) struct UIImageViewData {
+    /// `UIImage*`. Strong reference.
+    image: id,
+    /// `UIImage*`. Strong reference.
+    highlighted_image: id,
+    highlighted: bool,
+    /// `NSArray*` of `UIImage*`. Strong reference.
+    animation_images: id,
+    animation_duration: NSTimeInterval,
+    /// Zero means repeat forever.
+    animation_repeat_count: NSInteger,
+    animation: Option<Animation>,
+}
+
+impl UIImageViewData {
+    /// The image to show when not animating.
+    fn still_image(&self) -> id {
+        if self.highlighted && self.highlighted_image != nil {
+            self.highlighted_image
+        } else {
+            self.image
+        }
+    }
+}
+
+/// State of a running `startAnimating` animation.
+struct Animation {
+    /// `NSTimer*`. Weak reference, the run loop owns it.
+    timer: id,
+    frame_count: NSUInteger,
+    current_frame: NSUInteger,
+    /// Number of times the animation has been shown in full.
+    loops_done: NSInteger,
+}
+impl Animation {
+    /// Move to the next frame. Returns [false] if the animation has finished.
+    fn advance(&mut self, repeat_count: NSInteger) -> bool {
+        self.current_frame += 1;
+        if self.current_frame < self.frame_count {
+            return true;
+        }
+        self.current_frame = 0;
+        self.loops_done += 1;
+        repeat_count == 0 || self.loops_done < repeat_count
+    }
+}
+
+fn image_view_data(env: &mut Environment, image_view: id) -> &mut UIImageViewData {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(image_view);
+    let UIViewSubclass::UIImageView(ref mut data) = host_obj.subclass else {
+        panic!();
+    };
+    data
+}
+
+/// Set the layer's contents to whichever image should currently be visible.
+fn update_displayed_image(env: &mut Environment, image_view: id) {
+    let data = image_view_data(env, image_view);
+    let image = if let Some(ref animation) = data.animation {
+        let images = data.animation_images;
+        let index = animation.current_frame;
+        msg![env; images objectAtIndex:index]
+    } else {
+        data.still_image()
+    };
+
+    // Setting the layer's contents is what gets the new image drawn.
+    // TODO: maybe this should be done in `displayLayer:` once we implement that
+    // (or whatever its UIKit equivalent is)?
+    let cg_image: CGImageRef = msg![env; image CGImage];
+    let layer: id = msg![env; image_view layer];
+    () = msg![env; layer setContents:cg_image];
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -22,19 +101,22 @@ pub const CLASSES: ClassExports = objc_classes! {
     // with alpha channels.
     () = msg![env; this setOpaque:false];
     let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
-    host_obj.subclass = UIViewSubclass::UIImageView {
-        image: nil
-    };
+    host_obj.subclass = UIViewSubclass::UIImageView(Default::default());
     this
 }
 
 - (())dealloc {
     let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
     let subclass = std::mem::take(&mut host_obj.subclass);
-    let UIViewSubclass::UIImageView { image } = subclass else {
+    let UIViewSubclass::UIImageView(data) = subclass else {
         panic!();
     };
-    release(env, image);
+    // The timer retains the image view, so it can't be deallocated while
+    // animating.
+    assert!(data.animation.is_none());
+    release(env, data.image);
+    release(env, data.highlighted_image);
+    release(env, data.animation_images);
     msg_super![env; this dealloc]
 }
 
@@ -51,22 +133,176 @@ pub const CLASSES: ClassExports = objc_classes! {
     this
 }
 
+- (id)image {
+    image_view_data(env, this).image
+}
+
 - (())setImage:(id)new_image { // UIImage*
-    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
-    let UIViewSubclass::UIImageView { ref mut image } = host_obj.subclass else {
-        panic!();
-    };
-    let old_image = std::mem::replace(image, new_image);
     retain(env, new_image);
+    let old_image = std::mem::replace(&mut image_view_data(env, this).image, new_image);
     release(env, old_image);
+    update_displayed_image(env, this);
+}
 
-    // TODO: maybe this should be done in `displayLayer:` once we implement that
-    // (or whatever its UIKit equivalent is)?
-    let cg_image: CGImageRef = msg![env; new_image CGImage];
-    let layer: id = msg![env; this layer];
-    () = msg![env; layer setContents:cg_image];
+- (id)highlightedImage {
+    image_view_data(env, this).highlighted_image
+}
+- (())setHighlightedImage:(id)new_image { // UIImage*
+    retain(env, new_image);
+    let old_image = std::mem::replace(
+        &mut image_view_data(env, this).highlighted_image,
+        new_image,
+    );
+    release(env, old_image);
+    update_displayed_image(env, this);
+}
+
+- (bool)isHighlighted {
+    image_view_data(env, this).highlighted
+}
+- (())setHighlighted:(bool)highlighted {
+    image_view_data(env, this).highlighted = highlighted;
+    update_displayed_image(env, this);
+}
+
+- (id)animationImages {
+    image_view_data(env, this).animation_images
+}
+- (())setAnimationImages:(id)images { // NSArray* of UIImage*
+    // The running animation relies on the frame count staying the same.
+    () = msg![env; this stopAnimating];
+    let images: id = msg![env; images copy];
+    let old_images = std::mem::replace(&mut image_view_data(env, this).animation_images, images);
+    release(env, old_images);
+}
+
+- (NSTimeInterval)animationDuration {
+    image_view_data(env, this).animation_duration
+}
+- (())setAnimationDuration:(NSTimeInterval)duration {
+    image_view_data(env, this).animation_duration = duration;
+}
+
+- (NSInteger)animationRepeatCount {
+    image_view_data(env, this).animation_repeat_count
+}
+- (())setAnimationRepeatCount:(NSInteger)count {
+    image_view_data(env, this).animation_repeat_count = count;
+}
+
+- (())startAnimating {
+    let data = image_view_data(env, this);
+    if data.animation.is_some() {
+        return;
+    }
+    let images = data.animation_images;
+    let duration = data.animation_duration;
+    let frame_count: NSUInteger = msg![env; images count];
+    if frame_count == 0 {
+        return;
+    }
+    // The default duration is 30 frames per second.
+    let duration = if duration > 0.0 {
+        duration
+    } else {
+        frame_count as NSTimeInterval / 30.0
+    };
+    let interval = duration / frame_count as NSTimeInterval;
+
+    let selector = env.objc.lookup_selector("_touchHLE_animationTimerFired:").unwrap();
+    let timer: id = msg_class![env; NSTimer scheduledTimerWithTimeInterval:interval
+                                                                    target:this
+                                                                  selector:selector
+                                                                  userInfo:nil
+                                                                   repeats:true];
+    image_view_data(env, this).animation = Some(Animation {
+        timer,
+        frame_count,
+        current_frame: 0,
+        loops_done: 0,
+    });
+    update_displayed_image(env, this);
+}
+
+- (())stopAnimating {
+    let Some(animation) = image_view_data(env, this).animation.take() else {
+        return;
+    };
+    let timer = animation.timer;
+    () = msg![env; timer invalidate];
+    update_displayed_image(env, this);
+}
+
+- (bool)isAnimating {
+    image_view_data(env, this).animation.is_some()
+}
+
+- (())_touchHLE_animationTimerFired:(id)_timer { // NSTimer*
+    let data = image_view_data(env, this);
+    let repeat_count = data.animation_repeat_count;
+    let Some(ref mut animation) = data.animation else {
+        return;
+    };
+    if animation.advance(repeat_count) {
+        update_displayed_image(env, this);
+    } else {
+        () = msg![env; this stopAnimating];
+    }
+}
+
+- (CGSize)sizeThatFits:(CGSize)_size {
+    // The proposed size is ignored: image views always want to be the size of
+    // their image.
+    let image = image_view_data(env, this).image;
+    if image == nil {
+        return CGSize { width: 0.0, height: 0.0 };
+    }
+    msg![env; image size]
 }
 
 @end
 
 };
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animation_advance() {
+        let mut animation = Animation {
+            timer: nil,
+            frame_count: 3,
+            current_frame: 0,
+            loops_done: 0,
+        };
+        let mut frames = Vec::new();
+        while animation.advance(2) {
+            frames.push(animation.current_frame);
+        }
+        assert_eq!(frames, [1, 2, 0, 1, 2]);
+        assert_eq!(animation.loops_done, 2);
+
+        // A repeat count of zero means forever.
+        for _ in 0..100 {
+            assert!(animation.advance(0));
+        }
+    }
+
+    #[test]
+    fn test_still_image() {
+        let image: id = crate::mem::Ptr::from_bits(0x1000);
+        let highlighted_image: id = crate::mem::Ptr::from_bits(0x2000);
+        let mut data = UIImageViewData {
+            image,
+            highlighted: true,
+            ..Default::default()
+        };
+        // No highlighted image, so the normal one is used.
+        assert_eq!(data.still_image(), image);
+        data.highlighted_image = highlighted_image;
+        assert_eq!(data.still_image(), highlighted_image);
+        data.highlighted = false;
+        assert_eq!(data.still_image(), image);
+    }
+}
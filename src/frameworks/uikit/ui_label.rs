@@ -1,4 +1,5 @@
-use crate::objc::{objc_classes, ClassExports};
+use super::ui_view::{UIViewHostObject, UIViewSubclass};
+use crate::objc::{id, msg, msg_super, nil, objc_classes, release, ClassExports};
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -6,6 +7,43 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UILabel: UIView
 
+- (id)init {
+    let this: id = msg_super![env; this init];
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    host_obj.subclass = UIViewSubclass::UILabel {
+        text: nil
+    };
+    this
+}
+
+- (())dealloc {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let subclass = std::mem::take(&mut host_obj.subclass);
+    let UIViewSubclass::UILabel { text } = subclass else {
+        panic!();
+    };
+    release(env, text);
+    msg_super![env; this dealloc]
+}
+
+- (id)text {
+    let UIViewSubclass::UILabel { text } = env.objc.borrow::<UIViewHostObject>(this).subclass else {
+        panic!();
+    };
+    text
+}
+
+- (())setText:(id)new_text { // NSString*
+    let new_text: id = msg![env; new_text copy];
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UILabel { ref mut text } = host_obj.subclass else {
+        panic!();
+    };
+    let old_text = std::mem::replace(text, new_text);
+    release(env, old_text);
+    // TODO: drawing
+}
+
 @end
 
-};
\ No newline at end of file
+};
@@ -1,4 +1,13 @@
-use crate::objc::{objc_classes, ClassExports};
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UIScrollView`.
+
+use super::ui_view::{UIScrollViewSubclass, UIViewHostObject, UIViewSubclass};
+use crate::frameworks::core_graphics::CGSize;
+use crate::objc::{id, msg_super, objc_classes, ClassExports};
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -6,6 +15,47 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UIScrollView: UIView
 
+- (id)init {
+    let this: id = msg_super![env; this init];
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    host_obj.subclass = UIViewSubclass::UIScrollView {
+        content_size: CGSize {
+            width: 0.0,
+            height: 0.0,
+        },
+        subclass: UIScrollViewSubclass::UIScrollView,
+    };
+    this
+}
+
+- (())dealloc {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let subclass = std::mem::take(&mut host_obj.subclass);
+    let UIViewSubclass::UIScrollView { subclass, .. } = subclass else {
+        panic!();
+    };
+    // This assert forces subclasses to clean up their data in their dealloc
+    // implementation, like for UIView.
+    assert!(matches!(subclass, UIScrollViewSubclass::UIScrollView));
+    msg_super![env; this dealloc]
+}
+
+- (CGSize)contentSize {
+    let UIViewSubclass::UIScrollView { content_size, .. } = env.objc.borrow::<UIViewHostObject>(this).subclass else {
+        panic!();
+    };
+    content_size
+}
+- (())setContentSize:(CGSize)new_size {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UIScrollView { ref mut content_size, .. } = host_obj.subclass else {
+        panic!();
+    };
+    *content_size = new_size;
+}
+
+// TODO: scrolling
+
 @end
 
-};
\ No newline at end of file
+};
@@ -0,0 +1,220 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UISlider`.
+
+use super::ui_control::{send_actions, UIControlEventValueChanged};
+use super::ui_view::{UIControlSubclass, UIViewHostObject, UIViewSubclass};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::objc::{id, msg, msg_super, objc_classes, ClassExports};
+use crate::Environment;
+
+pub(super) struct UISliderData {
+    value: f32,
+    minimum_value: f32,
+    maximum_value: f32,
+    /// If [true], value changes are sent while the user is still dragging,
+    /// otherwise only when they let go.
+    continuous: bool,
+    /// The value when the current touch began, if there is one.
+    value_at_touch_start: Option<f32>,
+}
+impl Default for UISliderData {
+    fn default() -> Self {
+        UISliderData {
+            value: 0.0,
+            minimum_value: 0.0,
+            maximum_value: 1.0,
+            continuous: true,
+            value_at_touch_start: None,
+        }
+    }
+}
+impl UISliderData {
+    /// Keep the value within the minimum and maximum. If they are the wrong
+    /// way round, the minimum wins.
+    fn clamp_value(&mut self) {
+        self.value = self.value.min(self.maximum_value).max(self.minimum_value);
+    }
+
+    /// Set the value according to a touch's horizontal position on a track
+    /// starting at `track_x` with width `track_width`. Returns [true] if the
+    /// value changed.
+    fn set_value_from_touch(&mut self, x: CGFloat, track_x: CGFloat, track_width: CGFloat) -> bool {
+        let fraction = if track_width > 0.0 {
+            ((x - track_x) / track_width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let old_value = self.value;
+        self.value = self.minimum_value + fraction * (self.maximum_value - self.minimum_value);
+        self.clamp_value();
+        self.value != old_value
+    }
+}
+
+/// Update the slider's value for a touch and send value-changed actions if
+/// appropriate.
+fn track_touch(env: &mut Environment, slider: id, touch: id, event: id, ended: bool) {
+    let location: CGPoint = msg![env; touch locationInView:slider];
+    let bounds: CGRect = msg![env; slider bounds];
+
+    let data = slider_data(env, slider);
+    let changed = data.set_value_from_touch(location.x, bounds.origin.x, bounds.size.width);
+    let send = if data.continuous {
+        changed
+    } else if ended {
+        data.value_at_touch_start != Some(data.value)
+    } else {
+        false
+    };
+    if ended {
+        data.value_at_touch_start = None;
+    }
+    if send {
+        send_actions(env, slider, UIControlEventValueChanged, event);
+    }
+}
+
+fn slider_data(env: &mut Environment, slider: id) -> &mut UISliderData {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(slider);
+    let UIViewSubclass::UIControl {
+        subclass: UIControlSubclass::UISlider(ref mut data),
+        ..
+    } = host_obj.subclass
+    else {
+        panic!();
+    };
+    data
+}
+
+pub const CLASSES: ClassExports = objc_classes! {
+
+(env, this, _cmd);
+
+@implementation UISlider: UIControl
+
+- (id)init {
+    let this: id = msg_super![env; this init];
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UIControl { ref mut subclass, .. } = host_obj.subclass else {
+        panic!();
+    };
+    *subclass = UIControlSubclass::UISlider(Default::default());
+    this
+}
+
+- (())dealloc {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UIControl { ref mut subclass, .. } = host_obj.subclass else {
+        panic!();
+    };
+    let UIControlSubclass::UISlider(_) = std::mem::take(subclass) else {
+        panic!();
+    };
+    msg_super![env; this dealloc]
+}
+
+- (f32)value {
+    slider_data(env, this).value
+}
+- (())setValue:(f32)value {
+    let data = slider_data(env, this);
+    data.value = value;
+    data.clamp_value();
+}
+- (())setValue:(f32)value animated:(bool)_animated {
+    // TODO: animation
+    msg![env; this setValue:value]
+}
+
+- (f32)minimumValue {
+    slider_data(env, this).minimum_value
+}
+- (())setMinimumValue:(f32)minimum_value {
+    let data = slider_data(env, this);
+    data.minimum_value = minimum_value;
+    data.clamp_value();
+}
+
+- (f32)maximumValue {
+    slider_data(env, this).maximum_value
+}
+- (())setMaximumValue:(f32)maximum_value {
+    let data = slider_data(env, this);
+    data.maximum_value = maximum_value;
+    data.clamp_value();
+}
+
+- (bool)isContinuous {
+    slider_data(env, this).continuous
+}
+- (())setContinuous:(bool)continuous {
+    slider_data(env, this).continuous = continuous;
+}
+
+- (bool)beginTrackingWithTouch:(id)touch // UITouch*
+                     withEvent:(id)event { // UIEvent*
+    let data = slider_data(env, this);
+    data.value_at_touch_start = Some(data.value);
+    track_touch(env, this, touch, event, false);
+    true
+}
+
+- (bool)continueTrackingWithTouch:(id)touch // UITouch*
+                        withEvent:(id)event { // UIEvent*
+    track_touch(env, this, touch, event, false);
+    true
+}
+
+- (())endTrackingWithTouch:(id)touch // UITouch*
+                 withEvent:(id)event { // UIEvent*
+    track_touch(env, this, touch, event, true);
+}
+
+// TODO: drawing
+
+@end
+
+};
+
+#[cfg(test)]
+mod tests {
+    use super::UISliderData;
+
+    #[test]
+    fn test_clamp_value() {
+        let mut data = UISliderData {
+            value: 2.0,
+            ..Default::default()
+        };
+        data.clamp_value();
+        assert_eq!(data.value, 1.0);
+        data.value = -1.0;
+        data.clamp_value();
+        assert_eq!(data.value, 0.0);
+        data.maximum_value = 10.0;
+        data.value = 5.0;
+        data.clamp_value();
+        assert_eq!(data.value, 5.0);
+    }
+
+    #[test]
+    fn test_set_value_from_touch() {
+        let mut data = UISliderData {
+            minimum_value: 10.0,
+            maximum_value: 20.0,
+            ..Default::default()
+        };
+        assert!(data.set_value_from_touch(75.0, 50.0, 100.0));
+        assert_eq!(data.value, 12.5);
+        assert!(!data.set_value_from_touch(75.0, 50.0, 100.0));
+        // Touches outside the track are clamped to its ends.
+        assert!(data.set_value_from_touch(500.0, 50.0, 100.0));
+        assert_eq!(data.value, 20.0);
+        assert!(data.set_value_from_touch(0.0, 50.0, 100.0));
+        assert_eq!(data.value, 10.0);
+    }
+}
@@ -1,4 +1,328 @@
-use crate::objc::{objc_classes, ClassExports};
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! `UITextView`.
+
+use super::ui_color;
+use super::ui_font::{
+    self, UILineBreakModeWordWrap, UITextAlignment, UITextAlignmentCenter, UITextAlignmentLeft,
+    UITextAlignmentRight,
+};
+use super::ui_graphics::{UIGraphicsPopContext, UIGraphicsPushContext};
+use super::ui_view::{UIScrollViewSubclass, UIViewHostObject, UIViewSubclass};
+use crate::frameworks::core_graphics::cg_bitmap_context::CGBitmapContextCreate;
+use crate::frameworks::core_graphics::cg_color_space::{
+    CGColorSpaceCreateDeviceRGB, CGColorSpaceRelease,
+};
+use crate::frameworks::core_graphics::cg_context::{CGContextRelease, CGContextSetRGBFillColor};
+use crate::frameworks::core_graphics::cg_image::{
+    self, kCGImageAlphaPremultipliedLast, CGImageRelease,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
+use crate::frameworks::foundation::ns_string::{from_rust_string, get_static_str, to_rust_string};
+use crate::frameworks::foundation::{NSRange, NSUInteger};
+use crate::image::Image;
+use crate::mem::GuestUSize;
+use crate::objc::{
+    id, msg, msg_class, msg_super, nil, objc_classes, release, retain, ClassExports,
+};
+use crate::window::Event;
+use crate::Environment;
+
+#[derive(Default)]
+pub struct State {
+    /// The text view being edited, which receives host keyboard input. Weak
+    /// reference.
+    editing_view: Option<id>,
+}
+
+#[derive(Default)]
+pub(super) struct UITextViewData {
+    /// `NSString*`. Strong reference.
+    text: id,
+    /// `UIFont*`. Strong reference.
+    font: id,
+    /// `UIColor*`. Strong reference.
+    text_color: id,
+    text_alignment: UITextAlignment,
+    editable: bool,
+    /// Whether this is the first responder, i.e. being edited.
+    editing: bool,
+    /// Something implementing `UITextViewDelegate`. Weak reference.
+    delegate: id,
+    /// In UTF-16 code units, like `NSString`.
+    selected_range: NSRange,
+}
+
+/// Is `idx` between the two halves of a surrogate pair in `text`?
+fn splits_surrogate_pair(text: &[u16], idx: usize) -> bool {
+    idx > 0
+        && idx < text.len()
+        && (0xD800..0xDC00).contains(&text[idx - 1])
+        && (0xDC00..0xE000).contains(&text[idx])
+}
+
+/// Replace the `range` of `text` with `insertion`, returning the range of the
+/// caret afterwards. The range is clamped to the text length, and widened so
+/// it doesn't split a surrogate pair (e.g. an emoji).
+fn replace_range(text: &mut Vec<u16>, range: NSRange, insertion: &[u16]) -> NSRange {
+    let mut start = (range.location as usize).min(text.len());
+    let mut end = (start + range.length as usize).min(text.len());
+    let is_empty = start == end;
+    if splits_surrogate_pair(text, start) {
+        start -= 1;
+    }
+    if is_empty {
+        end = start;
+    } else if splits_surrogate_pair(text, end) {
+        end += 1;
+    }
+    text.splice(start..end, insertion.iter().copied());
+    NSRange {
+        location: (start + insertion.len()) as NSUInteger,
+        length: 0,
+    }
+}
+
+/// Get the range that `deleteBackward` should delete: the selection, or if it
+/// is empty, the character (code point) before the caret.
+fn range_to_delete_backward(text: &[u16], range: NSRange) -> Option<NSRange> {
+    if range.length > 0 {
+        return Some(range);
+    }
+    let end = (range.location as usize).min(text.len());
+    if end == 0 {
+        return None;
+    }
+    let start = if splits_surrogate_pair(text, end - 1) {
+        end - 2
+    } else {
+        end - 1
+    };
+    Some(NSRange {
+        location: start as NSUInteger,
+        length: (end - start) as NSUInteger,
+    })
+}
+
+/// The part of `becomeFirstResponder` that doesn't involve the delegate: given
+/// whether the text view is editable and already being edited, get the result
+/// to return straight away, or [None] if it should go on to start editing.
+fn begin_editing_result(editable: bool, editing: bool) -> Option<bool> {
+    if !editable {
+        Some(false)
+    } else if editing {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+fn text_view_data(env: &mut Environment, text_view: id) -> &mut UITextViewData {
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(text_view);
+    let UIViewSubclass::UIScrollView {
+        subclass: UIScrollViewSubclass::UITextView(ref mut data),
+        ..
+    } = host_obj.subclass
+    else {
+        panic!();
+    };
+    data
+}
+
+/// Get the delegate, if it implements the named method.
+fn delegate_implementing(env: &mut Environment, text_view: id, method: &str) -> Option<id> {
+    let delegate = text_view_data(env, text_view).delegate;
+    if delegate != nil && env.objc.object_has_method_named(&env.mem, delegate, method) {
+        Some(delegate)
+    } else {
+        None
+    }
+}
+
+/// Should a change to the text be reported to the delegate with
+/// `textViewDidChange:`? Edits made like the user typing always are, but
+/// `setText:` only is while the text view is being edited.
+fn reports_change(typed: bool, editing: bool) -> bool {
+    typed || editing
+}
+
+/// Set the content size to fit the text at the current width, so that it can
+/// be scrolled.
+fn update_content_size(env: &mut Environment, text_view: id) {
+    let &mut UITextViewData { text, font, .. } = text_view_data(env, text_view);
+    let bounds: CGRect = msg![env; text_view bounds];
+    let height = if text == nil {
+        0.0
+    } else {
+        // TODO: insets
+        let text = to_rust_string(env, text);
+        let constraint = CGSize {
+            width: bounds.size.width,
+            height: CGFloat::MAX,
+        };
+        ui_font::size_with_font(
+            env,
+            font,
+            &text,
+            Some((constraint, UILineBreakModeWordWrap)),
+        )
+        .height
+    };
+    let size = CGSize {
+        width: bounds.size.width,
+        height,
+    };
+    () = msg![env; text_view setContentSize:size];
+}
+
+/// Get the alignment if text drawing supports it, otherwise [None].
+fn supported_alignment(alignment: UITextAlignment) -> Option<UITextAlignment> {
+    match alignment {
+        UITextAlignmentLeft | UITextAlignmentCenter | UITextAlignmentRight => Some(alignment),
+        _ => None,
+    }
+}
+
+/// Draw the text into an image and make it the layer's contents. This needs to
+/// be called whenever anything affecting the appearance changes.
+fn update_rendering(env: &mut Environment, text_view: id) {
+    let &mut UITextViewData {
+        text,
+        font,
+        text_color,
+        text_alignment,
+        ..
+    } = text_view_data(env, text_view);
+    let layer: id = msg![env; text_view layer];
+    let bounds: CGRect = msg![env; text_view bounds];
+    let width = bounds.size.width.max(0.0).ceil() as GuestUSize;
+    let height = bounds.size.height.max(0.0).ceil() as GuestUSize;
+    if text == nil || width == 0 || height == 0 {
+        () = msg![env; layer setContents:nil];
+        return;
+    }
+    let text = to_rust_string(env, text);
+    let alignment = supported_alignment(text_alignment).unwrap_or_else(|| {
+        log!(
+            "TODO: text alignment {} for {:?}, using left alignment",
+            text_alignment,
+            text_view
+        );
+        UITextAlignmentLeft
+    });
+    let (r, g, b, a) = if text_color == nil {
+        (0.0, 0.0, 0.0, 1.0)
+    } else {
+        ui_color::get_rgba(&env.objc, text_color)
+    };
+
+    let bytes_per_row = width * 4;
+    let size = bytes_per_row * height;
+    let pixels = env.mem.alloc(size);
+    env.mem.bytes_at_mut(pixels.cast(), size).fill(0);
+    let color_space = CGColorSpaceCreateDeviceRGB(env);
+    let context = CGBitmapContextCreate(
+        env,
+        pixels,
+        width,
+        height,
+        8,
+        bytes_per_row,
+        color_space,
+        kCGImageAlphaPremultipliedLast,
+    );
+    CGColorSpaceRelease(env, color_space);
+
+    CGContextSetRGBFillColor(env, context, r, g, b, a);
+    UIGraphicsPushContext(env, context);
+    // TODO: scrolling, insets
+    let rect = CGRect {
+        origin: CGPoint { x: 0.0, y: 0.0 },
+        size: bounds.size,
+    };
+    ui_font::draw_in_rect(env, font, &text, rect, UILineBreakModeWordWrap, alignment);
+    UIGraphicsPopContext(env);
+    CGContextRelease(env, context);
+
+    // Text drawing lays out lines from the top of the context, but its rows
+    // are stored bottom-to-top, so they need reversing for an image.
+    let pixel_vec = env.mem.bytes_at(pixels.cast(), size).to_vec();
+    env.mem.free(pixels);
+    let mut image = Image::from_pixel_vec(pixel_vec, (width, height));
+    image.flip_vertical();
+    let image = cg_image::from_image(env, image);
+    () = msg![env; layer setContents:image];
+    CGImageRelease(env, image);
+}
+
+/// Set the text (which must already be owned by the caller) without moving
+/// the selection or notifying the delegate.
+fn set_text_inner(env: &mut Environment, text_view: id, text: id) {
+    let old_text = std::mem::replace(&mut text_view_data(env, text_view).text, text);
+    release(env, old_text);
+    update_content_size(env, text_view);
+    update_rendering(env, text_view);
+}
+
+/// Shared part of `insertText:` and `deleteBackward`, which edit the text like
+/// the user typing would.
+fn edit_text(env: &mut Environment, text_view: id, range: NSRange, insertion: &str) {
+    let text: id = msg![env; text_view text];
+    let mut utf16: Vec<u16> = to_rust_string(env, text).encode_utf16().collect();
+    let insertion: Vec<u16> = insertion.encode_utf16().collect();
+    let new_range = replace_range(&mut utf16, range, &insertion);
+    // replace_range() keeps surrogate pairs intact, so this is lossless.
+    let new_text = from_rust_string(env, String::from_utf16_lossy(&utf16));
+    set_text_inner(env, text_view, new_text);
+    text_view_data(env, text_view).selected_range = new_range;
+    notify_did_change(env, text_view, true);
+}
+
+/// Send `textViewDidChange:` to the delegate, if the change should be
+/// reported (see [reports_change]).
+fn notify_did_change(env: &mut Environment, text_view: id, typed: bool) {
+    let editing = text_view_data(env, text_view).editing;
+    if !reports_change(typed, editing) {
+        return;
+    }
+    if let Some(delegate) = delegate_implementing(env, text_view, "textViewDidChange:") {
+        () = msg![env; delegate textViewDidChange:text_view];
+    }
+}
+
+/// Start or stop sending host keyboard input to a text view.
+fn set_editing_view(env: &mut Environment, text_view: Option<id>) {
+    if text_view.is_some() {
+        env.window.start_text_input();
+    } else {
+        env.window.stop_text_input();
+    }
+    env.framework_state.uikit.ui_text_view.editing_view = text_view;
+}
+
+/// For use by [super::handle_events]: sends host keyboard input to the text
+/// view being edited, if any.
+pub fn handle_event(env: &mut Environment, event: Event) {
+    let Some(text_view) = env.framework_state.uikit.ui_text_view.editing_view else {
+        return;
+    };
+    match event {
+        Event::TextInput(text) => {
+            log_dbg!("Text input {:?} for {:?}", text, text_view);
+            let text = from_rust_string(env, text);
+            () = msg![env; text_view insertText:text];
+            release(env, text);
+        }
+        Event::TextBackspace => {
+            log_dbg!("Backspace for {:?}", text_view);
+            () = msg![env; text_view deleteBackward];
+        }
+        _ => unreachable!(),
+    }
+}
 
 pub const CLASSES: ClassExports = objc_classes! {
 
@@ -6,6 +330,346 @@ pub const CLASSES: ClassExports = objc_classes! {
 
 @implementation UITextView: UIScrollView
 
+- (id)init {
+    let this: id = msg_super![env; this init];
+    let font: id = msg_class![env; UIFont systemFontOfSize:(12.0 as CGFloat)];
+    retain(env, font);
+    let text_color: id = msg_class![env; UIColor blackColor];
+    retain(env, text_color);
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UIScrollView { ref mut subclass, .. } = host_obj.subclass else {
+        panic!();
+    };
+    *subclass = UIScrollViewSubclass::UITextView(UITextViewData {
+        font,
+        text_color,
+        text_alignment: UITextAlignmentLeft,
+        editable: true,
+        ..Default::default()
+    });
+    this
+}
+
+- (())dealloc {
+    if env.framework_state.uikit.ui_text_view.editing_view == Some(this) {
+        set_editing_view(env, None);
+    }
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    let UIViewSubclass::UIScrollView { ref mut subclass, .. } = host_obj.subclass else {
+        panic!();
+    };
+    let UIScrollViewSubclass::UITextView(data) = std::mem::take(subclass) else {
+        panic!();
+    };
+    release(env, data.text);
+    release(env, data.font);
+    release(env, data.text_color);
+    msg_super![env; this dealloc]
+}
+
+- (id)text {
+    let text = text_view_data(env, this).text;
+    if text == nil {
+        get_static_str(env, "")
+    } else {
+        text
+    }
+}
+- (())setText:(id)text { // NSString*
+    let text: id = msg![env; text copy];
+    set_text_inner(env, this, text);
+    // The caret moves to the end.
+    let length: NSUInteger = msg![env; text length];
+    text_view_data(env, this).selected_range = NSRange {
+        location: length,
+        length: 0,
+    };
+    notify_did_change(env, this, false);
+}
+
+// The content size and rendering depend on the size.
+- (())setFrame:(CGRect)frame {
+    () = msg_super![env; this setFrame:frame];
+    update_content_size(env, this);
+    update_rendering(env, this);
+}
+- (())setBounds:(CGRect)bounds {
+    () = msg_super![env; this setBounds:bounds];
+    update_content_size(env, this);
+    update_rendering(env, this);
+}
+
+- (NSRange)selectedRange {
+    text_view_data(env, this).selected_range
+}
+- (())setSelectedRange:(NSRange)range {
+    text_view_data(env, this).selected_range = range;
+}
+
+// UIKeyInput implementation
+- (bool)hasText {
+    let text = text_view_data(env, this).text;
+    let length: NSUInteger = msg![env; text length];
+    length > 0
+}
+- (())insertText:(id)insertion { // NSString*
+    let insertion = to_rust_string(env, insertion);
+    let range = text_view_data(env, this).selected_range;
+    edit_text(env, this, range, &insertion);
+}
+- (())deleteBackward {
+    let range = text_view_data(env, this).selected_range;
+    let text: id = msg![env; this text];
+    let text: Vec<u16> = to_rust_string(env, text).encode_utf16().collect();
+    if let Some(range) = range_to_delete_backward(&text, range) {
+        edit_text(env, this, range, "");
+    }
+}
+
+- (id)delegate {
+    text_view_data(env, this).delegate
+}
+- (())setDelegate:(id)delegate { // something implementing UITextViewDelegate
+    text_view_data(env, this).delegate = delegate;
+}
+
+- (id)font {
+    text_view_data(env, this).font
+}
+- (())setFont:(id)font { // UIFont*
+    // nil means the default font.
+    let font: id = if font == nil {
+        msg_class![env; UIFont systemFontOfSize:(12.0 as CGFloat)]
+    } else {
+        font
+    };
+    retain(env, font);
+    let old_font = std::mem::replace(&mut text_view_data(env, this).font, font);
+    release(env, old_font);
+    update_content_size(env, this);
+    update_rendering(env, this);
+}
+
+- (id)textColor {
+    text_view_data(env, this).text_color
+}
+- (())setTextColor:(id)color { // UIColor*
+    retain(env, color);
+    let old_color = std::mem::replace(&mut text_view_data(env, this).text_color, color);
+    release(env, old_color);
+    update_rendering(env, this);
+}
+
+- (UITextAlignment)textAlignment {
+    text_view_data(env, this).text_alignment
+}
+- (())setTextAlignment:(UITextAlignment)alignment {
+    text_view_data(env, this).text_alignment = alignment;
+    update_rendering(env, this);
+}
+
+- (bool)isEditable {
+    text_view_data(env, this).editable
+}
+- (())setEditable:(bool)editable {
+    if !editable && text_view_data(env, this).editing {
+        () = msg![env; this resignFirstResponder];
+    }
+    text_view_data(env, this).editable = editable;
+}
+
+- (bool)canBecomeFirstResponder {
+    text_view_data(env, this).editable
+}
+- (bool)isFirstResponder {
+    text_view_data(env, this).editing
+}
+- (bool)becomeFirstResponder {
+    let &mut UITextViewData { editable, editing, .. } = text_view_data(env, this);
+    if let Some(result) = begin_editing_result(editable, editing) {
+        return result;
+    }
+    if let Some(delegate) = delegate_implementing(env, this, "textViewShouldBeginEditing:") {
+        let should_begin: bool = msg![env; delegate textViewShouldBeginEditing:this];
+        if !should_begin {
+            return false;
+        }
+    }
+    // Only one text view can have the keyboard at a time.
+    if let Some(other) = env.framework_state.uikit.ui_text_view.editing_view {
+        let resigned: bool = msg![env; other resignFirstResponder];
+        if !resigned {
+            return false;
+        }
+    }
+    text_view_data(env, this).editing = true;
+    set_editing_view(env, Some(this));
+    if let Some(delegate) = delegate_implementing(env, this, "textViewDidBeginEditing:") {
+        () = msg![env; delegate textViewDidBeginEditing:this];
+    }
+    true
+}
+- (bool)resignFirstResponder {
+    if !text_view_data(env, this).editing {
+        return true;
+    }
+    if let Some(delegate) = delegate_implementing(env, this, "textViewShouldEndEditing:") {
+        let should_end: bool = msg![env; delegate textViewShouldEndEditing:this];
+        if !should_end {
+            return false;
+        }
+    }
+    text_view_data(env, this).editing = false;
+    if env.framework_state.uikit.ui_text_view.editing_view == Some(this) {
+        set_editing_view(env, None);
+    }
+    if let Some(delegate) = delegate_implementing(env, this, "textViewDidEndEditing:") {
+        () = msg![env; delegate textViewDidEndEditing:this];
+    }
+    true
+}
+
 @end
 
-};
\ No newline at end of file
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str) -> Vec<u16> {
+        s.encode_utf16().collect()
+    }
+
+    #[test]
+    fn test_replace_range() {
+        let mut text = utf16("helo");
+        let caret = NSRange {
+            location: 3,
+            length: 0,
+        };
+        let caret = replace_range(&mut text, caret, &utf16("l"));
+        assert_eq!(text, utf16("hello"));
+        assert_eq!(
+            caret,
+            NSRange {
+                location: 4,
+                length: 0
+            }
+        );
+
+        // Replacing a selection
+        let selection = NSRange {
+            location: 1,
+            length: 4,
+        };
+        let caret = replace_range(&mut text, selection, &utf16("i"));
+        assert_eq!(text, utf16("hi"));
+        assert_eq!(
+            caret,
+            NSRange {
+                location: 2,
+                length: 0
+            }
+        );
+    }
+
+    #[test]
+    fn test_delete_backward() {
+        let mut text = utf16("hi!");
+        let caret = NSRange {
+            location: 3,
+            length: 0,
+        };
+        let range = range_to_delete_backward(&text, caret).unwrap();
+        let caret = replace_range(&mut text, range, &[]);
+        assert_eq!(text, utf16("hi"));
+        assert_eq!(
+            caret,
+            NSRange {
+                location: 2,
+                length: 0
+            }
+        );
+
+        let start = NSRange {
+            location: 0,
+            length: 0,
+        };
+        assert_eq!(range_to_delete_backward(&text, start), None);
+    }
+
+    #[test]
+    fn test_begin_editing_result() {
+        // A non-editable text view can never become first responder.
+        assert_eq!(begin_editing_result(false, false), Some(false));
+        assert_eq!(begin_editing_result(false, true), Some(false));
+        // Becoming first responder again is a no-op.
+        assert_eq!(begin_editing_result(true, true), Some(true));
+        assert_eq!(begin_editing_result(true, false), None);
+    }
+
+    #[test]
+    fn test_supported_alignment() {
+        for alignment in [
+            UITextAlignmentLeft,
+            UITextAlignmentCenter,
+            UITextAlignmentRight,
+        ] {
+            assert_eq!(supported_alignment(alignment), Some(alignment));
+        }
+        // Anything else would hit unimplemented!() in ui_font::draw_in_rect().
+        assert_eq!(supported_alignment(3), None);
+        assert_eq!(supported_alignment(-1), None);
+    }
+
+    #[test]
+    fn test_reports_change() {
+        assert!(reports_change(true, true));
+        assert!(reports_change(true, false));
+        assert!(reports_change(false, true));
+        // Setting the text programmatically when not editing is silent.
+        assert!(!reports_change(false, false));
+    }
+
+    #[test]
+    fn test_edit_emoji() {
+        // U+1F600 is a surrogate pair in UTF-16.
+        let mut text = utf16("a\u{1F600}");
+        assert_eq!(text.len(), 3);
+        let caret = NSRange {
+            location: 3,
+            length: 0,
+        };
+        let range = range_to_delete_backward(&text, caret).unwrap();
+        let caret = replace_range(&mut text, range, &[]);
+        assert_eq!(text, utf16("a"));
+        assert_eq!(
+            caret,
+            NSRange {
+                location: 1,
+                length: 0
+            }
+        );
+
+        // Deleting with the caret in the middle of the pair removes all of it.
+        let mut text = utf16("\u{1F600}b");
+        let caret = NSRange {
+            location: 1,
+            length: 0,
+        };
+        let range = range_to_delete_backward(&text, caret).unwrap();
+        replace_range(&mut text, range, &[]);
+        assert_eq!(text, utf16("b"));
+
+        // Inserting in the middle of the pair goes before it instead.
+        let mut text = utf16("\u{1F600}");
+        let caret = NSRange {
+            location: 1,
+            length: 0,
+        };
+        let caret = replace_range(&mut text, caret, &utf16("x"));
+        assert_eq!(String::from_utf16(&text).unwrap(), "x\u{1F600}");
+        assert_eq!(caret.location, 1);
+    }
+}
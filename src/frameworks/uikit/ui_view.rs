@@ -5,13 +5,60 @@
  */
 //! `UIView`.
 
-use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect};
+use crate::frameworks::core_animation::ca_layer::{
+    kCAGravityBottom, kCAGravityBottomLeft, kCAGravityBottomRight, kCAGravityCenter,
+    kCAGravityLeft, kCAGravityResize, kCAGravityResizeAspect, kCAGravityResizeAspectFill,
+    kCAGravityRight, kCAGravityTop, kCAGravityTopLeft, kCAGravityTopRight,
+};
+use crate::frameworks::core_graphics::{CGFloat, CGPoint, CGRect, CGSize};
 use crate::frameworks::foundation::ns_string::{get_static_str, to_rust_string};
-use crate::frameworks::foundation::NSUInteger;
+use crate::frameworks::foundation::{NSInteger, NSUInteger};
 use crate::objc::{
-    id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject, NSZonePtr,
+    id, msg, nil, objc_classes, release, retain, Class, ClassExports, HostObject, NSZonePtr, SEL,
 };
 
+pub type UIViewContentMode = NSInteger;
+pub const UIViewContentModeScaleToFill: UIViewContentMode = 0;
+pub const UIViewContentModeScaleAspectFit: UIViewContentMode = 1;
+pub const UIViewContentModeScaleAspectFill: UIViewContentMode = 2;
+pub const UIViewContentModeRedraw: UIViewContentMode = 3;
+pub const UIViewContentModeCenter: UIViewContentMode = 4;
+pub const UIViewContentModeTop: UIViewContentMode = 5;
+pub const UIViewContentModeBottom: UIViewContentMode = 6;
+pub const UIViewContentModeLeft: UIViewContentMode = 7;
+pub const UIViewContentModeRight: UIViewContentMode = 8;
+pub const UIViewContentModeTopLeft: UIViewContentMode = 9;
+pub const UIViewContentModeTopRight: UIViewContentMode = 10;
+pub const UIViewContentModeBottomLeft: UIViewContentMode = 11;
+pub const UIViewContentModeBottomRight: UIViewContentMode = 12;
+
+/// Get the `CALayer` `contentsGravity` corresponding to a content mode. Note
+/// that the top and bottom are swapped, because Core Animation's "top" means
+/// maximum Y.
+fn contents_gravity_for_content_mode(mode: UIViewContentMode) -> &'static str {
+    match mode {
+        UIViewContentModeScaleToFill | UIViewContentModeRedraw => kCAGravityResize,
+        UIViewContentModeScaleAspectFit => kCAGravityResizeAspect,
+        UIViewContentModeScaleAspectFill => kCAGravityResizeAspectFill,
+        UIViewContentModeCenter => kCAGravityCenter,
+        UIViewContentModeTop => kCAGravityBottom,
+        UIViewContentModeBottom => kCAGravityTop,
+        UIViewContentModeLeft => kCAGravityLeft,
+        UIViewContentModeRight => kCAGravityRight,
+        UIViewContentModeTopLeft => kCAGravityBottomLeft,
+        UIViewContentModeTopRight => kCAGravityBottomRight,
+        UIViewContentModeBottomLeft => kCAGravityTopLeft,
+        UIViewContentModeBottomRight => kCAGravityTopRight,
+        _ => {
+            log!(
+                "Warning: unknown content mode {}, using scale-to-fill",
+                mode
+            );
+            kCAGravityResize
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct State {
     /// List of views for internal purposes. Non-retaining!
@@ -23,12 +70,47 @@ pub(super) enum UIViewSubclass {
     #[default]
     /// Plain `UIView*`, or some subclass that doesn't need extra data.
     UIView,
-    UIImageView {
-        /// `UIImage*`
-        image: id,
+    UIImageView(super::ui_image_view::UIImageViewData),
+    UILabel {
+        /// `NSString*`
+        text: id,
+    },
+    UIScrollView {
+        content_size: CGSize,
+        /// Data for subclasses of `UIScrollView`
+        subclass: UIScrollViewSubclass,
+    },
+    UIControl {
+        /// Target-action pairs and the events they are for. The targets are
+        /// weak references.
+        targets: Vec<(id, SEL, super::ui_control::UIControlEvents)>,
+        /// Combination of the enabled, selected and highlighted flags.
+        state: super::ui_control::UIControlState,
+        /// Whether the current touch is being tracked, see
+        /// `beginTrackingWithTouch:withEvent:`.
+        tracking: bool,
+        /// Data for subclasses of `UIControl`
+        subclass: UIControlSubclass,
     },
 }
 
+#[derive(Default)]
+pub(super) enum UIScrollViewSubclass {
+    #[default]
+    /// Plain `UIScrollView*`, or some subclass that doesn't need extra data.
+    UIScrollView,
+    UITextView(super::ui_text_view::UITextViewData),
+}
+
+#[derive(Default)]
+pub(super) enum UIControlSubclass {
+    #[default]
+    /// Plain `UIControl*`, or some subclass that doesn't need extra data.
+    UIControl,
+    UIButton(super::ui_button::UIButtonData),
+    UISlider(super::ui_slider::UISliderData),
+}
+
 #[derive(Default)]
 pub(super) struct UIViewHostObject {
     /// CALayer or subclass.
@@ -37,6 +119,7 @@ pub(super) struct UIViewHostObject {
     subviews: Vec<id>,
     /// The superview. This is a weak reference.
     superview: id,
+    content_mode: UIViewContentMode,
     /// Subclass-specific data
     pub(super) subclass: UIViewSubclass,
 }
@@ -153,6 +236,11 @@ pub const CLASSES: ClassExports = objc_classes! {
 - (id)superview {
     env.objc.borrow::<UIViewHostObject>(this).superview
 }
+
+- (id)nextResponder {
+    // TODO: a view controller's view should return the view controller
+    env.objc.borrow::<UIViewHostObject>(this).superview
+}
 // TODO: subviews accessor
 
 - (())addSubview:(id)view {
@@ -255,6 +343,17 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; layer setOpaque:opaque]
 }
 
+- (UIViewContentMode)contentMode {
+    env.objc.borrow::<UIViewHostObject>(this).content_mode
+}
+- (())setContentMode:(UIViewContentMode)content_mode {
+    let gravity = get_static_str(env, contents_gravity_for_content_mode(content_mode));
+    let host_obj = env.objc.borrow_mut::<UIViewHostObject>(this);
+    host_obj.content_mode = content_mode;
+    let layer = host_obj.layer;
+    msg![env; layer setContentsGravity:gravity]
+}
+
 - (CGFloat)alpha {
     let layer = env.objc.borrow::<UIViewHostObject>(this).layer;
     msg![env; layer opacity]
@@ -303,6 +402,19 @@ pub const CLASSES: ClassExports = objc_classes! {
     msg![env; layer setFrame:frame]
 }
 
+- (CGSize)sizeThatFits:(CGSize)_size {
+    // By default, a view is happy with the size it already has.
+    let bounds: CGRect = msg![env; this bounds];
+    bounds.size
+}
+- (())sizeToFit {
+    let bounds: CGRect = msg![env; this bounds];
+    let size: CGSize = msg![env; this sizeThatFits:(bounds.size)];
+    let mut frame: CGRect = msg![env; this frame];
+    frame.size = size;
+    msg![env; this setFrame:frame]
+}
+
 @end
 
 @implementation UIAlertView: UIView
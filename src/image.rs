@@ -3,22 +3,52 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/.
  */
-//! Image decoding. Currently only supports PNG files (treated as 8-bit sRGB).
+//! Image decoding and encoding. Currently only supports PNG files (treated as
+//! 8-bit sRGB).
 //!
 //! Implemented as a wrapper around the C library stb_image, since it supports
 //! "CgBI" PNG files (an Apple proprietary extension used in iPhone OS apps).
+//! Encoding uses its sibling stb_image_write.
 //!
 //! This module also exposes decompression for Imagination Technologies' PVRTC
 //! format, implementing as a wrapper around their decoder from the PowerVR
 //! SDK.
 
-use std::ffi::{c_int, c_uchar, CStr};
+use std::ffi::{c_int, c_void, CStr};
 
 use touchHLE_pvrt_decompress_wrapper::*;
 use touchHLE_stb_image_wrapper::*;
 
+/// Filtering used by [Image::scaled_to].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    NearestNeighbor,
+    Bilinear,
+}
+
+/// Order of the channels in raw 32-bit pixel data (see [Image::from_raw]),
+/// from the lowest address to the highest.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChannelOrder {
+    Rgba,
+    Bgra,
+    Argb,
+    Abgr,
+}
+
+/// What the alpha channel of raw pixel data (see [Image::from_raw]) means.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AlphaMode {
+    Premultiplied,
+    Straight,
+    /// The alpha channel is padding, and the image is opaque.
+    Ignored,
+}
+
 pub struct Image {
-    pixels: *mut c_uchar,
+    /// 8 bits per channel sRGB RGBA with premultiplied alpha, rows in
+    /// top-to-bottom order.
+    pixels: Vec<u8>,
     dimensions: (u32, u32),
 }
 
@@ -59,26 +89,233 @@ impl Image {
         let width: u32 = x.try_into().unwrap();
         let height: u32 = y.try_into().unwrap();
 
+        // Copy the pixels so that we own them and can construct images from
+        // other sources too.
+        let len = width as usize * height as usize * 4;
+        let mut pixels_vec = unsafe { std::slice::from_raw_parts(pixels, len) }.to_vec();
+        unsafe { stbi_image_free(pixels.cast()) };
+
         // (Un-un-)premultiply pixels to match iPhone OS's image loading.
-        {
-            let len = width as usize * height as usize * 4;
-            let pixels = unsafe { std::slice::from_raw_parts_mut(pixels, len) };
-            let mut i = 0;
-            while i < pixels.len() {
-                let a = pixels[i + 3] as f32 / 255.0;
-                pixels[i] = (pixels[i] as f32 * a) as u8;
-                pixels[i + 1] = (pixels[i + 1] as f32 * a) as u8;
-                pixels[i + 2] = (pixels[i + 2] as f32 * a) as u8;
-                i += 4;
-            }
-        }
+        premultiply(&mut pixels_vec);
 
         Ok(Image {
-            pixels,
+            pixels: pixels_vec,
             dimensions: (width, height),
         })
     }
 
+    /// Construct an image from pixel data in the same format [Image::pixels]
+    /// returns.
+    pub fn from_pixel_vec(pixels: Vec<u8>, dimensions: (u32, u32)) -> Image {
+        assert_eq!(
+            pixels.len(),
+            dimensions.0 as usize * dimensions.1 as usize * 4
+        );
+        Image { pixels, dimensions }
+    }
+
+    /// Construct an image from 8-bit alpha-only (coverage) data, e.g. a glyph
+    /// mask. The color channels are black. Rows are in top-to-bottom order and
+    /// may be padded to `bytes_per_row`.
+    pub fn from_alpha_only(alpha: &[u8], dimensions: (u32, u32), bytes_per_row: u32) -> Image {
+        let (width, height) = dimensions;
+        assert!(bytes_per_row >= width);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            let row = &alpha[(y * bytes_per_row) as usize..][..width as usize];
+            for &a in row {
+                // Premultiplied black is all zeroes apart from the alpha.
+                pixels.extend_from_slice(&[0, 0, 0, a]);
+            }
+        }
+        Image::from_pixel_vec(pixels, dimensions)
+    }
+
+    /// Construct an opaque image from 8-bit grayscale data. Rows are in
+    /// top-to-bottom order and may be padded to `bytes_per_row`.
+    pub fn from_gray(gray: &[u8], dimensions: (u32, u32), bytes_per_row: u32) -> Image {
+        let (width, height) = dimensions;
+        assert!(bytes_per_row >= width);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            let row = &gray[(y * bytes_per_row) as usize..][..width as usize];
+            for &v in row {
+                pixels.extend_from_slice(&[v, v, v, 255]);
+            }
+        }
+        Image::from_pixel_vec(pixels, dimensions)
+    }
+
+    /// Construct an opaque image from 8-bit indices into a table of RGB colors.
+    /// Indices beyond the end of the table are black. Rows are in
+    /// top-to-bottom order and may be padded to `bytes_per_row`.
+    pub fn from_indexed(
+        indices: &[u8],
+        palette: &[[u8; 3]],
+        dimensions: (u32, u32),
+        bytes_per_row: u32,
+    ) -> Image {
+        let (width, height) = dimensions;
+        assert!(bytes_per_row >= width);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            let row = &indices[(y * bytes_per_row) as usize..][..width as usize];
+            for &index in row {
+                let [r, g, b] = palette.get(index as usize).copied().unwrap_or([0; 3]);
+                pixels.extend_from_slice(&[r, g, b, 255]);
+            }
+        }
+        Image::from_pixel_vec(pixels, dimensions)
+    }
+
+    /// Construct an image from 8-bit-per-channel, 32-bit-per-pixel data in some
+    /// channel order, converting it to the format [Image::pixels] returns.
+    /// Rows are in top-to-bottom order and may be padded to `bytes_per_row`.
+    pub fn from_raw(
+        bytes: &[u8],
+        dimensions: (u32, u32),
+        bytes_per_row: u32,
+        order: ChannelOrder,
+        alpha_mode: AlphaMode,
+    ) -> Image {
+        let (width, height) = dimensions;
+        assert!(bytes_per_row >= width * 4);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            let row = &bytes[(y * bytes_per_row) as usize..][..width as usize * 4];
+            for pixel in row.chunks_exact(4) {
+                let [p0, p1, p2, p3]: [u8; 4] = pixel.try_into().unwrap();
+                let [r, g, b, a] = match order {
+                    ChannelOrder::Rgba => [p0, p1, p2, p3],
+                    ChannelOrder::Bgra => [p2, p1, p0, p3],
+                    ChannelOrder::Argb => [p1, p2, p3, p0],
+                    ChannelOrder::Abgr => [p3, p2, p1, p0],
+                };
+                let a = if alpha_mode == AlphaMode::Ignored {
+                    255
+                } else {
+                    a
+                };
+                pixels.extend_from_slice(&[r, g, b, a]);
+            }
+        }
+        if alpha_mode == AlphaMode::Straight {
+            premultiply(&mut pixels);
+        }
+        Image::from_pixel_vec(pixels, dimensions)
+    }
+
+    /// Encode the image as a PNG file (8-bit RGBA, non-premultiplied alpha).
+    pub fn encode_png(&self) -> Vec<u8> {
+        // PNG has straight alpha, so undo the premultiplication.
+        let pixels = self.unpremultiplied().pixels;
+
+        extern "C" fn write(context: *mut c_void, data: *mut c_void, size: c_int) {
+            let out = unsafe { &mut *context.cast::<Vec<u8>>() };
+            let data = unsafe { std::slice::from_raw_parts(data.cast::<u8>(), size as usize) };
+            out.extend_from_slice(data);
+        }
+
+        let (width, height) = self.dimensions;
+        let width: c_int = width.try_into().unwrap();
+        let height: c_int = height.try_into().unwrap();
+        let mut out = Vec::<u8>::new();
+        let success = unsafe {
+            stbi_write_png_to_func(
+                write,
+                (&mut out as *mut Vec<u8>).cast(),
+                width,
+                height,
+                4,
+                pixels.as_ptr().cast(),
+                width * 4,
+            )
+        };
+        assert!(success != 0);
+        out
+    }
+
+    /// Make a copy of the image resized to `width` by `height` pixels.
+    ///
+    /// Pixel centers are aligned, so scaling up by an integer factor with
+    /// [ScalingMode::NearestNeighbor] duplicates pixels exactly. Interpolation
+    /// is done on the premultiplied values, without gamma correction.
+    pub fn scaled_to(&self, width: u32, height: u32, mode: ScalingMode) -> Image {
+        let (src_width, src_height) = self.dimensions;
+        assert!(src_width > 0 && src_height > 0);
+        let scale_x = src_width as f32 / width as f32;
+        let scale_y = src_height as f32 / height as f32;
+        // Position in the source image of the center of a destination pixel.
+        let src_coord = |dst: u32, scale: f32, src_size: u32| -> f32 {
+            ((dst as f32 + 0.5) * scale - 0.5).clamp(0.0, (src_size - 1) as f32)
+        };
+        let src_pixel = |x: u32, y: u32| -> &[u8] {
+            &self.pixels[(y as usize * src_width as usize + x as usize) * 4..][..4]
+        };
+
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            let src_y = src_coord(y, scale_y, src_height);
+            for x in 0..width {
+                let src_x = src_coord(x, scale_x, src_width);
+                match mode {
+                    ScalingMode::NearestNeighbor => {
+                        let pixel = src_pixel(src_x.round() as u32, src_y.round() as u32);
+                        pixels.extend_from_slice(pixel);
+                    }
+                    ScalingMode::Bilinear => {
+                        let (x0, y0) = (src_x.floor() as u32, src_y.floor() as u32);
+                        let (x1, y1) = ((x0 + 1).min(src_width - 1), (y0 + 1).min(src_height - 1));
+                        let (fx, fy) = (src_x.fract(), src_y.fract());
+                        let (p00, p10) = (src_pixel(x0, y0), src_pixel(x1, y0));
+                        let (p01, p11) = (src_pixel(x0, y1), src_pixel(x1, y1));
+                        for c in 0..4 {
+                            let top = p00[c] as f32 * (1.0 - fx) + p10[c] as f32 * fx;
+                            let bottom = p01[c] as f32 * (1.0 - fx) + p11[c] as f32 * fx;
+                            let value = top * (1.0 - fy) + bottom * fy;
+                            pixels.push(value.round() as u8);
+                        }
+                    }
+                }
+            }
+        }
+        Image::from_pixel_vec(pixels, (width, height))
+    }
+
+    /// Make a copy of the image with the color channels divided by alpha, i.e.
+    /// with straight alpha, for consumers that don't expect premultiplied
+    /// alpha. Fully transparent pixels become transparent black.
+    pub fn unpremultiplied(&self) -> Image {
+        let mut pixels = self.pixels.clone();
+        for pixel in pixels.chunks_exact_mut(4) {
+            let a = pixel[3] as u32;
+            for c in &mut pixel[..3] {
+                *c = if a == 0 {
+                    0
+                } else {
+                    // Values greater than alpha aren't valid premultiplied
+                    // colors, but might still show up.
+                    ((*c as u32 * 255 + a / 2) / a).min(255) as u8
+                };
+            }
+        }
+        Image::from_pixel_vec(pixels, self.dimensions)
+    }
+
+    /// Reverse the order of the rows, e.g. for consumers that expect the
+    /// bottom row first (OpenGL textures, Core Graphics bitmaps).
+    pub fn flip_vertical(&mut self) {
+        let row_size = self.dimensions.0 as usize * 4;
+        if row_size == 0 {
+            return;
+        }
+        let height = self.dimensions.1 as usize;
+        for y in 0..height / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((height - 1 - y) * row_size);
+            top[y * row_size..][..row_size].swap_with_slice(&mut bottom[..row_size]);
+        }
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         self.dimensions
     }
@@ -86,12 +323,7 @@ impl Image {
     /// Get image data as bytes (8 bits per channel sRGB RGBA with premultiplied
     /// alpha). Rows are in top-to-bottom order.
     pub fn pixels(&self) -> &[u8] {
-        unsafe {
-            std::slice::from_raw_parts(
-                self.pixels,
-                self.dimensions.0 as usize * self.dimensions.1 as usize * 4,
-            )
-        }
+        &self.pixels
     }
 
     /// Get value of a pixel as linear RGBA (not sRGB!) with premultiplied
@@ -118,9 +350,14 @@ impl Image {
     }
 }
 
-impl Drop for Image {
-    fn drop(&mut self) {
-        unsafe { stbi_image_free(self.pixels.cast()) }
+/// Multiply the color channels of 8-bit RGBA pixels by their alpha. This
+/// truncates rather than rounding, which matches iPhone OS's image loading.
+fn premultiply(pixels: &mut [u8]) {
+    for pixel in pixels.chunks_exact_mut(4) {
+        let a = pixel[3] as f32 / 255.0;
+        for c in &mut pixel[..3] {
+            *c = (*c as f32 * a) as u8;
+        }
     }
 }
 
@@ -163,3 +400,158 @@ pub fn decode_pvrtc(pvrtc_data: &[u8], is_2bit: bool, width: u32, height: u32) -
     };
     rgba8_data
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_png_round_trip() {
+        #[rustfmt::skip]
+        let pixels = vec![
+            255, 0, 0, 255,     0, 255, 0, 255,
+            0, 0, 0, 0,         64, 32, 0, 128,
+        ];
+        let image = Image::from_pixel_vec(pixels, (2, 2));
+        let png = image.encode_png();
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+        let decoded = Image::from_bytes(&png).unwrap();
+        assert_eq!(decoded.dimensions(), (2, 2));
+        assert_eq!(decoded.pixels(), image.pixels());
+
+        // Re-encoding a decoded image must not drift.
+        let redecoded = Image::from_bytes(&decoded.encode_png()).unwrap();
+        assert_eq!(redecoded.pixels(), decoded.pixels());
+    }
+
+    #[test]
+    fn test_premultiply_round_trip() {
+        #[rustfmt::skip]
+        let image = Image::from_pixel_vec(vec![
+            64, 32, 0, 128,     // half alpha
+            255, 0, 0, 128,     // invalid, color exceeds alpha
+            7, 7, 7, 0,         // transparent
+            10, 20, 30, 255,    // opaque
+        ], (4, 1));
+
+        let straight = image.unpremultiplied();
+        #[rustfmt::skip]
+        assert_eq!(straight.pixels(), [
+            128, 64, 0, 128,
+            255, 0, 0, 128,
+            0, 0, 0, 0,
+            10, 20, 30, 255,
+        ]);
+
+        // This is what loading straight-alpha data does.
+        let mut pixels = straight.pixels().to_vec();
+        premultiply(&mut pixels);
+        #[rustfmt::skip]
+        assert_eq!(pixels, [
+            64, 32, 0, 128,
+            128, 0, 0, 128,
+            0, 0, 0, 0,
+            10, 20, 30, 255,
+        ]);
+    }
+
+    #[test]
+    fn test_flip_vertical() {
+        let mut image = Image::from_pixel_vec(vec![1, 2, 3, 4, 5, 6, 7, 8], (1, 2));
+        image.flip_vertical();
+        assert_eq!(image.dimensions(), (1, 2));
+        assert_eq!(image.pixels(), [5, 6, 7, 8, 1, 2, 3, 4]);
+
+        // The middle row of an odd-height image stays put.
+        let mut image = Image::from_pixel_vec((0..12).collect(), (1, 3));
+        image.flip_vertical();
+        assert_eq!(image.pixels(), [8, 9, 10, 11, 4, 5, 6, 7, 0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_from_gray() {
+        // 2x2 image with a byte of padding at the end of each row
+        let image = Image::from_gray(&[0, 255, 99, 128, 64, 99], (2, 2), 3);
+        assert_eq!(grays(&image), [0, 255, 128, 64]);
+        assert_eq!(image.get_pixel((1, 0)), Some((1.0, 1.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn test_from_indexed() {
+        let palette = [[255, 0, 0], [0, 0, 255], [10, 20, 30]];
+        let image = Image::from_indexed(&[2, 0, 1, 7], &palette, (2, 2), 2);
+        #[rustfmt::skip]
+        let expected = [
+            10, 20, 30, 255,    255, 0, 0, 255,
+            0, 0, 255, 255,     0, 0, 0, 255,
+        ];
+        assert_eq!(image.pixels(), expected);
+    }
+
+    #[test]
+    fn test_from_raw() {
+        // One row of two pixels, plus padding
+        let bgra = [30, 20, 10, 255, 0, 0, 100, 128, 99, 99];
+        let image = Image::from_raw(
+            &bgra,
+            (2, 1),
+            10,
+            ChannelOrder::Bgra,
+            AlphaMode::Premultiplied,
+        );
+        assert_eq!(image.pixels(), [10, 20, 30, 255, 100, 0, 0, 128]);
+
+        // Straight alpha is premultiplied, and unpremultiplying gets it back.
+        let argb = [128, 255, 0, 100];
+        let image = Image::from_raw(&argb, (1, 1), 4, ChannelOrder::Argb, AlphaMode::Straight);
+        assert_eq!(image.pixels(), [128, 0, 50, 128]);
+        assert_eq!(image.unpremultiplied().pixels(), [255, 0, 100, 128]);
+
+        let image = Image::from_raw(&argb, (1, 1), 4, ChannelOrder::Argb, AlphaMode::Ignored);
+        assert_eq!(image.pixels(), [255, 0, 100, 255]);
+    }
+
+    /// Gray values of an opaque gray image, for easier comparisons.
+    fn grays(image: &Image) -> Vec<u8> {
+        image
+            .pixels()
+            .chunks_exact(4)
+            .map(|pixel| {
+                assert!(pixel[0] == pixel[1] && pixel[1] == pixel[2] && pixel[3] == 255);
+                pixel[0]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_scaled_to() {
+        #[rustfmt::skip]
+        let checkerboard = Image::from_pixel_vec(vec![
+            255, 255, 255, 255,  0, 0, 0, 255,
+            0, 0, 0, 255,        255, 255, 255, 255,
+        ], (2, 2));
+
+        let nearest = checkerboard.scaled_to(4, 4, ScalingMode::NearestNeighbor);
+        assert_eq!(nearest.dimensions(), (4, 4));
+        #[rustfmt::skip]
+        assert_eq!(grays(&nearest), [
+            255, 255, 0, 0,
+            255, 255, 0, 0,
+            0, 0, 255, 255,
+            0, 0, 255, 255,
+        ]);
+
+        // The source pixel centers land at 0.25 and 0.75 of the way between
+        // destination pixels 1 and 2, and the edges are clamped.
+        let bilinear = checkerboard.scaled_to(4, 4, ScalingMode::Bilinear);
+        assert_eq!(bilinear.dimensions(), (4, 4));
+        #[rustfmt::skip]
+        assert_eq!(grays(&bilinear), [
+            255, 191, 64, 0,
+            191, 159, 96, 64,
+            64, 96, 159, 191,
+            0, 64, 191, 255,
+        ]);
+    }
+}
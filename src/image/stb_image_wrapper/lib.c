@@ -7,3 +7,7 @@
 #define STB_ONLY_PNG
 #define STB_NO_STDIO
 #include "../../../vendor/stb/stb_image.h"
+
+#define STB_IMAGE_WRITE_IMPLEMENTATION
+#define STBI_WRITE_NO_STDIO
+#include "../../../vendor/stb/stb_image_write.h"
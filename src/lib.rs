@@ -54,7 +54,7 @@ mod window;
 // re-exported to avoid having to update lots of imports.
 // Unlike its siblings, this module should be considered private and only used
 // via re-exports.
-use environment::{Environment, ThreadID};
+use environment::{Environment, ThreadBlock, ThreadID};
 
 use std::ffi::OsStr;
 use std::path::PathBuf;
@@ -0,0 +1,225 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Condition variables.
+
+use super::mutex::{pthread_mutex_lock, pthread_mutex_t, pthread_mutex_unlock};
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::errno::ETIMEDOUT;
+use crate::libc::time::timespec;
+use crate::mem::{ConstPtr, ConstVoidPtr, MutPtr, SafeRead};
+use crate::{Environment, ThreadBlock, ThreadID};
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant, SystemTime};
+
+#[derive(Default)]
+pub struct State {
+    conds: HashMap<MutPtr<pthread_cond_t>, CondHostObject>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.libc_state.pthread.cond
+    }
+}
+
+/// Apple's implementation is a 4-byte magic number followed by a 24-byte opaque
+/// region. We will store the actual data on the host instead.
+#[repr(C, packed)]
+pub struct pthread_cond_t {
+    /// Magic number (must be [MAGIC_COND])
+    magic: u32,
+}
+unsafe impl SafeRead for pthread_cond_t {}
+
+#[derive(Default)]
+struct CondHostObject {
+    /// Threads blocked in `pthread_cond_wait` or `pthread_cond_timedwait`, in
+    /// the order they started waiting.
+    waiting: VecDeque<ThreadID>,
+}
+impl CondHostObject {
+    /// Remove the thread that should be woken by `pthread_cond_signal`.
+    fn signal(&mut self) -> Option<ThreadID> {
+        self.waiting.pop_front()
+    }
+
+    /// Remove all the threads that should be woken by
+    /// `pthread_cond_broadcast`.
+    fn broadcast(&mut self) -> Vec<ThreadID> {
+        self.waiting.drain(..).collect()
+    }
+
+    /// Called by a thread once it is awake. Returns [true] if it was still
+    /// waiting, i.e. it wasn't signalled, so the wait must have timed out.
+    fn stop_waiting(&mut self, thread: ThreadID) -> bool {
+        let Some(idx) = self.waiting.iter().position(|&t| t == thread) else {
+            return false;
+        };
+        self.waiting.remove(idx);
+        true
+    }
+}
+
+/// Arbitrarily-chosen magic number for `pthread_cond_t` (not Apple's).
+const MAGIC_COND: u32 = u32::from_be_bytes(*b"COND");
+/// Magic number used by `PTHREAD_COND_INITIALIZER`. This is part of the ABI!
+const MAGIC_COND_STATIC: u32 = 0x3CB0B1BB;
+
+fn pthread_cond_init(
+    env: &mut Environment,
+    cond: MutPtr<pthread_cond_t>,
+    attr: ConstVoidPtr, // const pthread_condattr_t *
+) -> i32 {
+    if !attr.is_null() {
+        // The only attribute is process-sharing, which doesn't matter since
+        // we don't have multiple processes.
+        log_dbg!("Ignoring attributes for condition variable {:?}", cond);
+    }
+    env.mem.write(cond, pthread_cond_t { magic: MAGIC_COND });
+
+    assert!(!State::get(env).conds.contains_key(&cond));
+    State::get(env).conds.insert(cond, Default::default());
+    0 // success
+}
+
+fn check_or_register_cond(env: &mut Environment, cond: MutPtr<pthread_cond_t>) {
+    let magic: u32 = env.mem.read(cond.cast());
+    // This is a statically-initialized condition variable, we need to register
+    // it, and change the magic number in the process.
+    if magic == MAGIC_COND_STATIC {
+        log_dbg!(
+            "Detected statically-initialized condition variable at {:?}, registering.",
+            cond
+        );
+        pthread_cond_init(env, cond, ConstPtr::null());
+    } else {
+        // As with mutexes, a mismatch almost certainly indicates memory
+        // corruption, so panicking is more useful than returning EINVAL.
+        assert_eq!(magic, MAGIC_COND);
+    }
+}
+
+fn pthread_cond_destroy(env: &mut Environment, cond: MutPtr<pthread_cond_t>) -> i32 {
+    check_or_register_cond(env, cond);
+    let host_object = State::get(env).conds.remove(&cond).unwrap();
+    assert!(host_object.waiting.is_empty()); // should be EBUSY
+    env.mem.write(cond, pthread_cond_t { magic: 0 });
+    0 // success
+}
+
+/// Shared implementation of `pthread_cond_wait` and `pthread_cond_timedwait`.
+fn wait(
+    env: &mut Environment,
+    cond: MutPtr<pthread_cond_t>,
+    mutex: MutPtr<pthread_mutex_t>,
+    timeout: Option<Instant>,
+) -> i32 {
+    check_or_register_cond(env, cond);
+
+    // Unlocking the mutex and starting to wait must be atomic, which is easy
+    // since we don't switch threads in between.
+    let res = pthread_mutex_unlock(env, mutex);
+    assert!(res == 0); // mutex must be locked by this thread
+
+    let current_thread = env.current_thread;
+    let host_object = State::get(env).conds.get_mut(&cond).unwrap();
+    host_object.waiting.push_back(current_thread);
+    env.block(ThreadBlock::Condition(cond), timeout);
+
+    let timed_out = State::get(env)
+        .conds
+        .get_mut(&cond)
+        .unwrap()
+        .stop_waiting(current_thread);
+
+    // The mutex is reacquired even if the wait timed out.
+    pthread_mutex_lock(env, mutex);
+
+    if timed_out {
+        ETIMEDOUT
+    } else {
+        0 // success
+    }
+}
+
+fn pthread_cond_wait(
+    env: &mut Environment,
+    cond: MutPtr<pthread_cond_t>,
+    mutex: MutPtr<pthread_mutex_t>,
+) -> i32 {
+    wait(env, cond, mutex, None)
+}
+
+fn pthread_cond_timedwait(
+    env: &mut Environment,
+    cond: MutPtr<pthread_cond_t>,
+    mutex: MutPtr<pthread_mutex_t>,
+    abstime: ConstPtr<timespec>,
+) -> i32 {
+    // The timeout is an absolute time on the system clock, but the scheduler
+    // uses the monotonic clock.
+    let timespec { tv_sec, tv_nsec } = env.mem.read(abstime);
+    assert!((0..1_000_000_000).contains(&tv_nsec)); // should be EINVAL
+    let since_epoch = Duration::new(tv_sec.max(0) as u64, tv_nsec as u32);
+    let abstime = SystemTime::UNIX_EPOCH + since_epoch;
+    let duration = abstime
+        .duration_since(SystemTime::now())
+        .unwrap_or(Duration::ZERO);
+    wait(env, cond, mutex, Some(Instant::now() + duration))
+}
+
+fn pthread_cond_signal(env: &mut Environment, cond: MutPtr<pthread_cond_t>) -> i32 {
+    check_or_register_cond(env, cond);
+    if let Some(thread) = State::get(env).conds.get_mut(&cond).unwrap().signal() {
+        env.unblock(thread, ThreadBlock::Condition(cond));
+    }
+    0 // success
+}
+
+fn pthread_cond_broadcast(env: &mut Environment, cond: MutPtr<pthread_cond_t>) -> i32 {
+    check_or_register_cond(env, cond);
+    for thread in State::get(env).conds.get_mut(&cond).unwrap().broadcast() {
+        env.unblock(thread, ThreadBlock::Condition(cond));
+    }
+    0 // success
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(pthread_cond_init(_, _)),
+    export_c_func!(pthread_cond_destroy(_)),
+    export_c_func!(pthread_cond_wait(_, _)),
+    export_c_func!(pthread_cond_timedwait(_, _, _)),
+    export_c_func!(pthread_cond_signal(_)),
+    export_c_func!(pthread_cond_broadcast(_)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::CondHostObject;
+
+    #[test]
+    fn test_signal_wakes_in_order() {
+        let mut cond = CondHostObject::default();
+        assert_eq!(cond.signal(), None);
+        cond.waiting.push_back(1);
+        cond.waiting.push_back(2);
+        assert_eq!(cond.signal(), Some(1));
+        // The signalled thread is no longer waiting, so didn't time out.
+        assert!(!cond.stop_waiting(1));
+        assert_eq!(cond.signal(), Some(2));
+        assert_eq!(cond.signal(), None);
+    }
+
+    #[test]
+    fn test_broadcast_and_timeout() {
+        let mut cond = CondHostObject::default();
+        cond.waiting.extend([1, 2, 3]);
+        // Thread 2's wait timed out before the broadcast.
+        assert!(cond.stop_waiting(2));
+        assert_eq!(cond.broadcast(), [1, 3]);
+        assert!(cond.waiting.is_empty());
+        assert!(!cond.stop_waiting(1));
+    }
+}
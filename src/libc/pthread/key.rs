@@ -5,7 +5,7 @@
  */
 //! Thread-specific data keys.
 
-use crate::abi::GuestFunction;
+use crate::abi::{CallFromHost, GuestFunction};
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::mem::{ConstVoidPtr, MutPtr, MutVoidPtr, Ptr};
 use crate::{Environment, ThreadID};
@@ -19,6 +19,43 @@ pub struct State {
     keys: Vec<(HashMap<ThreadID, MutVoidPtr>, GuestFunction)>,
 }
 
+impl State {
+    fn get_specific(&self, key: pthread_key_t, thread: ThreadID) -> MutVoidPtr {
+        // Use of invalid key is undefined, panicking is fine.
+        let idx: usize = key.checked_sub(1).unwrap().try_into().unwrap();
+        self.keys[idx]
+            .0
+            .get(&thread)
+            .copied()
+            .unwrap_or(Ptr::null())
+    }
+
+    fn set_specific(&mut self, key: pthread_key_t, thread: ThreadID, value: MutVoidPtr) {
+        // TODO: return error instead of panicking if key is invalid?
+        let idx: usize = key.checked_sub(1).unwrap().try_into().unwrap();
+        if value.is_null() {
+            self.keys[idx].0.remove(&thread);
+        } else {
+            self.keys[idx].0.insert(thread, value);
+        }
+    }
+
+    /// Clear all of a thread's values, returning the destructor calls that
+    /// need to be made for them.
+    fn take_destructor_calls(&mut self, thread: ThreadID) -> Vec<(GuestFunction, MutVoidPtr)> {
+        let mut calls = Vec::new();
+        for (values, destructor) in self.keys.iter_mut() {
+            let Some(value) = values.remove(&thread) else {
+                continue;
+            };
+            if destructor.addr_with_thumb_bit() != 0 {
+                calls.push((*destructor, value));
+            }
+        }
+        calls
+    }
+}
+
 fn get_state(env: &mut Environment) -> &mut State {
     &mut env.libc_state.pthread.key
 }
@@ -38,28 +75,74 @@ fn pthread_key_create(
 }
 
 fn pthread_getspecific(env: &mut Environment, key: pthread_key_t) -> MutVoidPtr {
-    // Use of invalid key is undefined, panicking is fine.
-    let idx: usize = key.checked_sub(1).unwrap().try_into().unwrap();
     let current_thread = env.current_thread;
-    get_state(env).keys[idx]
-        .0
-        .get(&current_thread)
-        .copied()
-        .unwrap_or(Ptr::null())
+    get_state(env).get_specific(key, current_thread)
 }
 
 fn pthread_setspecific(env: &mut Environment, key: pthread_key_t, value: ConstVoidPtr) -> i32 {
-    // TODO: return error instead of panicking if key is invalid?
-    let idx: usize = key.checked_sub(1).unwrap().try_into().unwrap();
     let current_thread = env.current_thread;
-    get_state(env).keys[idx]
-        .0
-        .insert(current_thread, value.cast_mut());
+    get_state(env).set_specific(key, current_thread, value.cast_mut());
     0 // success
 }
 
+/// Called when a thread is about to exit (while it can still run guest code),
+/// to call the destructors for its thread-specific data.
+pub fn thread_exiting(env: &mut Environment, thread: ThreadID) {
+    // Destructors can set new values, in which case they need to be destroyed
+    // too. POSIX only requires a limited number of attempts.
+    const PTHREAD_DESTRUCTOR_ITERATIONS: usize = 4;
+    for _ in 0..PTHREAD_DESTRUCTOR_ITERATIONS {
+        let calls = get_state(env).take_destructor_calls(thread);
+        if calls.is_empty() {
+            return;
+        }
+        for (destructor, value) in calls {
+            log_dbg!(
+                "Calling thread-specific data destructor {:?} with {:?} for thread {}",
+                destructor,
+                value,
+                thread
+            );
+            () = destructor.call_from_host(env, (value,));
+        }
+    }
+    // Whatever is left is leaked, like on other systems.
+    get_state(env).take_destructor_calls(thread);
+}
+
 pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(pthread_key_create(_, _)),
     export_c_func!(pthread_getspecific(_)),
     export_c_func!(pthread_setspecific(_, _)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::State;
+    use crate::abi::GuestFunction;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_values_are_per_thread() {
+        let mut state = State::default();
+        let destructor = GuestFunction::from_addr_with_thumb_bit(0x2001);
+        state.keys.push((Default::default(), destructor));
+        let key = 1;
+
+        state.set_specific(key, 0, Ptr::from_bits(0x1000));
+        state.set_specific(key, 1, Ptr::from_bits(0x1100));
+        assert_eq!(state.get_specific(key, 0), Ptr::from_bits(0x1000));
+        assert_eq!(state.get_specific(key, 1), Ptr::from_bits(0x1100));
+        assert!(state.get_specific(key, 2).is_null());
+
+        // Only the exiting thread's non-null values need destroying.
+        state.set_specific(key, 2, Ptr::null());
+        let calls = state.take_destructor_calls(1);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0.addr_with_thumb_bit(), 0x2001);
+        assert_eq!(calls[0].1, Ptr::from_bits(0x1100));
+        assert!(state.get_specific(key, 1).is_null());
+        assert_eq!(state.get_specific(key, 0), Ptr::from_bits(0x1000));
+        assert!(state.take_destructor_calls(2).is_empty());
+    }
+}
@@ -8,8 +8,8 @@
 use crate::dyld::{export_c_func, FunctionExports};
 use crate::libc::errno::{EDEADLK, EPERM};
 use crate::mem::{ConstPtr, MutPtr, Ptr, SafeRead};
-use crate::{Environment, ThreadID};
-use std::collections::HashMap;
+use crate::{Environment, ThreadBlock, ThreadID};
+use std::collections::{HashMap, VecDeque};
 use std::num::NonZeroU32;
 
 #[derive(Default)]
@@ -37,7 +37,7 @@ unsafe impl SafeRead for pthread_mutexattr_t {}
 /// Apple's implementation is a 4-byte magic number followed by a 56-byte opaque
 /// region. We will store the actual data on the host instead.
 #[repr(C, packed)]
-struct pthread_mutex_t {
+pub struct pthread_mutex_t {
     /// Magic number (must be [MAGIC_MUTEX])
     magic: u32,
 }
@@ -48,6 +48,9 @@ struct MutexHostObject {
     /// The `NonZeroU32` is the number of locks on this thread (if it's a
     /// recursive mutex).
     locked: Option<(ThreadID, NonZeroU32)>,
+    /// Threads blocked in `pthread_mutex_lock`, in the order they started
+    /// waiting.
+    waiting: VecDeque<ThreadID>,
 }
 
 /// Arbitrarily-chosen magic number for `pthread_mutexattr_t` (not Apple's).
@@ -129,6 +132,7 @@ fn pthread_mutex_init(
         MutexHostObject {
             type_,
             locked: None,
+            waiting: VecDeque::new(),
         },
     );
 
@@ -153,7 +157,7 @@ fn check_or_register_mutex(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>
     }
 }
 
-fn pthread_mutex_lock(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) -> i32 {
+pub(super) fn pthread_mutex_lock(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) -> i32 {
     check_or_register_mutex(env, mutex);
 
     let current_thread = env.current_thread;
@@ -191,16 +195,20 @@ fn pthread_mutex_lock(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) ->
         }
     }
 
-    // TODO: wait for unlock on other thread
-    unimplemented!(
-        "Attempted to lock mutex {:?} for thread {}, already locked by thread {}",
-        mutex,
+    log_dbg!(
+        "Thread {} is waiting for mutex {:?}, locked by thread {}.",
         current_thread,
+        mutex,
         locking_thread,
-    )
+    );
+    host_object.waiting.push_back(current_thread);
+    // pthread_mutex_unlock() will hand over the lock directly when waking this
+    // thread, so there's nothing to do afterwards.
+    env.block(ThreadBlock::Mutex(mutex), None);
+    0 // success
 }
 
-fn pthread_mutex_unlock(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) -> i32 {
+pub(super) fn pthread_mutex_unlock(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) -> i32 {
     check_or_register_mutex(env, mutex);
 
     let current_thread = env.current_thread;
@@ -248,7 +256,13 @@ fn pthread_mutex_unlock(env: &mut Environment, mutex: MutPtr<pthread_mutex_t>) -
 
     if lock_count.get() == 1 {
         log_dbg!("Unlocked mutex {:?} for thread {}.", mutex, current_thread);
-        host_object.locked = None;
+        if let Some(next_thread) = host_object.waiting.pop_front() {
+            log_dbg!("Locked mutex {:?} for thread {}.", mutex, next_thread);
+            host_object.locked = Some((next_thread, NonZeroU32::new(1).unwrap()));
+            env.unblock(next_thread, ThreadBlock::Mutex(mutex));
+        } else {
+            host_object.locked = None;
+        }
     } else {
         assert!(host_object.type_ == PTHREAD_MUTEX_RECURSIVE);
         log_dbg!(
@@ -7,8 +7,10 @@
 
 use crate::abi::GuestFunction;
 use crate::dyld::{export_c_func, FunctionExports};
+use crate::frameworks::foundation::ns_thread;
+use crate::libc::errno::{EDEADLK, EINVAL, ESRCH};
 use crate::mem::{ConstPtr, MutPtr, MutVoidPtr, SafeRead};
-use crate::{Environment, ThreadID};
+use crate::{Environment, ThreadBlock, ThreadID};
 use std::collections::HashMap;
 
 #[derive(Default)]
@@ -54,6 +56,64 @@ type pthread_t = MutPtr<OpaqueThread>;
 struct ThreadHostObject {
     thread_id: ThreadID,
     _attr: pthread_attr_t,
+    join_state: JoinState,
+    /// Set once the thread has finished.
+    return_value: Option<MutVoidPtr>,
+}
+impl ThreadHostObject {
+    fn new(thread_id: ThreadID, attr: pthread_attr_t) -> Self {
+        let detachstate = attr.detachstate;
+        let join_state = if detachstate == PTHREAD_CREATE_DETACHED {
+            JoinState::Detached
+        } else {
+            JoinState::Joinable
+        };
+        ThreadHostObject {
+            thread_id,
+            _attr: attr,
+            join_state,
+            return_value: None,
+        }
+    }
+
+    /// Host part of `pthread_join`. Returns the thread's return value if it
+    /// has already finished, otherwise the joining thread must wait for
+    /// [Self::finish].
+    fn start_join(&mut self, joining_thread: ThreadID) -> Result<Option<MutVoidPtr>, i32> {
+        if self.join_state != JoinState::Joinable {
+            return Err(EINVAL);
+        }
+        if let Some(return_value) = self.return_value {
+            self.join_state = JoinState::Joined;
+            Ok(Some(return_value))
+        } else {
+            self.join_state = JoinState::BeingJoined(joining_thread);
+            Ok(None)
+        }
+    }
+
+    /// Record that the thread has finished. Returns the thread waiting to join
+    /// it, if there is one, so that it can be woken up.
+    fn finish(&mut self, return_value: MutVoidPtr) -> Option<ThreadID> {
+        assert!(self.return_value.is_none());
+        self.return_value = Some(return_value);
+        if let JoinState::BeingJoined(joining_thread) = self.join_state {
+            self.join_state = JoinState::Joined;
+            Some(joining_thread)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum JoinState {
+    Joinable,
+    Detached,
+    /// A thread is blocked in `pthread_join` waiting for this one.
+    BeingJoined(ThreadID),
+    /// `pthread_join` has returned, so this can't be joined again.
+    Joined,
 }
 
 /// Arbitrarily-chosen magic number for `pthread_attr_t` (not Apple's).
@@ -117,13 +177,9 @@ fn pthread_create(
     env.mem.write(thread, opaque);
 
     assert!(!State::get(env).threads.contains_key(&opaque));
-    State::get(env).threads.insert(
-        opaque,
-        ThreadHostObject {
-            thread_id,
-            _attr: attr,
-        },
-    );
+    State::get(env)
+        .threads
+        .insert(opaque, ThreadHostObject::new(thread_id, attr));
 
     log_dbg!("pthread_create({:?}, {:?}, {:?}, {:?}) => 0 (success), created new pthread_t {:?} (thread ID: {})", thread, attr, start_routine, user_data, opaque, thread_id);
 
@@ -143,13 +199,9 @@ fn pthread_self(env: &mut Environment) -> pthread_t {
         });
 
         assert!(!State::get(env).threads.contains_key(&opaque));
-        State::get(env).threads.insert(
-            opaque,
-            ThreadHostObject {
-                thread_id: 0,
-                _attr: DEFAULT_ATTR,
-            },
-        );
+        State::get(env)
+            .threads
+            .insert(opaque, ThreadHostObject::new(0, DEFAULT_ATTR));
         log_dbg!(
             "pthread_self: created pthread object {:?} for main thread",
             opaque
@@ -164,6 +216,70 @@ fn pthread_self(env: &mut Environment) -> pthread_t {
     ptr
 }
 
+fn pthread_join(env: &mut Environment, thread: pthread_t, value_ptr: MutPtr<MutVoidPtr>) -> i32 {
+    let current_thread = env.current_thread;
+    let Some(host_object) = State::get(env).threads.get_mut(&thread) else {
+        return ESRCH;
+    };
+    let thread_id = host_object.thread_id;
+    if thread_id == current_thread {
+        return EDEADLK;
+    }
+    let return_value = match host_object.start_join(current_thread) {
+        Ok(Some(return_value)) => return_value,
+        Ok(None) => {
+            log_dbg!(
+                "Thread {} is waiting to join thread {}.",
+                current_thread,
+                thread_id
+            );
+            env.block(ThreadBlock::Join(thread_id), None);
+            State::get(env).threads[&thread].return_value.unwrap()
+        }
+        Err(err) => return err,
+    };
+    // The thread has finished and has now been joined, so nothing can refer
+    // to it any more.
+    remove_thread(env, thread);
+    log_dbg!(
+        "pthread_join({:?}, {:?}) => 0, return value {:?}",
+        thread,
+        value_ptr,
+        return_value
+    );
+    if !value_ptr.is_null() {
+        env.mem.write(value_ptr, return_value);
+    }
+    0 // success
+}
+
+/// Called when a thread finishes (while it can still run guest code), to wake
+/// up any thread that is joining it and to release its `NSThread` object.
+pub fn thread_exited(env: &mut Environment, thread: ThreadID, return_value: MutVoidPtr) {
+    ns_thread::thread_exited(env, thread);
+
+    // Threads not created by pthread_create() don't have an object.
+    let Some((&pthread, host_object)) = State::get(env)
+        .threads
+        .iter_mut()
+        .find(|(_, host_object)| host_object.thread_id == thread)
+    else {
+        return;
+    };
+    if let Some(joining_thread) = host_object.finish(return_value) {
+        env.unblock(joining_thread, ThreadBlock::Join(thread));
+    } else if host_object.join_state == JoinState::Detached {
+        // Nothing can join a detached thread, so it can be cleaned up now.
+        remove_thread(env, pthread);
+    }
+}
+
+/// Free a finished thread's `pthread_t` and host object.
+fn remove_thread(env: &mut Environment, thread: pthread_t) {
+    State::get(env).threads.remove(&thread).unwrap();
+    env.mem.free(thread.cast());
+}
+
 fn pthread_setcanceltype(_env: &mut Environment, _type: i32, _oldtype: MutPtr<i32>) -> i32 {
     // TODO
     0
@@ -184,6 +300,45 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(pthread_attr_destroy(_)),
     export_c_func!(pthread_create(_, _, _, _)),
     export_c_func!(pthread_self()),
+    export_c_func!(pthread_join(_, _)),
     export_c_func!(pthread_setcanceltype(_, _)),
     export_c_func!(pthread_mach_thread_np(_)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::{JoinState, ThreadHostObject, DEFAULT_ATTR, PTHREAD_CREATE_DETACHED};
+    use crate::libc::errno::EINVAL;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_join_before_finish() {
+        let sentinel = Ptr::from_bits(0xC0FFEE);
+        let mut worker = ThreadHostObject::new(1, DEFAULT_ATTR);
+        // The main thread has to wait.
+        assert_eq!(worker.start_join(0), Ok(None));
+        assert_eq!(worker.join_state, JoinState::BeingJoined(0));
+        assert_eq!(worker.finish(sentinel), Some(0));
+        assert_eq!(worker.return_value, Some(sentinel));
+        // Double join.
+        assert_eq!(worker.start_join(0), Err(EINVAL));
+    }
+
+    #[test]
+    fn test_join_after_finish() {
+        let sentinel = Ptr::from_bits(0xC0FFEE);
+        let mut worker = ThreadHostObject::new(1, DEFAULT_ATTR);
+        assert_eq!(worker.finish(sentinel), None);
+        assert_eq!(worker.start_join(0), Ok(Some(sentinel)));
+        assert_eq!(worker.start_join(2), Err(EINVAL));
+    }
+
+    #[test]
+    fn test_join_detached() {
+        let mut attr = DEFAULT_ATTR;
+        attr.detachstate = PTHREAD_CREATE_DETACHED;
+        let mut worker = ThreadHostObject::new(1, attr);
+        assert_eq!(worker.start_join(0), Err(EINVAL));
+        assert_eq!(worker.finish(Ptr::null()), None);
+    }
+}
@@ -0,0 +1,382 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! POSIX semaphores (`semaphore.h`).
+//!
+//! Like for pthread, this assumes the app uses the API correctly, and asserts
+//! rather than returning errors in most cases.
+
+use crate::abi::DotDotDot;
+use crate::dyld::{export_c_func, FunctionExports};
+use crate::libc::errno::{set_errno, EAGAIN, EOVERFLOW};
+use crate::libc::posix_io::{O_CREAT, O_EXCL};
+use crate::mem::{ConstPtr, MutPtr, Ptr};
+use crate::{Environment, ThreadBlock, ThreadID};
+use std::collections::{HashMap, VecDeque};
+
+#[derive(Default)]
+pub struct State {
+    semaphores: HashMap<MutPtr<sem_t>, SemaphoreHostObject>,
+    /// Named semaphores that haven't been unlinked yet. Unlinked semaphores
+    /// stay in `semaphores` until the last `sem_close`.
+    named_semaphores: HashMap<String, MutPtr<sem_t>>,
+}
+impl State {
+    fn get(env: &mut Environment) -> &mut Self {
+        &mut env.libc_state.semaphore
+    }
+
+    /// Host part of `sem_open`. If there is no semaphore with this name yet
+    /// and `oflag` permits creating one, `alloc` is used to get the memory for
+    /// it. Returns [None] if the semaphore can't be opened.
+    fn open_named(
+        &mut self,
+        name: &str,
+        oflag: i32,
+        value: i32,
+        alloc: impl FnOnce() -> MutPtr<sem_t>,
+    ) -> Option<MutPtr<sem_t>> {
+        if let Some(&sem) = self.named_semaphores.get(name) {
+            if oflag & (O_CREAT | O_EXCL) == (O_CREAT | O_EXCL) {
+                return None; // should be EEXIST
+            }
+            self.semaphores.get_mut(&sem).unwrap().open_count += 1;
+            return Some(sem);
+        }
+        if oflag & O_CREAT == 0 {
+            return None; // should be ENOENT
+        }
+        let sem = alloc();
+        let mut host_object = SemaphoreHostObject::new(value);
+        host_object.open_count = 1;
+        assert!(self.semaphores.insert(sem, host_object).is_none());
+        self.named_semaphores.insert(name.to_string(), sem);
+        Some(sem)
+    }
+
+    /// Host part of `sem_close`. Returns [true] if this was the last reference
+    /// to the semaphore, so that its memory should be freed.
+    fn close_named(&mut self, sem: MutPtr<sem_t>) -> bool {
+        let host_object = self.semaphores.get_mut(&sem).unwrap(); // should be EINVAL
+        assert!(host_object.open_count > 0); // should be EINVAL
+        host_object.open_count -= 1;
+        if host_object.open_count > 0 || self.named_semaphores.values().any(|&s| s == sem) {
+            return false;
+        }
+        self.semaphores.remove(&sem);
+        true
+    }
+
+    /// Host part of `sem_unlink`. Returns the semaphore if it was already
+    /// closed by everyone and its memory should be freed, otherwise it lives
+    /// on until the last `sem_close`.
+    fn unlink_named(&mut self, name: &str) -> Result<Option<MutPtr<sem_t>>, ()> {
+        let Some(sem) = self.named_semaphores.remove(name) else {
+            return Err(()); // should be ENOENT
+        };
+        if self.semaphores[&sem].open_count > 0 {
+            return Ok(None);
+        }
+        self.semaphores.remove(&sem);
+        Ok(Some(sem))
+    }
+}
+
+/// Maximum value of a semaphore. This is the value iOS uses.
+pub const SEM_VALUE_MAX: i32 = 32767;
+
+/// Apple's definition is just an `int`. We store the actual data on the host,
+/// keyed by the address.
+#[allow(non_camel_case_types)]
+pub type sem_t = i32;
+
+struct SemaphoreHostObject {
+    value: i32,
+    /// Threads blocked in `sem_wait`, in the order they started waiting.
+    waiting: VecDeque<ThreadID>,
+    /// Number of `sem_open` calls not yet balanced by `sem_close`. Always zero
+    /// for unnamed semaphores.
+    open_count: u32,
+}
+impl SemaphoreHostObject {
+    fn new(value: i32) -> SemaphoreHostObject {
+        SemaphoreHostObject {
+            value,
+            waiting: VecDeque::new(),
+            open_count: 0,
+        }
+    }
+
+    /// Decrement the value if that can be done without blocking, otherwise
+    /// fail with `EAGAIN`.
+    fn try_wait(&mut self) -> Result<(), i32> {
+        if self.value > 0 {
+            self.value -= 1;
+            Ok(())
+        } else {
+            Err(EAGAIN)
+        }
+    }
+
+    /// Decrement the value if that can be done without blocking, otherwise
+    /// add `thread` to the waiters and return [false], meaning it must block.
+    /// `thread` is the blocking thread's [ThreadID], i.e. the thread that
+    /// `[NSThread currentThread]` refers to on it.
+    fn wait(&mut self, thread: ThreadID) -> bool {
+        if self.try_wait().is_ok() {
+            return true;
+        }
+        self.waiting.push_back(thread);
+        false
+    }
+
+    /// Increment the value, unless there is a thread waiting, in which case
+    /// the value is passed directly to that thread and it is returned so that
+    /// it can be woken up. Fails with `EOVERFLOW` if the value would exceed
+    /// [SEM_VALUE_MAX].
+    fn post(&mut self) -> Result<Option<ThreadID>, i32> {
+        if let Some(thread) = self.waiting.pop_front() {
+            assert!(self.value == 0);
+            Ok(Some(thread))
+        } else if self.value == SEM_VALUE_MAX {
+            Err(EOVERFLOW)
+        } else {
+            self.value += 1;
+            Ok(None)
+        }
+    }
+}
+
+fn sem_init(env: &mut Environment, sem: MutPtr<sem_t>, pshared: i32, value: u32) -> i32 {
+    // We don't have multiple processes, so this shouldn't matter.
+    if pshared != 0 {
+        log!("TODO: sem_init() with pshared = {}", pshared);
+    }
+    assert!(value <= SEM_VALUE_MAX as u32); // should be EINVAL
+    let value = value as i32;
+
+    // The guest owns the memory, we just zero it so it doesn't look like
+    // something else.
+    env.mem.write(sem, 0);
+
+    assert!(!State::get(env).semaphores.contains_key(&sem));
+    State::get(env)
+        .semaphores
+        .insert(sem, SemaphoreHostObject::new(value));
+    log_dbg!("sem_init({:?}, {}, {}) => 0", sem, pshared, value);
+    0 // success
+}
+
+fn sem_destroy(env: &mut Environment, sem: MutPtr<sem_t>) -> i32 {
+    // The guest memory is owned by the guest, so it's not freed here.
+    let host_object = State::get(env).semaphores.remove(&sem).unwrap(); // should be EINVAL
+    assert!(host_object.waiting.is_empty()); // should be EBUSY
+    log_dbg!("sem_destroy({:?}) => 0", sem);
+    0 // success
+}
+
+/// `SEM_FAILED` is `(sem_t *)-1`.
+fn sem_failed() -> MutPtr<sem_t> {
+    Ptr::from_bits(u32::MAX)
+}
+
+fn sem_open(
+    env: &mut Environment,
+    name: ConstPtr<u8>,
+    oflag: i32,
+    args: DotDotDot,
+) -> MutPtr<sem_t> {
+    let name_str = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    assert!(oflag & !(O_CREAT | O_EXCL) == 0); // should be EINVAL
+    let value: u32 = if oflag & O_CREAT != 0 {
+        let mut args = args.start();
+        let _mode: u32 = args.next(env);
+        args.next(env)
+    } else {
+        0
+    };
+    assert!(value <= SEM_VALUE_MAX as u32); // should be EINVAL
+    let value = value as i32;
+
+    let mem = &mut env.mem;
+    let res = env
+        .libc_state
+        .semaphore
+        .open_named(&name_str, oflag, value, || mem.alloc_and_write(0));
+    let res = res.unwrap_or_else(sem_failed);
+    log_dbg!(
+        "sem_open({:?} {:?}, {:#x}, {}) => {:?}",
+        name,
+        name_str,
+        oflag,
+        value,
+        res
+    );
+    res
+}
+
+fn sem_close(env: &mut Environment, sem: MutPtr<sem_t>) -> i32 {
+    if State::get(env).close_named(sem) {
+        env.mem.free(sem.cast());
+    }
+    log_dbg!("sem_close({:?}) => 0", sem);
+    0 // success
+}
+
+fn sem_unlink(env: &mut Environment, name: ConstPtr<u8>) -> i32 {
+    let name_str = env.mem.cstr_at_utf8(name).unwrap().to_string();
+    let res = match State::get(env).unlink_named(&name_str) {
+        Ok(Some(sem)) => {
+            env.mem.free(sem.cast());
+            0
+        }
+        Ok(None) => 0,
+        Err(()) => -1,
+    };
+    log_dbg!("sem_unlink({:?} {:?}) => {}", name, name_str, res);
+    res
+}
+
+fn sem_post(env: &mut Environment, sem: MutPtr<sem_t>) -> i32 {
+    match State::get(env).semaphores.get_mut(&sem).unwrap().post() {
+        Ok(Some(thread)) => env.unblock(thread, ThreadBlock::Semaphore(sem)),
+        Ok(None) => (),
+        Err(errno) => {
+            log!("sem_post({:?}) failed, value is at SEM_VALUE_MAX", sem);
+            set_errno(env, errno);
+            return -1;
+        }
+    }
+    0 // success
+}
+
+fn sem_wait(env: &mut Environment, sem: MutPtr<sem_t>) -> i32 {
+    let current_thread = env.current_thread;
+    let host_object = State::get(env).semaphores.get_mut(&sem).unwrap();
+    if host_object.wait(current_thread) {
+        return 0; // success
+    }
+    // sem_post() will hand over the value directly when waking this thread,
+    // so there's nothing to decrement afterwards.
+    env.block(ThreadBlock::Semaphore(sem), None);
+    0 // success
+}
+
+fn sem_trywait(env: &mut Environment, sem: MutPtr<sem_t>) -> i32 {
+    match State::get(env).semaphores.get_mut(&sem).unwrap().try_wait() {
+        Ok(()) => 0, // success
+        Err(errno) => {
+            set_errno(env, errno);
+            -1
+        }
+    }
+}
+
+pub const FUNCTIONS: FunctionExports = &[
+    export_c_func!(sem_init(_, _, _)),
+    export_c_func!(sem_destroy(_)),
+    export_c_func!(sem_open(_, _, _)),
+    export_c_func!(sem_close(_)),
+    export_c_func!(sem_unlink(_)),
+    export_c_func!(sem_post(_)),
+    export_c_func!(sem_wait(_)),
+    export_c_func!(sem_trywait(_)),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{SemaphoreHostObject, State, SEM_VALUE_MAX};
+    use crate::libc::errno::{EAGAIN, EOVERFLOW};
+    use crate::libc::posix_io::{O_CREAT, O_EXCL};
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_produce_consume() {
+        let mut sem = SemaphoreHostObject::new(0);
+        assert_eq!(sem.try_wait(), Err(EAGAIN));
+        assert_eq!(sem.post(), Ok(None));
+        assert_eq!(sem.post(), Ok(None));
+        assert_eq!(sem.try_wait(), Ok(()));
+        assert_eq!(sem.try_wait(), Ok(()));
+        assert_eq!(sem.try_wait(), Err(EAGAIN));
+        assert_eq!(sem.value, 0);
+    }
+
+    #[test]
+    fn test_posts_accumulate() {
+        let mut sem = SemaphoreHostObject::new(0);
+        for _ in 0..3 {
+            assert_eq!(sem.post(), Ok(None));
+        }
+        assert_eq!(sem.value, 3);
+        for _ in 0..3 {
+            assert_eq!(sem.try_wait(), Ok(()));
+        }
+        assert_eq!(sem.try_wait(), Err(EAGAIN));
+    }
+
+    #[test]
+    fn test_post_wakes_one_waiter() {
+        let mut sem = SemaphoreHostObject::new(0);
+        sem.waiting.push_back(1);
+        sem.waiting.push_back(2);
+        assert_eq!(sem.post(), Ok(Some(1)));
+        assert_eq!(sem.value, 0);
+        assert_eq!(sem.post(), Ok(Some(2)));
+        assert_eq!(sem.post(), Ok(None));
+        assert_eq!(sem.value, 1);
+    }
+
+    #[test]
+    fn test_wait_records_thread() {
+        let mut sem = SemaphoreHostObject::new(1);
+        assert!(sem.wait(3));
+        assert!(sem.waiting.is_empty());
+        assert!(!sem.wait(3));
+        assert!(!sem.wait(5));
+        assert_eq!(sem.waiting, [3, 5]);
+        // The thread that gets woken is the one that waited first.
+        assert_eq!(sem.post(), Ok(Some(3)));
+        assert_eq!(sem.post(), Ok(Some(5)));
+    }
+
+    #[test]
+    fn test_post_overflow() {
+        let mut sem = SemaphoreHostObject::new(SEM_VALUE_MAX - 1);
+        assert_eq!(sem.post(), Ok(None));
+        assert_eq!(sem.value, SEM_VALUE_MAX);
+        assert_eq!(sem.post(), Err(EOVERFLOW));
+        assert_eq!(sem.value, SEM_VALUE_MAX);
+        // Once the value goes down, posting works again.
+        assert_eq!(sem.try_wait(), Ok(()));
+        assert_eq!(sem.post(), Ok(None));
+    }
+
+    #[test]
+    fn test_named_shared_by_name() {
+        let mut state = State::default();
+        let a = state
+            .open_named("/sem", O_CREAT, 0, || Ptr::from_bits(0x1000))
+            .unwrap();
+        let b = state.open_named("/sem", 0, 0, || unreachable!()).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(
+            state.open_named("/sem", O_CREAT | O_EXCL, 0, || unreachable!()),
+            None
+        );
+        assert_eq!(state.open_named("/other", 0, 0, || unreachable!()), None);
+
+        // A post on one is seen by a wait on the other.
+        assert_eq!(state.semaphores.get_mut(&a).unwrap().post(), Ok(None));
+        assert_eq!(state.semaphores.get_mut(&b).unwrap().try_wait(), Ok(()));
+
+        // Only freed once both unlinked and fully closed.
+        assert!(!state.close_named(a));
+        assert_eq!(state.unlink_named("/sem"), Ok(None));
+        assert_eq!(state.unlink_named("/sem"), Err(()));
+        assert!(state.close_named(b));
+        assert!(state.semaphores.is_empty());
+    }
+}
@@ -7,7 +7,7 @@
 
 use crate::abi::{DotDotDot, VaList};
 use crate::dyld::{export_c_func, FunctionExports};
-use crate::frameworks::foundation::ns_string;
+use crate::frameworks::foundation::{ns_string, ns_value};
 use crate::mem::{ConstPtr, GuestUSize, Mem, MutPtr};
 use crate::objc::{id, msg};
 use crate::Environment;
@@ -74,7 +74,7 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
         }
 
         if has_precision {
-            assert!(INTEGER_SPECIFIERS.contains(&specifier))
+            assert!(INTEGER_SPECIFIERS.contains(&specifier) || specifier == b'g')
         }
 
         match specifier {
@@ -122,8 +122,22 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
                     write!(&mut res, "{}", float).unwrap();
                 }
             }
+            b'g' => {
+                let float: f64 = args.next(env);
+                // C's default precision is 6. The precision is parsed as if it
+                // were a pad width.
+                let precision = if has_precision { pad_width } else { 6 };
+                assert!(has_precision || pad_width == 0); // TODO: width
+                res.extend_from_slice(format_g(float, precision).as_bytes());
+            }
             b'@' if NS_LOG => {
                 let object: id = args.next(env);
+                // Fast path: numbers are commonly formatted in hot loops, and
+                // there's no need to create a guest string for them.
+                if let Some(number) = ns_value::number_to_rust_string(env, object) {
+                    write!(&mut res, "{}", number).unwrap();
+                    continue;
+                }
                 // TODO: use localized description if available?
                 let description: id = msg![env; object description];
                 // TODO: avoid copy
@@ -145,6 +159,49 @@ pub fn printf_inner<const NS_LOG: bool, F: Fn(&Mem, GuestUSize) -> u8>(
     res
 }
 
+/// Format a number like C's `%.*g` does: with `precision` significant digits,
+/// in exponential notation if the exponent is less than -4 or not less than
+/// the precision, and without trailing zeros.
+pub fn format_g(value: f64, precision: usize) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value < 0.0 { "-inf" } else { "inf" }.to_string();
+    }
+    let precision = precision.max(1);
+
+    // Rounding to the precision can change the exponent (e.g. 9.99 -> 10), so
+    // it's taken from the rounded exponential form.
+    let exponential = format!("{:.*e}", precision - 1, value);
+    let (mantissa, exponent) = exponential.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+
+    let strip_zeros = |digits: &str| -> String {
+        if digits.contains('.') {
+            digits
+                .trim_end_matches('0')
+                .trim_end_matches('.')
+                .to_string()
+        } else {
+            digits.to_string()
+        }
+    };
+
+    if exponent < -4 || exponent >= precision as i32 {
+        let sign = if exponent < 0 { '-' } else { '+' };
+        format!(
+            "{}e{}{:02}",
+            strip_zeros(mantissa),
+            sign,
+            exponent.unsigned_abs()
+        )
+    } else {
+        let decimals = (precision as i32 - 1 - exponent) as usize;
+        strip_zeros(&format!("{:.*}", decimals, value))
+    }
+}
+
 fn vsnprintf(
     env: &mut Environment,
     dest: MutPtr<u8>,
@@ -294,3 +351,32 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(sprintf(_, _, _)),
     export_c_func!(printf(_, _)),
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::format_g;
+
+    #[test]
+    fn test_format_g() {
+        assert_eq!(format_g(0.0, 6), "0");
+        assert_eq!(format_g(-0.0, 6), "-0");
+        assert_eq!(format_g(1.5, 6), "1.5");
+        assert_eq!(format_g(100.0, 6), "100");
+        assert_eq!(format_g(123456.0, 6), "123456");
+        assert_eq!(format_g(1234567.0, 6), "1.23457e+06");
+        assert_eq!(format_g(999999.5, 6), "1e+06");
+        assert_eq!(format_g(0.0001, 6), "0.0001");
+        assert_eq!(format_g(0.00001, 6), "1e-05");
+        assert_eq!(format_g(1e300, 16), "1e+300");
+        assert_eq!(format_g(-2.5e-300, 16), "-2.5e-300");
+        assert_eq!(format_g(0.1 + 0.2, 16), "0.3");
+        assert_eq!(format_g(0.1 + 0.2, 17), "0.30000000000000004");
+        assert_eq!(format_g(1.0 / 3.0, 16), "0.3333333333333333");
+        assert_eq!(format_g(1e16, 16), "1e+16");
+        assert_eq!(format_g(1234567890123456.0, 16), "1234567890123456");
+        assert_eq!(format_g(0.5, 0), "0.5");
+        assert_eq!(format_g(f64::INFINITY, 16), "inf");
+        assert_eq!(format_g(f64::NEG_INFINITY, 16), "-inf");
+        assert_eq!(format_g(f64::NAN, 16), "nan");
+    }
+}
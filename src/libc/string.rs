@@ -64,7 +64,8 @@ fn strtok(env: &mut Environment, s: MutPtr<u8>, sep: ConstPtr<u8>) -> MutPtr<u8>
 // Functions shared with wchar.rs
 
 fn memset(env: &mut Environment, dest: MutVoidPtr, ch: i32, count: GuestUSize) -> MutVoidPtr {
-    GenericChar::<u8>::memset(env, dest.cast(), ch as u8, count).cast()
+    env.mem.memset(dest, ch as u8, count);
+    dest
 }
 fn memcpy(
     env: &mut Environment,
@@ -268,6 +268,16 @@ struct timeval {
 }
 unsafe impl SafeRead for timeval {}
 
+/// `struct timespec`, e.g. for absolute timeouts.
+#[allow(non_camel_case_types)]
+#[derive(Copy, Clone, Debug)]
+#[repr(C, packed)]
+pub struct timespec {
+    pub tv_sec: time_t,
+    pub tv_nsec: i32,
+}
+unsafe impl SafeRead for timespec {}
+
 #[allow(non_camel_case_types)]
 #[repr(C, packed)]
 struct timezone {
@@ -191,6 +191,47 @@ impl<T: SafeRead> SafeWrite for T {}
 
 type Bytes = [u8; 1 << 32];
 
+/// Reason a checked memory access (e.g. [Mem::checked_bytes_at_mut]) failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemError {
+    /// The access starts within the null page.
+    NullPage { addr: VAddr, count: GuestUSize },
+    /// The access starts in memory that isn't allocated or reserved.
+    Unallocated { addr: VAddr, count: GuestUSize },
+    /// The access starts within an allocation but runs past its end.
+    Overrun {
+        addr: VAddr,
+        count: GuestUSize,
+        allocation_base: VAddr,
+        allocation_size: GuestUSize,
+    },
+}
+
+impl std::fmt::Display for MemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            MemError::NullPage { addr, count } => {
+                write!(f, "null-page access at {:#x} ({:#x} bytes)", addr, count)
+            }
+            MemError::Unallocated { addr, count } => write!(
+                f,
+                "access to unallocated memory at {:#x} ({:#x} bytes)",
+                addr, count
+            ),
+            MemError::Overrun {
+                addr,
+                count,
+                allocation_base,
+                allocation_size,
+            } => write!(
+                f,
+                "access at {:#x} ({:#x} bytes) overruns allocation at {:#x} ({:#x} bytes)",
+                addr, count, allocation_base, allocation_size
+            ),
+        }
+    }
+}
+
 /// The type that owns the guest memory and provides accessors for it.
 pub struct Mem {
     /// This array is 4GiB in size so that it can cover the entire 32-bit
@@ -342,6 +383,36 @@ impl Mem {
         &mut self.bytes_mut()[ptr.to_bits() as usize..][..count as usize]
     }
 
+    /// Version of [Self::bytes_at_mut] for when `count` comes from the guest
+    /// and can't be trusted. Rather than panicking or silently touching other
+    /// memory, this returns an error if the range isn't entirely within a
+    /// single allocation (or reserved region, e.g. the stack or a binary's
+    /// segments).
+    pub fn checked_bytes_at_mut(
+        &mut self,
+        ptr: MutPtr<u8>,
+        count: GuestUSize,
+    ) -> Result<&mut [u8], MemError> {
+        let addr = ptr.to_bits();
+        if addr < Self::NULL_PAGE_SIZE {
+            return Err(MemError::NullPage { addr, count });
+        }
+        let Some((allocation_base, allocation_size)) =
+            self.allocator.find_allocation_containing(addr)
+        else {
+            return Err(MemError::Unallocated { addr, count });
+        };
+        if addr as u64 + count as u64 > allocation_base as u64 + allocation_size as u64 {
+            return Err(MemError::Overrun {
+                addr,
+                count,
+                allocation_base,
+                allocation_size,
+            });
+        }
+        Ok(self.bytes_at_mut(ptr, count))
+    }
+
     /// Get a pointer for reading an array of `count` elements of type `T`.
     /// Only use this for interfacing with unsafe C-like APIs.
     ///
@@ -406,13 +477,40 @@ impl Mem {
         unsafe { ptr.write_unaligned(value) }
     }
 
-    /// C-style `memmove`.
+    /// C-style `memmove`. This operates on the whole range at once, so it's
+    /// much faster than copying byte-by-byte, and overlapping ranges are
+    /// handled correctly.
+    ///
+    /// Like [Self::bytes_at], this will panic if either range is within the
+    /// null page or goes past the end of the address space. Unlike it, a size
+    /// of 0 is always allowed, since C code often passes `NULL` in that case.
     pub fn memmove(&mut self, dest: MutVoidPtr, src: ConstVoidPtr, size: GuestUSize) {
+        if size == 0 {
+            return;
+        }
+        for addr in [src.to_bits(), dest.to_bits()] {
+            if addr < Self::NULL_PAGE_SIZE {
+                Self::null_check_fail(addr, size)
+            }
+            assert!(addr.checked_add(size).is_some());
+        }
         let src = src.to_bits() as usize;
         let dest = dest.to_bits() as usize;
         let size = size as usize;
-        self.bytes_mut()
-            .copy_within(src..src.checked_add(size).unwrap(), dest)
+        self.bytes_mut().copy_within(src..src + size, dest)
+    }
+
+    /// C-style `memset`, operating on the whole range at once.
+    ///
+    /// Like [Self::bytes_at_mut], this will panic if the range is within the
+    /// null page or goes past the end of the address space. As with
+    /// [Self::memmove], a size of 0 is always allowed.
+    pub fn memset(&mut self, dest: MutVoidPtr, value: u8, size: GuestUSize) {
+        if size == 0 {
+            return;
+        }
+        assert!(dest.to_bits().checked_add(size).is_some());
+        self.bytes_at_mut(dest.cast(), size).fill(value)
     }
 
     /// Allocate `size` bytes.
@@ -436,6 +534,11 @@ impl Mem {
     }
 
     /// Free an allocation made with one of the `alloc` methods on this type.
+    ///
+    /// The freed memory is always zeroed, so that e.g. the contents of a
+    /// released `NSData`/`CFData` (which might be a password or key) don't
+    /// linger. Since the zeroing writes to memory the guest can observe, it
+    /// can't be optimized away.
     pub fn free(&mut self, ptr: MutVoidPtr) {
         if ptr.is_null() {
             return;
@@ -466,6 +569,16 @@ impl Mem {
         ptr
     }
 
+    /// Allocate memory for a byte slice and copy it there. Unlike
+    /// [Self::alloc_and_write_cstr], there is no special handling of null
+    /// bytes, so this is suitable for binary data.
+    pub fn alloc_and_write_bytes(&mut self, bytes: &[u8]) -> MutPtr<u8> {
+        let len = bytes.len().try_into().unwrap();
+        let ptr = self.alloc(len).cast();
+        self.bytes_at_mut(ptr, len).copy_from_slice(bytes);
+        ptr
+    }
+
     /// Get a C string (null-terminated) as a slice. The null terminator is not
     /// included in the slice.
     pub fn cstr_at<const MUT: bool>(&self, ptr: Ptr<u8, MUT>) -> &[u8] {
@@ -490,3 +603,111 @@ impl Mem {
         self.allocator.reserve(allocator::Chunk::new(base, size));
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Mem, MemError, Ptr};
+
+    #[test]
+    fn test_free_zeroes() {
+        let mut mem = Mem::new();
+        let ptr = mem.alloc(32);
+        mem.bytes_at_mut(ptr.cast(), 32).fill(0xA5);
+        mem.free(ptr);
+        assert!(mem.bytes_at(ptr.cast(), 32).iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_checked_bytes_at_mut() {
+        let mut mem = Mem::new();
+        let ptr = mem.alloc(16).cast();
+        assert_eq!(mem.checked_bytes_at_mut(ptr, 16).unwrap().len(), 16);
+        assert_eq!(mem.checked_bytes_at_mut(ptr + 4, 12).unwrap().len(), 12);
+
+        assert_eq!(
+            mem.checked_bytes_at_mut(ptr + 4, 13),
+            Err(MemError::Overrun {
+                addr: ptr.to_bits() + 4,
+                count: 13,
+                allocation_base: ptr.to_bits(),
+                allocation_size: 16,
+            })
+        );
+        assert_eq!(
+            mem.checked_bytes_at_mut(Ptr::from_bits(0x10), 1),
+            Err(MemError::NullPage {
+                addr: 0x10,
+                count: 1
+            })
+        );
+        mem.free(ptr.cast());
+        assert_eq!(
+            mem.checked_bytes_at_mut(ptr, 1),
+            Err(MemError::Unallocated {
+                addr: ptr.to_bits(),
+                count: 1
+            })
+        );
+    }
+
+    #[test]
+    fn test_alloc_and_write_bytes() {
+        let mut mem = Mem::new();
+        let data = b"binary\0data\0";
+        let ptr = mem.alloc_and_write_bytes(data);
+        assert_eq!(mem.bytes_at(ptr, data.len() as u32), data);
+        // Only the part before the first null byte is a C string.
+        assert_eq!(mem.cstr_at(ptr), b"binary");
+    }
+
+    #[test]
+    fn test_memmove_memset() {
+        const SIZE: u32 = 1024 * 1024;
+        let mut mem = Mem::new();
+        let src = mem.alloc(SIZE * 2);
+        let dest = mem.alloc(SIZE);
+        let pattern = |i: u32| (i % 251) as u8;
+        let has_pattern = |bytes: &[u8]| {
+            bytes
+                .iter()
+                .enumerate()
+                .all(|(i, &b)| b == pattern(i as u32))
+        };
+        for (i, byte) in mem.bytes_at_mut(src.cast(), SIZE).iter_mut().enumerate() {
+            *byte = pattern(i as u32);
+        }
+
+        mem.memmove(dest, src.cast_const(), SIZE);
+        assert!(has_pattern(mem.bytes_at(dest.cast(), SIZE)));
+
+        // Overlapping, moving forwards then back again.
+        mem.memmove(src + 1000, src.cast_const(), SIZE);
+        assert!(has_pattern(mem.bytes_at(src.cast::<u8>() + 1000, SIZE)));
+        mem.memmove(src, (src + 1000).cast_const(), SIZE);
+        assert!(has_pattern(mem.bytes_at(src.cast(), SIZE)));
+
+        mem.memset(dest + 1, 0xA5, SIZE - 2);
+        let bytes = mem.bytes_at(dest.cast(), SIZE);
+        assert_eq!(bytes[0], pattern(0));
+        assert!(bytes[1..SIZE as usize - 1].iter().all(|&b| b == 0xA5));
+        assert_eq!(bytes[SIZE as usize - 1], pattern(SIZE - 1));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_memmove_null_page() {
+        let mut mem = Mem::new();
+        let dest = mem.alloc(16);
+        mem.memmove(dest, Ptr::from_bits(0x10), 16);
+    }
+
+    #[test]
+    fn test_memmove_memset_zero_size() {
+        let mut mem = Mem::new();
+        let dest = mem.alloc(16);
+        // e.g. `memcpy(dest, NULL, 0)` and `memset(NULL, 0, 0)`
+        mem.memmove(dest, Ptr::null(), 0);
+        mem.memmove(Ptr::null(), dest.cast_const(), 0);
+        mem.memset(Ptr::null(), 0, 0);
+    }
+}
@@ -199,6 +199,15 @@ impl Allocator {
         chunk.size.get()
     }
 
+    /// Find the allocation (or reserved region) containing `addr`, returning
+    /// its base and size.
+    pub fn find_allocation_containing(&self, addr: VAddr) -> Option<(VAddr, GuestUSize)> {
+        self.used_chunks
+            .iter()
+            .find(|chunk| chunk.contains(addr))
+            .map(|chunk| (chunk.base, chunk.size.get()))
+    }
+
     /// Returns the size of the freed chunk so it can be zeroed if desired
     #[must_use]
     pub fn free(&mut self, base: VAddr) -> GuestUSize {
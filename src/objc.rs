@@ -22,6 +22,7 @@ use crate::dyld::{export_c_func, FunctionExports};
 
 use std::collections::HashMap;
 
+mod associations;
 mod classes;
 mod messages;
 mod methods;
@@ -29,6 +30,7 @@ mod objects;
 mod properties;
 mod selectors;
 
+pub use associations::remove_associated_objects;
 pub use classes::{objc_classes, Class, ClassExports, ClassTemplate};
 pub use messages::{
     autorelease, msg, msg_class, msg_send, msg_send_super2, msg_super, objc_super, release, retain,
@@ -37,6 +39,9 @@ pub use methods::{GuestIMP, HostIMP, IMP};
 pub use objects::{id, nil, AnyHostObject, HostObject, TrivialHostObject};
 pub use selectors::{selector, SEL};
 
+use associations::{
+    objc_getAssociatedObject, objc_removeAssociatedObjects, objc_setAssociatedObject, Associations,
+};
 use classes::{ClassHostObject, FakeClass, UnimplementedClass, CLASS_LISTS};
 use messages::{objc_msgSend, objc_msgSendSuper2, objc_msgSend_stret};
 use methods::method_list_t;
@@ -64,6 +69,9 @@ pub struct ObjC {
     ///
     /// Look at the `isa` to get the metaclass for a class.
     classes: HashMap<String, Class>,
+
+    /// Associated objects, see [associations].
+    associations: Associations,
 }
 
 impl ObjC {
@@ -72,6 +80,7 @@ impl ObjC {
             selectors: HashMap::new(),
             objects: HashMap::new(),
             classes: HashMap::new(),
+            associations: Associations::default(),
         }
     }
 }
@@ -82,4 +91,7 @@ pub const FUNCTIONS: FunctionExports = &[
     export_c_func!(objc_msgSendSuper2(_, _)),
     export_c_func!(objc_setProperty(_, _, _, _, _, _)),
     export_c_func!(objc_copyStruct(_, _, _, _, _)),
+    export_c_func!(objc_setAssociatedObject(_, _, _, _)),
+    export_c_func!(objc_getAssociatedObject(_, _)),
+    export_c_func!(objc_removeAssociatedObjects(_)),
 ];
@@ -0,0 +1,154 @@
+/*
+ * This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/.
+ */
+//! Associated objects (`objc_setAssociatedObject` and friends).
+//!
+//! Resources:
+//! - [Apple's documentation of `objc_setAssociatedObject`](https://developer.apple.com/documentation/objectivec/1418509-objc_setassociatedobject)
+
+use super::{id, msg, nil, release, retain};
+use crate::mem::{ConstVoidPtr, GuestUSize};
+use crate::Environment;
+use std::collections::HashMap;
+
+#[allow(non_camel_case_types)]
+pub type objc_AssociationPolicy = GuestUSize;
+pub const OBJC_ASSOCIATION_ASSIGN: objc_AssociationPolicy = 0;
+pub const OBJC_ASSOCIATION_RETAIN_NONATOMIC: objc_AssociationPolicy = 1;
+pub const OBJC_ASSOCIATION_COPY_NONATOMIC: objc_AssociationPolicy = 3;
+/// Added to the non-atomic policies to get `OBJC_ASSOCIATION_RETAIN` and
+/// `OBJC_ASSOCIATION_COPY`.
+const OBJC_ASSOCIATION_ATOMIC_BIT: objc_AssociationPolicy = 0o1400;
+
+struct Association {
+    value: id,
+    /// Whether the value was retained or copied, and must be released.
+    owned: bool,
+}
+
+/// Associated objects of every object that has any, keyed by object and then
+/// by the key pointer.
+#[derive(Default)]
+pub(super) struct Associations {
+    objects: HashMap<id, HashMap<ConstVoidPtr, Association>>,
+}
+
+impl Associations {
+    /// Set or (if `value` is `nil`) remove an association. Returns the old
+    /// value if it must be released.
+    fn set(&mut self, object: id, key: ConstVoidPtr, value: id, owned: bool) -> Option<id> {
+        let old = if value == nil {
+            let associations = self.objects.get_mut(&object)?;
+            let old = associations.remove(&key);
+            if associations.is_empty() {
+                self.objects.remove(&object);
+            }
+            old
+        } else {
+            self.objects
+                .entry(object)
+                .or_default()
+                .insert(key, Association { value, owned })
+        };
+        old.filter(|old| old.owned).map(|old| old.value)
+    }
+
+    fn get(&self, object: id, key: ConstVoidPtr) -> id {
+        self.objects
+            .get(&object)
+            .and_then(|associations| associations.get(&key))
+            .map_or(nil, |association| association.value)
+    }
+
+    /// Remove all of an object's associations. Returns the values that must
+    /// be released.
+    fn remove_all(&mut self, object: id) -> Vec<id> {
+        let Some(associations) = self.objects.remove(&object) else {
+            return Vec::new();
+        };
+        associations
+            .into_values()
+            .filter(|association| association.owned)
+            .map(|association| association.value)
+            .collect()
+    }
+}
+
+/// Remove all of an object's associated objects, releasing the ones that were
+/// retained or copied. This must be done when the object is deallocated,
+/// otherwise a new object at the same address would inherit them.
+pub fn remove_associated_objects(env: &mut Environment, object: id) {
+    for value in env.objc.associations.remove_all(object) {
+        release(env, value);
+    }
+}
+
+pub(super) fn objc_setAssociatedObject(
+    env: &mut Environment,
+    object: id,
+    key: ConstVoidPtr,
+    value: id,
+    policy: objc_AssociationPolicy,
+) {
+    // We don't have preemptive multithreading, so atomicity is free.
+    let value = match policy & !OBJC_ASSOCIATION_ATOMIC_BIT {
+        OBJC_ASSOCIATION_ASSIGN => value,
+        OBJC_ASSOCIATION_RETAIN_NONATOMIC => retain(env, value),
+        OBJC_ASSOCIATION_COPY_NONATOMIC => msg![env; value copy],
+        _ => panic!("Unknown association policy: {:#o}", policy),
+    };
+    let owned = policy != OBJC_ASSOCIATION_ASSIGN;
+    if let Some(old) = env.objc.associations.set(object, key, value, owned) {
+        release(env, old);
+    }
+}
+
+pub(super) fn objc_getAssociatedObject(env: &mut Environment, object: id, key: ConstVoidPtr) -> id {
+    env.objc.associations.get(object, key)
+}
+
+pub(super) fn objc_removeAssociatedObjects(env: &mut Environment, object: id) {
+    remove_associated_objects(env, object);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mem::Ptr;
+
+    #[test]
+    fn test_associations() {
+        let object: id = Ptr::from_bits(0x1000);
+        let other_object: id = Ptr::from_bits(0x2000);
+        let value: id = Ptr::from_bits(0x3000);
+        let other_value: id = Ptr::from_bits(0x4000);
+        let key: ConstVoidPtr = Ptr::from_bits(0x5000);
+        let other_key: ConstVoidPtr = Ptr::from_bits(0x6000);
+
+        let mut associations = Associations::default();
+        assert_eq!(associations.get(object, key), nil);
+        assert_eq!(associations.set(object, key, value, true), None);
+        assert_eq!(
+            associations.set(object, other_key, other_value, false),
+            None
+        );
+        assert_eq!(associations.get(object, key), value);
+        assert_eq!(associations.get(object, other_key), other_value);
+        assert_eq!(associations.get(other_object, key), nil);
+
+        // Replacing an owned value means it must be released, but not for an
+        // unowned one.
+        assert_eq!(
+            associations.set(object, key, other_value, true),
+            Some(value)
+        );
+        assert_eq!(associations.set(object, other_key, nil, true), None);
+        assert_eq!(associations.get(object, other_key), nil);
+
+        assert_eq!(associations.remove_all(object), vec![other_value]);
+        assert_eq!(associations.get(object, key), nil);
+        assert!(associations.objects.is_empty());
+    }
+}
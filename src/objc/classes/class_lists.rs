@@ -6,14 +6,19 @@
 //! Separate module just for the class lists, since this will probably be a
 //! very long and frequently-updated list.
 
-use crate::frameworks::{core_animation, core_graphics, foundation, media_player, opengles, uikit};
+use crate::frameworks::{
+    core_animation, core_foundation, core_graphics, foundation, media_player, opengles, uikit,
+};
 
 /// All the lists of classes that the runtime should search through.
 pub const CLASS_LISTS: &[super::ClassExports] = &[
     core_animation::ca_eagl_layer::CLASSES,
     core_animation::ca_layer::CLASSES,
+    core_foundation::cf_date_formatter::CLASSES,
+    core_foundation::cf_time_zone::CLASSES,
     core_graphics::cg_color_space::CLASSES,
     core_graphics::cg_context::CLASSES,
+    core_graphics::cg_data_provider::CLASSES,
     core_graphics::cg_image::CLASSES,
     foundation::ns_array::CLASSES,
     foundation::ns_autorelease_pool::CLASSES,
@@ -23,6 +28,7 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_data::CLASSES,
     foundation::ns_dictionary::CLASSES,
     foundation::ns_enumerator::CLASSES,
+    foundation::ns_error::CLASSES,
     foundation::ns_file_manager::CLASSES,
     foundation::ns_keyed_unarchiver::CLASSES,
     foundation::ns_locale::CLASSES,
@@ -37,6 +43,8 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     foundation::ns_thread::CLASSES,
     foundation::ns_timer::CLASSES,
     foundation::ns_url::CLASSES,
+    foundation::ns_url_request::CLASSES,
+    foundation::ns_url_response::CLASSES,
     foundation::ns_user_defaults::CLASSES,
     foundation::ns_value::CLASSES,
     media_player::movie_player::CLASSES,
@@ -55,6 +63,7 @@ pub const CLASS_LISTS: &[super::ClassExports] = &[
     uikit::ui_nib::CLASSES,
     uikit::ui_responder::CLASSES,
     uikit::ui_screen::CLASSES,
+    uikit::ui_slider::CLASSES,
     uikit::ui_text_field::CLASSES,
     uikit::ui_touch::CLASSES,
     uikit::ui_view::CLASSES,
@@ -17,6 +17,7 @@ use crate::gles::{create_gles1_ctx, GLES};
 use crate::image::Image;
 use crate::matrix::Matrix;
 use crate::options::Options;
+use sdl2::keyboard::Keycode;
 use sdl2::mouse::MouseButton;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::surface::Surface;
@@ -81,6 +82,10 @@ pub enum Event {
     TouchDown((f32, f32)),
     TouchMove((f32, f32)),
     TouchUp((f32, f32)),
+    /// Text typed on the host keyboard, while text input is enabled.
+    TextInput(String),
+    /// Backspace pressed on the host keyboard, while text input is enabled.
+    TextBackspace,
 }
 
 pub enum GLVersion {
@@ -220,6 +225,9 @@ impl Window {
         // the idle timer that triggers sleep by default, so we turn it back on
         // here, and then the app can disable it if it wants to.
         video_ctx.enable_screen_saver();
+        // SDL enables text input by default on some platforms, but we only want
+        // it while the app is editing text (see [Self::start_text_input]).
+        video_ctx.text_input().stop();
 
         let scale_hack = options.scale_hack;
         // TODO: some apps specify their orientation in Info.plist, we could use
@@ -408,6 +416,11 @@ impl Window {
                     mouse_btn: MouseButton::Left,
                     ..
                 } => Event::TouchUp(transform_input_coords(self, (x as f32, y as f32), false)),
+                E::TextInput { text, .. } => Event::TextInput(text),
+                E::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } if self.video_ctx.text_input().is_active() => Event::TextBackspace,
                 E::ControllerDeviceAdded { which, .. } => {
                     self.controller_added(which);
                     continue;
@@ -929,6 +942,16 @@ impl Window {
             false => self.video_ctx.disable_screen_saver(),
         }
     }
+
+    /// Start sending [Event::TextInput] and [Event::TextBackspace] events for
+    /// host keyboard input, and show the host's on-screen keyboard if it has
+    /// one. Used for text editing in the app.
+    pub fn start_text_input(&mut self) {
+        self.video_ctx.text_input().start();
+    }
+    pub fn stop_text_input(&mut self) {
+        self.video_ctx.text_input().stop();
+    }
 }
 
 pub fn open_url(url: &str) {
@@ -9,6 +9,8 @@ This code supposed to be compiled with iPhone SDK and Xcode 3.1 Developer Tools
 for Mac OS X v10.5
 */
 #include <errno.h>
+#include <math.h>
+#include <semaphore.h>
 #include <stdarg.h>
 #include <stdio.h>
 #include <stdlib.h>
@@ -16,6 +18,7 @@ for Mac OS X v10.5
 
 #include <CoreFoundation/CoreFoundation.h>
 #include <Foundation/Foundation.h>
+#include <UIKit/UIKit.h>
 
 int int_compar(const void *a, const void *b) { return *(int *)a - *(int *)b; }
 
@@ -192,6 +195,647 @@ int test_eof() {
   return strcmp(buf, "WAFFLE\n");
 }
 
+int test_NSThread_sleep() {
+  // Degenerate intervals must return promptly instead of crashing.
+  CFAbsoluteTime start = CFAbsoluteTimeGetCurrent();
+  [NSThread sleepForTimeInterval:-1.0];
+  [NSThread sleepForTimeInterval:NAN];
+  [NSThread sleepForTimeInterval:0.0];
+  if (CFAbsoluteTimeGetCurrent() - start > 0.5)
+    return -1;
+
+  start = CFAbsoluteTimeGetCurrent();
+  [NSThread sleepForTimeInterval:0.05];
+  CFAbsoluteTime elapsed = CFAbsoluteTimeGetCurrent() - start;
+  if (elapsed < 0.05 || elapsed > 1.0)
+    return -1;
+  return 0;
+}
+
+@interface ThreadTestTarget : NSObject {
+@public
+  volatile BOOL ran;
+  id receivedObject;
+  BOOL wasMainThread;
+  BOOL currentThreadWasMainThread;
+}
+@end
+@implementation ThreadTestTarget
+- (void)run:(id)object {
+  receivedObject = object;
+  wasMainThread = [NSThread isMainThread];
+  currentThreadWasMainThread = [[NSThread currentThread] isMainThread];
+  ran = YES;
+}
+@end
+
+@interface MainOverridingThread : NSThread {
+@public
+  volatile BOOL ran;
+}
+@end
+@implementation MainOverridingThread
+- (void)main {
+  ran = YES;
+}
+@end
+
+// Give another thread up to a second to finish.
+void wait_until_finished(NSThread *thread) {
+  for (int i = 0; i < 100 && ![thread isFinished]; i++)
+    [NSThread sleepForTimeInterval:0.01];
+}
+
+int test_NSThread_start() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  ThreadTestTarget *target = [[[ThreadTestTarget alloc] init] autorelease];
+  NSThread *thread = [[[NSThread alloc] initWithTarget:target
+                                              selector:@selector(run:)
+                                                object:@"arg"] autorelease];
+  if ([thread isExecuting] || [thread isFinished])
+    res = -1;
+  [thread start];
+  wait_until_finished(thread);
+  if (![thread isFinished] || [thread isExecuting] || !target->ran ||
+      ![target->receivedObject isEqualToString:@"arg"])
+    res = -1;
+  // Subclasses can override -main instead of using a target and selector.
+  MainOverridingThread *subclassed =
+      [[[MainOverridingThread alloc] init] autorelease];
+  [subclassed start];
+  wait_until_finished(subclassed);
+  if (![subclassed isFinished] || !subclassed->ran)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSThread_detach() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  ThreadTestTarget *target = [[[ThreadTestTarget alloc] init] autorelease];
+  [NSThread detachNewThreadSelector:@selector(run:)
+                           toTarget:target
+                         withObject:@"detached"];
+  // The flag is set by the other thread.
+  for (int i = 0; i < 100 && !target->ran; i++)
+    [NSThread sleepForTimeInterval:0.01];
+  if (!target->ran || ![target->receivedObject isEqualToString:@"detached"])
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSThread_isMainThread() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  if (![NSThread isMainThread] || ![[NSThread currentThread] isMainThread] ||
+      [NSThread currentThread] != [NSThread mainThread])
+    res = -1;
+  ThreadTestTarget *target = [[[ThreadTestTarget alloc] init] autorelease];
+  target->wasMainThread = YES;
+  target->currentThreadWasMainThread = YES;
+  [NSThread detachNewThreadSelector:@selector(run:)
+                           toTarget:target
+                         withObject:nil];
+  for (int i = 0; i < 100 && !target->ran; i++)
+    [NSThread sleepForTimeInterval:0.01];
+  if (!target->ran || target->wasMainThread ||
+      target->currentThreadWasMainThread)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+@interface SemaphoreWaiter : NSObject {
+@public
+  sem_t *sem;
+  NSThread *threadBeforeWait;
+  NSThread *threadAfterWait;
+}
+@end
+@implementation SemaphoreWaiter
+- (void)waitOnSemaphore:(id)unused {
+  threadBeforeWait = [NSThread currentThread];
+  sem_wait(sem);
+  threadAfterWait = [NSThread currentThread];
+}
+@end
+
+int test_NSThread_semaphoreWaiter() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  sem_t sem;
+  if (sem_init(&sem, 0, 0) != 0) {
+    [pool release];
+    return -1;
+  }
+  SemaphoreWaiter *waiter = [[[SemaphoreWaiter alloc] init] autorelease];
+  waiter->sem = &sem;
+  NSThread *thread =
+      [[[NSThread alloc] initWithTarget:waiter
+                               selector:@selector(waitOnSemaphore:)
+                                 object:nil] autorelease];
+  [thread start];
+  // Let the other thread block in sem_wait(), then wake it up. It must still
+  // be the same NSThread afterwards.
+  for (int i = 0; i < 100 && waiter->threadBeforeWait == nil; i++)
+    [NSThread sleepForTimeInterval:0.01];
+  [NSThread sleepForTimeInterval:0.01];
+  sem_post(&sem);
+  wait_until_finished(thread);
+  if (![thread isFinished] || waiter->threadBeforeWait != thread ||
+      waiter->threadAfterWait != thread)
+    res = -1;
+  sem_destroy(&sem);
+  [pool release];
+  return res;
+}
+
+int test_NSThread_name() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  NSThread *thread = [[[NSThread alloc] init] autorelease];
+  if ([thread name] != nil)
+    res = -1;
+  [thread setName:@"worker"];
+  if (![[thread name] isEqualToString:@"worker"])
+    res = -1;
+  [thread setName:nil];
+  if ([thread name] != nil)
+    res = -1;
+  // The current thread's object is the same each time, so it keeps its name.
+  [[NSThread currentThread] setName:@"main"];
+  if (![[[NSThread currentThread] name] isEqualToString:@"main"])
+    res = -1;
+  [[NSThread currentThread] setName:nil];
+  [pool release];
+  return res;
+}
+
+int test_NSNumber_copy() {
+  // Numbers are immutable, so copying one gives back the same object.
+  NSNumber *num = [[NSNumber alloc] initWithInteger:42];
+  NSNumber *copy = [num copy];
+  int res = (copy == num && [copy integerValue] == 42) ? 0 : -1;
+  [copy release];
+  [num release];
+  return res;
+}
+
+int test_NSNumber_stringValue() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  double doubles[] = {1e300, 0.1 + 0.2, 0.5, -3.0, 1e-5, 1.0 / 3.0};
+  for (size_t i = 0; i < sizeof(doubles) / sizeof(doubles[0]); i++) {
+    NSNumber *num = [NSNumber numberWithDouble:doubles[i]];
+    char buf[64];
+    sprintf(buf, "%.16g", doubles[i]);
+    NSString *expected = [NSString stringWithUTF8String:buf];
+    // The %@ case takes a shortcut that mustn't change the result.
+    if (![[num stringValue] isEqualToString:expected] ||
+        ![[NSString stringWithFormat:@"%@", num] isEqualToString:expected])
+      res = -1;
+  }
+  NSNumber *num = [NSNumber numberWithInt:-42];
+  if (![[num stringValue] isEqualToString:@"-42"] ||
+      ![[NSString stringWithFormat:@"%@", num] isEqualToString:@"-42"])
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_CFData() {
+  int res = 0;
+  // The bytes are owned by the caller, so they must not be freed.
+  UInt8 bytes[4] = {1, 2, 3, 4};
+  CFDataRef data =
+      CFDataCreateWithBytesNoCopy(NULL, bytes, 4, kCFAllocatorNull);
+  if (CFDataGetLength(data) != 4 ||
+      memcmp(CFDataGetBytePtr(data), bytes, 4) != 0)
+    res = -1;
+  CFRelease(data);
+
+  CFMutableDataRef mutable = CFDataCreateMutable(NULL, 0);
+  CFDataAppendBytes(mutable, bytes, 2);
+  CFDataAppendBytes(mutable, bytes + 2, 2);
+  if (CFDataGetLength(mutable) != 4 ||
+      memcmp(CFDataGetBytePtr(mutable), bytes, 4) != 0)
+    res = -1;
+  CFRelease(mutable);
+  return res;
+}
+
+// Counts retain and release messages, to check memory management.
+@interface RetainCounter : NSObject {
+@public
+  int retains;
+  int releases;
+}
+@end
+@implementation RetainCounter
+- (id)retain {
+  retains++;
+  return [super retain];
+}
+- (oneway void)release {
+  releases++;
+  [super release];
+}
+@end
+
+int test_UIButton_titleColor() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UIButton *button = [UIButton buttonWithType:UIButtonTypeCustom];
+  // Any object will do for checking the storage.
+  RetainCounter *red = [[RetainCounter alloc] init];
+  RetainCounter *blue = [[RetainCounter alloc] init];
+  [button setTitleColor:(UIColor *)red forState:UIControlStateNormal];
+  [button setTitleColor:(UIColor *)blue forState:UIControlStateSelected];
+  if (red->retains != 1 || blue->retains != 1)
+    res = -1;
+  if ([button titleColorForState:UIControlStateNormal] != (UIColor *)red ||
+      [button titleColorForState:UIControlStateSelected] != (UIColor *)blue)
+    res = -1;
+  // No color for this state, so it falls back to the normal one.
+  if ([button titleColorForState:UIControlStateHighlighted] != (UIColor *)red)
+    res = -1;
+  // Replacing a color releases the old one.
+  [button setTitleColor:(UIColor *)blue forState:UIControlStateNormal];
+  if (red->releases != 1 || blue->retains != 2)
+    res = -1;
+
+  // The title label shows the title for the current state.
+  [button setTitle:@"normal" forState:UIControlStateNormal];
+  [button setTitle:@"selected" forState:UIControlStateSelected];
+  UILabel *label = [button titleLabel];
+  if (label != [button titleLabel])
+    res = -1;
+  if (![[label text] isEqualToString:@"normal"])
+    res = -1;
+  [button setSelected:YES];
+  if (![[label text] isEqualToString:@"selected"])
+    res = -1;
+
+  [red release];
+  [blue release];
+  [pool release];
+  return res;
+}
+
+int test_UIButton_currentTitle() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UIButton *button = [UIButton buttonWithType:UIButtonTypeCustom];
+  [button setTitle:@"normal" forState:UIControlStateNormal];
+  [button setTitle:@"highlighted" forState:UIControlStateHighlighted];
+  if (![[button currentTitle] isEqualToString:@"normal"])
+    res = -1;
+  [button setHighlighted:YES];
+  if (![[button currentTitle] isEqualToString:@"highlighted"])
+    res = -1;
+  [button setHighlighted:NO];
+  if (![[button currentTitle] isEqualToString:@"normal"])
+    res = -1;
+  // There's no title for highlighted and selected together, so this falls
+  // back to the normal title.
+  [button setSelected:YES];
+  [button setHighlighted:YES];
+  if (![[button currentTitle] isEqualToString:@"normal"])
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_UITextView_firstResponder() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UITextView *view =
+      [[[UITextView alloc] initWithFrame:CGRectMake(0, 0, 100, 100)]
+          autorelease];
+  [view setEditable:NO];
+  if ([view becomeFirstResponder] || [view isFirstResponder])
+    res = -1;
+  [view setEditable:YES];
+  if (![view becomeFirstResponder] || ![view isFirstResponder])
+    res = -1;
+  if (![view resignFirstResponder] || [view isFirstResponder])
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_UITextView_fontAndColor() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UITextView *view =
+      [[[UITextView alloc] initWithFrame:CGRectMake(0, 0, 100, 100)]
+          autorelease];
+  [view setText:@"Hello"];
+  UIFont *font = [UIFont boldSystemFontOfSize:20];
+  [view setFont:font];
+  if ([view font] != font)
+    res = -1;
+  UIColor *color = [UIColor redColor];
+  [view setTextColor:color];
+  if ([view textColor] != color)
+    res = -1;
+  // Changing the font or colour redraws the text.
+  if ([[view layer] contents] == nil)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_UITextView_textAlignment() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UITextView *view =
+      [[[UITextView alloc] initWithFrame:CGRectMake(0, 0, 100, 100)]
+          autorelease];
+  if ([view textAlignment] != UITextAlignmentLeft)
+    res = -1;
+  [view setText:@"Hello"];
+  [view setTextAlignment:UITextAlignmentCenter];
+  if ([view textAlignment] != UITextAlignmentCenter)
+    res = -1;
+  [view setTextAlignment:UITextAlignmentRight];
+  if ([view textAlignment] != UITextAlignmentRight)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+@interface TextViewChangeCounter : NSObject <UITextViewDelegate> {
+@public
+  int changes;
+}
+@end
+@implementation TextViewChangeCounter
+- (void)textViewDidChange:(UITextView *)textView {
+  changes++;
+}
+@end
+
+int test_UITextView_didChange() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UITextView *view =
+      [[[UITextView alloc] initWithFrame:CGRectMake(0, 0, 100, 100)]
+          autorelease];
+  TextViewChangeCounter *counter =
+      [[[TextViewChangeCounter alloc] init] autorelease];
+  [view setDelegate:counter];
+  // Changes made by the app while not editing aren't reported.
+  [view setText:@"a"];
+  if (counter->changes != 0)
+    res = -1;
+  [view becomeFirstResponder];
+  [view setText:@"ab"];
+  if (counter->changes != 1)
+    res = -1;
+  [view insertText:@"c"];
+  if (counter->changes != 2 || ![[view text] isEqualToString:@"abc"])
+    res = -1;
+  [view resignFirstResponder];
+  [view setText:@"d"];
+  if (counter->changes != 2)
+    res = -1;
+  [view setDelegate:nil];
+  [pool release];
+  return res;
+}
+
+int test_UITextView_contentSize() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UITextView *view =
+      [[[UITextView alloc] initWithFrame:CGRectMake(0, 0, 100, 100)]
+          autorelease];
+  [view setText:@"one line"];
+  CGSize one_line = [view contentSize];
+  [view setText:@"one line\ntwo lines\nthree lines"];
+  CGSize three_lines = [view contentSize];
+  if (!(one_line.height > 0) || !(three_lines.height > one_line.height))
+    res = -1;
+  // The width is that of the view, and the text wraps to fit it.
+  if (three_lines.width != 100)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSURLRequest_URL() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  NSURL *url = [NSURL URLWithString:@"http://example.com/a"];
+  NSURLRequest *request = [NSURLRequest requestWithURL:url];
+  if ([request URL] != url)
+    res = -1;
+  NSURL *other_url = [NSURL URLWithString:@"http://example.com/b"];
+  NSMutableURLRequest *mutable_request =
+      [NSMutableURLRequest requestWithURL:url];
+  [mutable_request setURL:other_url];
+  if ([mutable_request URL] != other_url)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSURLRequest_POST() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  NSURL *url = [NSURL URLWithString:@"http://example.com/submit"];
+  NSMutableURLRequest *request = [NSMutableURLRequest requestWithURL:url];
+  if (![[request HTTPMethod] isEqualToString:@"GET"] ||
+      [request HTTPBody] != nil)
+    res = -1;
+  [request setHTTPMethod:@"POST"];
+  [request setHTTPBody:[NSData dataWithBytes:"a=1" length:3]];
+  [request setValue:@"application/x-www-form-urlencoded"
+      forHTTPHeaderField:@"Content-Type"];
+  if (![[request HTTPMethod] isEqualToString:@"POST"])
+    res = -1;
+  if ([[request HTTPBody] length] != 3 ||
+      memcmp([[request HTTPBody] bytes], "a=1", 3) != 0)
+    res = -1;
+  // Header field names are case-insensitive.
+  if (![[request valueForHTTPHeaderField:@"content-type"]
+          isEqualToString:@"application/x-www-form-urlencoded"])
+    res = -1;
+  [request setValue:nil forHTTPHeaderField:@"CONTENT-TYPE"];
+  if ([request valueForHTTPHeaderField:@"Content-Type"] != nil ||
+      [request allHTTPHeaderFields] != nil)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSURLRequest_mutableCopy() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  NSURL *url = [NSURL URLWithString:@"http://example.com/"];
+  NSMutableURLRequest *original = [NSMutableURLRequest requestWithURL:url];
+  [original setHTTPMethod:@"PUT"];
+  [original setValue:@"1" forHTTPHeaderField:@"X-Test"];
+  NSMutableURLRequest *copy = [[original mutableCopy] autorelease];
+  if (copy == original || ![[copy HTTPMethod] isEqualToString:@"PUT"] ||
+      ![[copy valueForHTTPHeaderField:@"X-Test"] isEqualToString:@"1"])
+    res = -1;
+  // Changing the copy doesn't change the original, or vice versa.
+  [copy setHTTPMethod:@"DELETE"];
+  [copy setValue:@"2" forHTTPHeaderField:@"X-Test"];
+  if (![[original HTTPMethod] isEqualToString:@"PUT"] ||
+      ![[original valueForHTTPHeaderField:@"X-Test"] isEqualToString:@"1"])
+    res = -1;
+  [original setTimeoutInterval:5];
+  if ([copy timeoutInterval] == 5)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSURLRequest_timeoutAndCachePolicy() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  NSURL *url = [NSURL URLWithString:@"http://example.com/"];
+  NSURLRequest *request = [NSURLRequest requestWithURL:url];
+  if ([request timeoutInterval] != 60 ||
+      [request cachePolicy] != NSURLRequestUseProtocolCachePolicy)
+    res = -1;
+  request = [NSURLRequest
+       requestWithURL:url
+          cachePolicy:NSURLRequestReloadIgnoringLocalCacheData
+      timeoutInterval:30];
+  if ([request timeoutInterval] != 30 ||
+      [request cachePolicy] != NSURLRequestReloadIgnoringLocalCacheData)
+    res = -1;
+  NSMutableURLRequest *mutable_request =
+      [NSMutableURLRequest requestWithURL:url];
+  [mutable_request setTimeoutInterval:10];
+  [mutable_request setCachePolicy:NSURLRequestReloadIgnoringLocalCacheData];
+  if ([mutable_request timeoutInterval] != 10 ||
+      [mutable_request cachePolicy] !=
+          NSURLRequestReloadIgnoringLocalCacheData)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSHTTPURLResponse_headers() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  NSURL *url = [NSURL URLWithString:@"http://example.com/"];
+  // Header field names are case-insensitive.
+  NSDictionary *headers = [NSDictionary
+      dictionaryWithObjectsAndKeys:@"text/html; charset=utf-8",
+                                   @"content-type", @"42", @"CONTENT-LENGTH",
+                                   nil];
+  NSHTTPURLResponse *response =
+      [[[NSHTTPURLResponse alloc] initWithURL:url
+                                   statusCode:200
+                                  HTTPVersion:@"HTTP/1.1"
+                                 headerFields:headers] autorelease];
+  if ([response statusCode] != 200 ||
+      ![[response MIMEType] isEqualToString:@"text/html"] ||
+      [response expectedContentLength] != 42)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+int test_NSData_contentsOfURL() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  NSString *path = [[[NSBundle mainBundle] resourcePath]
+      stringByAppendingPathComponent:@"uwu_folder/waffle.txt"];
+  NSURL *url = [[[NSURL alloc] initFileURLWithPath:path] autorelease];
+  NSData *data = [NSData dataWithContentsOfURL:url];
+  if (data == nil || [data length] != 7 ||
+      memcmp([data bytes], "WAFFLE\n", 7) != 0)
+    res = -1;
+  // A missing file gives nil and an error.
+  NSURL *missing_url = [[[NSURL alloc]
+      initFileURLWithPath:[path stringByAppendingString:@".missing"]]
+      autorelease];
+  NSError *error = nil;
+  if ([NSData dataWithContentsOfURL:missing_url options:0 error:&error] !=
+          nil ||
+      error == nil)
+    res = -1;
+  [pool release];
+  return res;
+}
+
+// Stands in for a UITouch, which apps can't create themselves.
+@interface FakeTouch : NSObject {
+@public
+  CGPoint location;
+}
+@end
+@implementation FakeTouch
+- (CGPoint)locationInView:(UIView *)view {
+  return location;
+}
+@end
+
+@interface ActionCounter : NSObject {
+@public
+  int actions;
+  id lastSender;
+}
+@end
+@implementation ActionCounter
+- (void)action:(id)sender {
+  actions++;
+  lastSender = sender;
+}
+@end
+
+int test_UIControl_touchUpAction() {
+  NSAutoreleasePool *pool = [[NSAutoreleasePool alloc] init];
+  int res = 0;
+  UIButton *button = [UIButton buttonWithType:UIButtonTypeCustom];
+  [button setFrame:CGRectMake(0, 0, 100, 100)];
+  ActionCounter *counter = [[[ActionCounter alloc] init] autorelease];
+  [button addTarget:counter
+                action:@selector(action:)
+      forControlEvents:UIControlEventTouchUpInside];
+  FakeTouch *touch = [[[FakeTouch alloc] init] autorelease];
+  touch->location = CGPointMake(50, 50);
+  NSSet *touches = [NSSet setWithObject:touch];
+  // Touching down doesn't send the touch-up action, but lifting does.
+  [button touchesBegan:touches withEvent:nil];
+  if (counter->actions != 0)
+    res = -1;
+  [button touchesEnded:touches withEvent:nil];
+  if (counter->actions != 1 || counter->lastSender != button)
+    res = -1;
+  // Lifting outside the control doesn't count as touch-up-inside.
+  touch->location = CGPointMake(150, 50);
+  [button touchesBegan:touches withEvent:nil];
+  [button touchesEnded:touches withEvent:nil];
+  if (counter->actions != 1)
+    res = -1;
+  // Neither does a disabled control, or one without the target any more.
+  touch->location = CGPointMake(50, 50);
+  [button setEnabled:NO];
+  [button touchesBegan:touches withEvent:nil];
+  [button touchesEnded:touches withEvent:nil];
+  [button setEnabled:YES];
+  [button removeTarget:counter
+                action:@selector(action:)
+      forControlEvents:UIControlEventTouchUpInside];
+  [button touchesBegan:touches withEvent:nil];
+  [button touchesEnded:touches withEvent:nil];
+  if (counter->actions != 1)
+    res = -1;
+  [pool release];
+  return res;
+}
+
 #define FUNC_DEF(func)                                                         \
   { &func, #func }
 struct {
@@ -202,7 +846,29 @@ struct {
     FUNC_DEF(test_sscanf),  FUNC_DEF(test_errno),
     FUNC_DEF(test_realloc), FUNC_DEF(test_NSString_compare),
     FUNC_DEF(test_chdir),   FUNC_DEF(test_NSFileManager),
-    FUNC_DEF(test_eof),
+    FUNC_DEF(test_eof),     FUNC_DEF(test_NSThread_sleep),
+    FUNC_DEF(test_NSThread_start),
+    FUNC_DEF(test_NSThread_detach),
+    FUNC_DEF(test_NSThread_isMainThread),
+    FUNC_DEF(test_NSThread_semaphoreWaiter),
+    FUNC_DEF(test_NSThread_name),
+    FUNC_DEF(test_NSNumber_copy),
+    FUNC_DEF(test_NSNumber_stringValue),
+    FUNC_DEF(test_CFData),
+    FUNC_DEF(test_UIButton_titleColor),
+    FUNC_DEF(test_UIButton_currentTitle),
+    FUNC_DEF(test_UITextView_firstResponder),
+    FUNC_DEF(test_UITextView_fontAndColor),
+    FUNC_DEF(test_UITextView_textAlignment),
+    FUNC_DEF(test_UITextView_didChange),
+    FUNC_DEF(test_UITextView_contentSize),
+    FUNC_DEF(test_NSURLRequest_URL),
+    FUNC_DEF(test_NSURLRequest_POST),
+    FUNC_DEF(test_NSURLRequest_mutableCopy),
+    FUNC_DEF(test_NSURLRequest_timeoutAndCachePolicy),
+    FUNC_DEF(test_NSHTTPURLResponse_headers),
+    FUNC_DEF(test_NSData_contentsOfURL),
+    FUNC_DEF(test_UIControl_touchUpAction),
 };
 
 int main(int argc, char *argv[]) {